@@ -0,0 +1,88 @@
+//! Scanning benchmarks for large scopes, run with `cargo bench`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use macro_scope::MacroScope;
+use proc_macro2::TokenStream;
+
+const ITEMS: usize = 5000;
+const MARKED_EVERY: usize = 100;
+const QUERIES: usize = 20;
+
+/// A scope with `2 * ITEMS` items, every [MARKED_EVERY]th struct is marked with `#[system]`
+fn source() -> TokenStream {
+    let mut source = String::from("mod scope {");
+    for i in 0..ITEMS {
+        if i % MARKED_EVERY == 0 {
+            source.push_str("#[system]");
+        }
+        source.push_str(&format!(
+            "#[derive(Debug, Clone)] pub struct S{i} {{ a: u32, b: Vec<String> }}
+            impl S{i} {{ pub fn get(&self) -> u32 {{ self.a + {i} }} }}"
+        ));
+    }
+    source.push('}');
+    source.parse().unwrap()
+}
+
+fn parse(c: &mut Criterion) {
+    let tokens = source();
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(20);
+
+    group.bench_function("eager", |b| {
+        b.iter(|| syn::parse2::<MacroScope>(tokens.clone()).unwrap())
+    });
+    group.bench_function("lazy", |b| {
+        b.iter(|| MacroScope::parse_lazy(tokens.clone()).unwrap())
+    });
+    group.bench_function("eager + scan", |b| {
+        b.iter(|| {
+            let scope: MacroScope = syn::parse2(tokens.clone()).unwrap();
+            scope.get_items_by_mark_prefix("system")
+        })
+    });
+    group.bench_function("lazy + scan", |b| {
+        b.iter(|| {
+            let scope = MacroScope::parse_lazy(tokens.clone()).unwrap();
+            scope.get_items_by_mark_prefix("system")
+        })
+    });
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let scope: MacroScope = syn::parse2(source()).unwrap();
+    let marks: Vec<String> = (0..QUERIES).map(|i| format!("mark_{i}")).collect();
+    let mut group = c.benchmark_group("query");
+
+    group.bench_function("scan, no matches", |b| {
+        b.iter(|| scope.get_items_by_mark_prefix("unused"))
+    });
+    group.bench_function("20 queries without index", |b| {
+        b.iter(|| {
+            for mark in &marks {
+                scope.get_items_by_mark_prefix(mark);
+            }
+        })
+    });
+    group.bench_function("build index + 20 queries", |b| {
+        b.iter(|| {
+            scope.build_index();
+            for mark in &marks {
+                scope.get_items_by_mark_prefix(mark);
+            }
+        })
+    });
+    scope.build_index();
+    group.bench_function("20 queries with index", |b| {
+        b.iter(|| {
+            for mark in &marks {
+                scope.get_items_by_mark_prefix(mark);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, query);
+criterion_main!(benches);
@@ -0,0 +1,214 @@
+//! Machine readable export of what a scope sees, for docs generators, IDE plugins and the like
+//!
+//! The exported types implement `Serialize` with the `serde` feature, which also adds
+//! [MacroScope::analysis_json]
+
+use indexmap::IndexMap;
+use quote::ToTokens;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use syn::{Expr, ExprLit, Item, Lit};
+
+use crate::{
+    find_attribute, item_attrs, item_ident, lazy, ItemKind, MacroScope, MetaTree, SourceLocation,
+};
+
+/// What a scope sees, see [MacroScope::analysis]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ScopeAnalysis {
+    /// The name of the scope module, [None] for a scope without a module
+    pub module: Option<String>,
+    pub items: Vec<ItemAnalysis>,
+    /// The impl blocks of the scope, relating types and traits
+    pub impls: Vec<ImplAnalysis>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ItemAnalysis {
+    pub ident: Option<String>,
+    /// The keyword of the kind of the item, see [ItemKind::name]
+    pub kind: &'static str,
+    pub attributes: Vec<AttributeAnalysis>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct AttributeAnalysis {
+    /// The path of the attribute as written, e.g. `ecs::component`
+    pub name: String,
+    /// Whether the attribute matches a registered mark
+    pub mark: bool,
+    /// The arguments of the attribute, [None] if they can't be parsed as a [MetaTree]
+    pub args: Option<ArgValue>,
+    /// Where the attribute was written, see [SourceLocation]
+    pub location: String,
+}
+
+/// An impl block of the scope
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ImplAnalysis {
+    /// The type the block is implemented for
+    #[cfg_attr(feature = "serde", serde(rename = "self"))]
+    pub self_ty: String,
+    /// The implemented trait, [None] for inherent impls
+    #[cfg_attr(feature = "serde", serde(rename = "trait"))]
+    pub trait_path: Option<String>,
+}
+
+/// The value of an attribute argument
+///
+/// Bare paths are `true`, lists are objects keyed by the names of their children and unnamed
+/// children are keyed by their position. Children sharing a name like `index(a), index(b)` are
+/// collected into an array under that name. Serialized without the variant names, so the JSON
+/// looks like the arguments
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(untagged))]
+pub enum ArgValue {
+    Bool(bool),
+    Int(u128),
+    Float(f64),
+    /// String literals, other literals and expressions as written and numbers that don't fit
+    /// the other variants, like `1e999`
+    Str(String),
+    Array(Vec<ArgValue>),
+    Object(IndexMap<String, ArgValue>),
+}
+
+impl ArgValue {
+    fn from_lit(lit: &Lit) -> Self {
+        match lit {
+            Lit::Str(s) => Self::Str(s.value()),
+            Lit::Bool(b) => Self::Bool(b.value),
+            Lit::Int(i) => Self::number(i.base10_digits()),
+            Lit::Float(f) => Self::number(f.base10_digits()),
+            lit => Self::Str(lit.to_token_stream().to_string()),
+        }
+    }
+
+    /// Normalises a numeric literal, e.g. `01` to `1` and `1.` to `1.0`
+    fn number(digits: &str) -> Self {
+        if let Ok(int) = digits.parse() {
+            return Self::Int(int);
+        }
+        match digits.parse::<f64>() {
+            Ok(float) if float.is_finite() => Self::Float(float),
+            _ => Self::Str(digits.to_string()),
+        }
+    }
+
+    fn from_tree(tree: &MetaTree) -> Self {
+        match tree {
+            MetaTree::Path(_) => Self::Bool(true),
+            MetaTree::Lit(lit) => Self::from_lit(lit),
+            MetaTree::NameValue(_, expr) => match &**expr {
+                Expr::Lit(ExprLit { lit, .. }) => Self::from_lit(lit),
+                expr => Self::Str(expr.to_token_stream().to_string()),
+            },
+            MetaTree::List(_, children) => {
+                let mut keys: IndexMap<String, Vec<&MetaTree>> = IndexMap::new();
+                for (i, child) in children.iter().enumerate() {
+                    let key = match child.path() {
+                        Some(path) => path.to_token_stream().to_string().replace(' ', ""),
+                        None => i.to_string(),
+                    };
+                    keys.entry(key).or_default().push(child);
+                }
+
+                let object = keys.into_iter().map(|(key, values)| {
+                    let value = match values.as_slice() {
+                        [child] => Self::from_tree(child),
+                        values => Self::Array(values.iter().map(|c| Self::from_tree(c)).collect()),
+                    };
+                    (key, value)
+                });
+                Self::Object(object.collect())
+            }
+        }
+    }
+}
+
+impl MacroScope {
+    /// Returns the items of the scope with their kinds, attributes and locations, together with
+    /// the impl blocks relating types and traits
+    ///
+    /// Attributes matching a registered mark are flagged, call this before [MacroScope::expand],
+    /// which removes the marks
+    ///
+    /// ```
+    /// # use macro_scope::{ArgValue, MacroScope};
+    /// let scope: MacroScope = "mod scope { #[system(order = 1)] fn a() {} }".parse().unwrap();
+    /// let analysis = scope.analysis();
+    /// let ArgValue::Object(args) = analysis.items[0].attributes[0].args.as_ref().unwrap() else {
+    ///     unreachable!()
+    /// };
+    /// assert_eq!(args["order"], ArgValue::Int(1));
+    /// ```
+    pub fn analysis(&self) -> ScopeAnalysis {
+        let items = self.scanned_items();
+        for item in &items {
+            // unparsable items are exported as verbatim items with their leading attributes
+            let _ = lazy::parse(item);
+        }
+        let marks: Vec<_> = self.marks().collect();
+
+        let analyze = |item: &Item| {
+            let leading;
+            let attrs = match item {
+                Item::Verbatim(tokens) => {
+                    leading = lazy::leading_attrs(tokens);
+                    &leading
+                }
+                item => item_attrs(item).unwrap_or_default(),
+            };
+
+            let attributes = attrs.iter().map(|attr| AttributeAnalysis {
+                name: attr.path().to_token_stream().to_string().replace(' ', ""),
+                mark: marks.iter().any(|mark| {
+                    find_attribute(std::slice::from_ref(attr), mark, self.config.match_mode)
+                        .is_some()
+                }),
+                args: MetaTree::from_attribute(attr)
+                    .ok()
+                    .map(|tree| ArgValue::from_tree(&tree)),
+                location: SourceLocation::of(syn::spanned::Spanned::span(attr)).to_string(),
+            });
+            ItemAnalysis {
+                ident: item_ident(item).map(ToString::to_string),
+                kind: ItemKind::of(item).name(),
+                attributes: attributes.collect(),
+            }
+        };
+
+        let impls = items.iter().filter_map(|item| match &*item.borrow() {
+            Item::Impl(item) => Some(ImplAnalysis {
+                self_ty: item.self_ty.to_token_stream().to_string(),
+                trait_path: item
+                    .trait_
+                    .as_ref()
+                    .map(|(_, path, _)| path.to_token_stream().to_string()),
+            }),
+            _ => None,
+        });
+
+        ScopeAnalysis {
+            module: self.module.as_ref().map(|module| module.ident.to_string()),
+            items: items.iter().map(|item| analyze(&item.borrow())).collect(),
+            impls: impls.collect(),
+        }
+    }
+
+    /// Returns the [analysis](MacroScope::analysis) of the scope as JSON
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// let scope: MacroScope = "mod scope { #[system(order = 1)] fn a() {} }".parse().unwrap();
+    /// assert!(scope.analysis_json().contains(r#""args":{"order":1}"#));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn analysis_json(&self) -> String {
+        serde_json::to_string(&self.analysis()).expect("the analysis is valid JSON")
+    }
+}
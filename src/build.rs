@@ -0,0 +1,99 @@
+//! Scanning source files from a build script
+//!
+//! Proc macros only see the tokens they are applied to, build scripts can read the whole crate.
+//! The scanned files become a single [MacroScope] without a module shell, so the same mark
+//! queries and templates work across files:
+//!
+//! ```no_run
+//! let scope = macro_scope::build::scan("src").unwrap();
+//! let systems = scope.get_marked("system");
+//! # let generated = proc_macro2::TokenStream::new();
+//! macro_scope::build::write_out("systems.rs", &generated).unwrap();
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::{Span, TokenStream};
+
+use crate::{dump, MacroScope};
+
+fn io_error(path: &Path, e: std::io::Error) -> syn::Error {
+    syn::Error::new(
+        Span::call_site(),
+        format!("failed to access {}: {e}", path.display()),
+    )
+}
+
+/// Collects all `.rs` files below `path` in a stable order
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> syn::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|e| e.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| io_error(path, e))?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "rs") {
+            collect_files(&entry, files)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads and parses a `.rs` file or all `.rs` files below a directory into a single scope
+///
+/// Prints `cargo:rerun-if-changed` for every file read, so the build script reruns when one of
+/// them changes. Parse errors are prefixed with the file and position they occurred at
+pub fn scan(path: impl AsRef<Path>) -> syn::Result<MacroScope> {
+    let mut files = Vec::new();
+    collect_files(path.as_ref(), &mut files)?;
+
+    let mut items = Vec::new();
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+
+        let source = fs::read_to_string(&file).map_err(|e| io_error(&file, e))?;
+        let parsed = syn::parse_file(&source).map_err(|e| {
+            let start = e.span().start();
+            let message = format!(
+                "{}:{}:{}: {e}",
+                file.display(),
+                start.line,
+                start.column + 1
+            );
+            syn::Error::new(e.span(), message)
+        })?;
+
+        items.extend(parsed.items);
+    }
+
+    Ok(MacroScope::from_items(items))
+}
+
+/// Writes formatted `tokens` to `file_name` in `OUT_DIR` and returns the full path
+///
+/// The file can be included with `include!(concat!(env!("OUT_DIR"), "/<file_name>"))`
+pub fn write_out(file_name: &str, tokens: &TokenStream) -> syn::Result<PathBuf> {
+    let dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "`OUT_DIR` is not set, `write_out` can only be used from a build script",
+        )
+    })?;
+
+    let path = Path::new(&dir).join(file_name);
+    fs::write(&path, dump::pretty(tokens)).map_err(|e| io_error(&path, e))?;
+    Ok(path)
+}
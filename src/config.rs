@@ -0,0 +1,257 @@
+#[cfg(feature = "full")]
+use proc_macro2::TokenStream;
+#[cfg(feature = "full")]
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    punctuated::Punctuated,
+    Attribute, Token,
+};
+
+#[cfg(feature = "full")]
+use crate::{diagnostics, ItemKind, MetaTree, ScanSet, Selector};
+
+/// How the name of an attribute is compared to a mark
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The attribute name contains the mark, e.g. `system` matches `#[render_system]`
+    #[default]
+    Contains,
+    /// The attribute name starts with the mark, e.g. `system` matches `#[system_render]`
+    Prefix,
+    /// The attribute name is exactly the mark
+    Exact,
+}
+
+impl MatchMode {
+    pub fn matches(&self, name: &str, mark: &str) -> bool {
+        match self {
+            Self::Contains => name.contains(mark),
+            Self::Prefix => name.starts_with(mark),
+            Self::Exact => name == mark,
+        }
+    }
+}
+
+/// What happens to a matched mark on the scanned item
+#[cfg(feature = "full")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MarkPolicy {
+    /// Remove the mark from the item
+    #[default]
+    Remove,
+    /// Leave the mark on the item
+    Keep,
+    /// Replace the mark with another attribute, e.g. `#[internal]` with `#[doc(hidden)]`
+    ReplaceWith(Box<Attribute>),
+}
+
+/// What happens when the scope is a module without a body like `mod foo;`, which has no items to
+/// scan
+#[cfg(feature = "full")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingBody {
+    /// Emit the module declaration unchanged
+    #[default]
+    Allow,
+    /// Emit the module declaration together with a compiler warning
+    Warn,
+    /// Fail with an error at the module while parsing
+    Error,
+}
+
+/// Controls how a [MacroScope](crate::MacroScope) is parsed and scanned
+///
+/// The defaults match the behavior of parsing a scope with [syn::parse2]. End users can override
+/// options from the arguments of the outer attribute macro, see [ScopeConfig::apply_args]
+#[cfg(feature = "full")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopeConfig {
+    /// How attribute names are compared to marks
+    pub match_mode: MatchMode,
+    /// Also scan the items of nested inline modules, only has an effect while parsing
+    ///
+    /// Nested modules marked with `#[macro_scope(skip)]` are not scanned, the flag is removed from
+    /// the emitted module with or without this option
+    pub recursive: bool,
+    /// How many levels of nested modules are scanned with [ScopeConfig::recursive], all of them
+    /// if [None]
+    pub max_depth: Option<usize>,
+    /// Only parse items once a mark is found on them, see
+    /// [MacroScope::parse_lazy](crate::MacroScope::parse_lazy)
+    pub lazy: bool,
+    /// Keep items that syn fails to parse as [syn::Item::Verbatim] tokens instead of failing
+    /// to parse the scope, only has an effect while parsing
+    ///
+    /// The leading attributes of the tokens are still scanned for marks as
+    /// [ItemKind::Verbatim], the compiler reports the syntax error at the emitted tokens. Lazily
+    /// parsed scopes are always lenient
+    pub lenient: bool,
+    /// The item kinds that are scanned for marks
+    pub scan_set: ScanSet,
+    /// How matched marks are handled on the scanned items
+    pub mark_policy: MarkPolicy,
+    /// Report attributes that look like misspelled marks, marks on item kinds outside of
+    /// [ScopeConfig::scan_set] and generated references to private items as errors during
+    /// [MacroScope::expand](crate::MacroScope::expand)
+    pub strict: bool,
+    /// Marks on fields and variants that are removed once all templates ran, see
+    /// [MacroScope::strip_inner_marks](crate::MacroScope::strip_inner_marks)
+    pub inner_marks: Vec<String>,
+    /// Marks on nested modules that are moved onto the items inside of them while parsing, see
+    /// [MacroScope::inherit_marks](crate::MacroScope::inherit_marks)
+    pub inherit_marks: Vec<String>,
+    /// How modules without a body are handled
+    pub missing_body: MissingBody,
+    /// Items chosen by the end user, see [MacroScope::selected](crate::MacroScope::selected)
+    pub select: Option<Selector>,
+}
+
+#[cfg(feature = "full")]
+const KEYS: &[&str] = &[
+    "match_mode",
+    "recursive",
+    "max_depth",
+    "lazy",
+    "lenient",
+    "scan",
+    "mark_policy",
+    "replace_marks_with",
+    "strict",
+    "inner_marks",
+    "inherit_marks",
+    "missing_body",
+    "select",
+];
+
+#[cfg(feature = "full")]
+impl ScopeConfig {
+    /// Applies overrides written as attribute arguments, e.g. the arguments of
+    /// `#[my_scope(recursive, match_mode = "exact", scan = "struct, enum")]`
+    ///
+    /// Supported keys are `match_mode` (`"contains"`, `"prefix"`, `"exact"`), `recursive`,
+    /// `max_depth`, `lazy`, `lenient`, `strict`, `scan` (comma separated item kinds), `mark_policy`
+    /// (`"remove"`, `"keep"`), `replace_marks_with(attribute)`, `inner_marks` and `inherit_marks`
+    /// (comma separated marks), `missing_body` (`"allow"`, `"warn"`, `"error"`) and `select`
+    /// (a [Selector])
+    pub fn apply_args(&mut self, args: TokenStream) -> syn::Result<()> {
+        let args =
+            syn::parse::Parser::parse2(Punctuated::<MetaTree, Token![,]>::parse_terminated, args)?;
+
+        for arg in &args {
+            self.apply(arg)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply(&mut self, arg: &MetaTree) -> syn::Result<()> {
+        let name = arg.name().unwrap_or_default();
+
+        match name.as_str() {
+            "match_mode" => {
+                self.match_mode = match arg.expect_str()?.as_str() {
+                    "contains" => MatchMode::Contains,
+                    "prefix" => MatchMode::Prefix,
+                    "exact" => MatchMode::Exact,
+                    _ => {
+                        return Err(syn::Error::new(
+                            arg.span(),
+                            "expected one of \"contains\", \"prefix\" or \"exact\"",
+                        ))
+                    }
+                }
+            }
+            "recursive" => self.recursive = arg.expect_bool()?,
+            "max_depth" => self.max_depth = Some(arg.expect_int()?),
+            "lazy" => self.lazy = arg.expect_bool()?,
+            "lenient" => self.lenient = arg.expect_bool()?,
+            "strict" => self.strict = arg.expect_bool()?,
+            "scan" => {
+                let mut kinds = Vec::new();
+                for kind in arg.expect_str()?.split(',').map(str::trim) {
+                    match ItemKind::from_name(kind) {
+                        Some(kind) => kinds.push(kind),
+                        None => {
+                            return Err(syn::Error::new(
+                                arg.span(),
+                                format!("unknown item kind `{kind}`"),
+                            ))
+                        }
+                    }
+                }
+                self.scan_set = ScanSet::only(kinds);
+            }
+            "mark_policy" => {
+                self.mark_policy = match arg.expect_str()?.as_str() {
+                    "remove" => MarkPolicy::Remove,
+                    "keep" => MarkPolicy::Keep,
+                    _ => {
+                        return Err(syn::Error::new(
+                            arg.span(),
+                            "expected one of \"remove\" or \"keep\"",
+                        ))
+                    }
+                }
+            }
+            "inner_marks" => self.inner_marks = mark_list(arg)?,
+            "inherit_marks" => self.inherit_marks = mark_list(arg)?,
+            "missing_body" => {
+                self.missing_body = match arg.expect_str()?.as_str() {
+                    "allow" => MissingBody::Allow,
+                    "warn" => MissingBody::Warn,
+                    "error" => MissingBody::Error,
+                    _ => {
+                        return Err(syn::Error::new(
+                            arg.span(),
+                            "expected one of \"allow\", \"warn\" or \"error\"",
+                        ))
+                    }
+                }
+            }
+            "select" => {
+                let lit = arg.expect_lit()?;
+                self.select = Some(Selector::parse(&arg.expect_str()?, lit.span())?);
+            }
+            "replace_marks_with" => {
+                let [attr] = arg.expect_list()? else {
+                    let message =
+                        "expected a single attribute like `replace_marks_with(doc(hidden))`";
+                    return Err(syn::Error::new(arg.span(), message));
+                };
+                self.mark_policy = MarkPolicy::ReplaceWith(Box::new(parse_quote!(#[#attr])));
+            }
+            _ => {
+                let message = match diagnostics::suggest(&name, KEYS) {
+                    Some(key) => format!("unknown option `{name}`, did you mean `{key}`?"),
+                    None => format!("unknown option `{name}`"),
+                };
+                return Err(syn::Error::new(arg.span(), message));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a comma separated list of marks like `"a, b"`
+#[cfg(feature = "full")]
+fn mark_list(arg: &MetaTree) -> syn::Result<Vec<String>> {
+    Ok(arg
+        .expect_str()?
+        .split(',')
+        .map(str::trim)
+        .filter(|mark| !mark.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Parses the default config with the given overrides applied
+#[cfg(feature = "full")]
+impl Parse for ScopeConfig {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut config = Self::default();
+        config.apply_args(input.parse()?)?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,70 @@
+//! Conversions from [MarkedItem<Item>] to the specific item types
+
+use syn::{spanned::Spanned, Item};
+
+use crate::{MarkedItem, SharedMarkedItem};
+
+impl<T> MarkedItem<T> {
+    /// Returns the path of the mark as written, e.g. `component` or `a::component`
+    pub fn mark_name(&self) -> String {
+        let segments: Vec<_> = self
+            .mark
+            .path()
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect();
+        segments.join("::")
+    }
+
+    /// Error pointing at the mark, e.g. "`#[component]` can only be used on structs"
+    pub fn only_on(&self, kinds: &str) -> syn::Error {
+        syn::Error::new(
+            self.mark.span(),
+            format!("`#[{}]` can only be used on {kinds}", self.mark_name()),
+        )
+    }
+}
+
+macro_rules! impl_try_from {
+    ($($variant:ident($ty:ty) => $kinds:literal,)*) => {
+        $(
+            impl TryFrom<MarkedItem<Item>> for MarkedItem<$ty> {
+                type Error = syn::Error;
+
+                fn try_from(marked: MarkedItem<Item>) -> syn::Result<Self> {
+                    let (item, marked) = marked.split();
+                    match item {
+                        Item::$variant(item) => Ok(marked.map(|_| item)),
+                        _ => Err(marked.only_on($kinds)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from! {
+    Const(syn::ItemConst) => "consts",
+    Enum(syn::ItemEnum) => "enums",
+    ExternCrate(syn::ItemExternCrate) => "extern crates",
+    Fn(syn::ItemFn) => "functions",
+    ForeignMod(syn::ItemForeignMod) => "extern blocks",
+    Impl(syn::ItemImpl) => "impl blocks",
+    Macro(syn::ItemMacro) => "macro invocations",
+    Mod(syn::ItemMod) => "modules",
+    Static(syn::ItemStatic) => "statics",
+    Struct(syn::ItemStruct) => "structs",
+    Trait(syn::ItemTrait) => "traits",
+    TraitAlias(syn::ItemTraitAlias) => "trait aliases",
+    Type(syn::ItemType) => "type aliases",
+    Union(syn::ItemUnion) => "unions",
+    Use(syn::ItemUse) => "use declarations",
+}
+
+impl SharedMarkedItem<Item> {
+    /// Clones the shared item, e.g. to convert it with [TryFrom]
+    pub fn to_owned_item(&self) -> MarkedItem<Item> {
+        self.clone().map(|item| item.borrow().clone())
+    }
+}
@@ -0,0 +1,400 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use proc_macro2::{Span, TokenStream};
+use syn::{spanned::Spanned, Item};
+
+use crate::{
+    find_attribute, item_attrs, item_ident, lazy, scanned_attrs, ItemKind, MacroScope, ScopeConfig,
+    SharedMarkedItem,
+};
+
+/// Accumulates errors so every problem in a scope is reported in a single compilation
+///
+/// Errors from other sources are accumulated with [Extend] and [FromIterator], recorded errors
+/// are handed on one by one with [Diagnostics::emit_each]. The `manyhow` and `proc-macro-error`
+/// features add adapters that report them through these crates
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    error: Option<syn::Error>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, error: syn::Error) {
+        match &mut self.error {
+            Some(e) => e.combine(error),
+            None => self.error = Some(error),
+        }
+    }
+
+    /// Records an error at `span`, but keeps going
+    pub fn emit(&mut self, span: Span, message: impl Display) {
+        self.push(syn::Error::new(span, message));
+    }
+
+    /// Records the error of `result` and returns its value otherwise
+    pub fn check<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.push(e);
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns all recorded errors combined
+    pub fn finish(self) -> syn::Result<()> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a final error and returns everything recorded so far, for cases where
+    /// processing can't continue
+    pub fn abort(mut self, span: Span, message: impl Display) -> syn::Error {
+        self.emit(span, message);
+        self.error.unwrap()
+    }
+
+    /// Returns a single error spanning all of `items`, for problems that involve several items
+    /// at once like conflicting names
+    ///
+    /// The first item gets `message`, every further item a note pointing back to it, so the
+    /// compiler shows all of them in order. Items are pointed at by their name if they have one
+    pub fn related(items: &[SharedMarkedItem<Item>], message: impl Display) -> syn::Error {
+        let span = |marked: &SharedMarkedItem<Item>| match item_ident(&marked.item.borrow()) {
+            Some(ident) => ident.span(),
+            None => marked.mark.span(),
+        };
+
+        let Some((first, rest)) = items.split_first() else {
+            return syn::Error::new(Span::call_site(), message);
+        };
+
+        let mut error = syn::Error::new(span(first), &message);
+        for marked in rest {
+            error.combine(syn::Error::new(
+                span(marked),
+                format!("note: also involved in: {message}"),
+            ));
+        }
+        error
+    }
+
+    /// Calls `emit` with every recorded error in order, to report them through another
+    /// diagnostic crate, e.g. `|e| proc_macro_error::emit_error!(e.span(), e)`
+    pub fn emit_each(self, emit: impl FnMut(syn::Error)) {
+        self.into_iter().for_each(emit);
+    }
+
+    /// Returns a `compile_error!` invocation for every recorded error
+    pub fn to_compile_errors(&self) -> TokenStream {
+        match &self.error {
+            Some(e) => e.to_compile_error(),
+            None => TokenStream::new(),
+        }
+    }
+}
+
+#[cfg(feature = "manyhow")]
+impl Diagnostics {
+    /// Adds every recorded error to a manyhow [Emitter](manyhow::Emitter), which reports them
+    /// when the macro returns
+    pub fn emit_to_manyhow(self, emitter: &mut manyhow::Emitter) {
+        emitter.extend(self);
+    }
+
+    /// Returns all recorded errors as a single manyhow error, to stop the macro with `?`
+    pub fn finish_manyhow(self) -> manyhow::Result<()> {
+        self.finish().map_err(manyhow::Error::from)
+    }
+}
+
+#[cfg(feature = "proc-macro-error")]
+impl Diagnostics {
+    /// Emits every recorded error through proc-macro-error, which reports them when the
+    /// `#[proc_macro_error]` entry point returns
+    pub fn emit_proc_macro_error(self) {
+        self.into_iter().for_each(|e| {
+            proc_macro_error::Diagnostic::spanned(
+                e.span(),
+                proc_macro_error::Level::Error,
+                e.to_string(),
+            )
+            .emit()
+        });
+    }
+
+    /// Emits every recorded error through proc-macro-error and aborts the macro if there were
+    /// any, returns normally otherwise
+    pub fn abort_proc_macro_error(self) {
+        if !self.is_empty() {
+            self.emit_proc_macro_error();
+            proc_macro_error::abort_if_dirty();
+        }
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = syn::Error;
+    type IntoIter = std::vec::IntoIter<syn::Error>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let errors: Vec<_> = self.error.into_iter().flatten().collect();
+        errors.into_iter()
+    }
+}
+
+impl Extend<syn::Error> for Diagnostics {
+    fn extend<I: IntoIterator<Item = syn::Error>>(&mut self, errors: I) {
+        errors.into_iter().for_each(|e| self.push(e));
+    }
+}
+
+impl FromIterator<syn::Error> for Diagnostics {
+    fn from_iter<I: IntoIterator<Item = syn::Error>>(errors: I) -> Self {
+        let mut diagnostics = Self::new();
+        diagnostics.extend(errors);
+        diagnostics
+    }
+}
+
+/// Maximum edit distance for an attribute to be considered a misspelled mark
+const MAX_DISTANCE: usize = 2;
+
+/// Attributes built into the language, never reported as misspelled marks
+const BUILTIN_ATTRIBUTES: &[&str] = &[
+    "allow",
+    "automatically_derived",
+    "cfg",
+    "cfg_attr",
+    "cold",
+    "collapse_debuginfo",
+    "crate_name",
+    "crate_type",
+    "debugger_visualizer",
+    "deny",
+    "deprecated",
+    "derive",
+    "doc",
+    "expect",
+    "export_name",
+    "feature",
+    "forbid",
+    "global_allocator",
+    "ignore",
+    "inline",
+    "instruction_set",
+    "link",
+    "link_name",
+    "link_ordinal",
+    "link_section",
+    "macro_export",
+    "macro_use",
+    "must_use",
+    "naked",
+    "no_builtins",
+    "no_implicit_prelude",
+    "no_link",
+    "no_main",
+    "no_mangle",
+    "no_std",
+    "non_exhaustive",
+    "panic_handler",
+    "path",
+    "proc_macro",
+    "proc_macro_attribute",
+    "proc_macro_derive",
+    "recursion_limit",
+    "repr",
+    "should_panic",
+    "target_feature",
+    "test",
+    "track_caller",
+    "type_length_limit",
+    "unsafe",
+    "used",
+    "warn",
+    "windows_subsystem",
+];
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+/// Returns the mark closest to `name` if it is within [MAX_DISTANCE]
+pub(crate) fn suggest<'a>(name: &str, marks: &[&'a str]) -> Option<&'a str> {
+    marks
+        .iter()
+        .map(|mark| (edit_distance(name, mark), *mark))
+        .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, mark)| mark)
+}
+
+/// Returns an error for every attribute that does not match any of the marks but is close enough
+/// to one of them to likely be a typo
+///
+/// Built-in attributes like `doc` or `test` and path-qualified attributes are never reported
+pub(crate) fn check_unknown_marks(
+    items: &[Rc<RefCell<Item>>],
+    marks: &[&str],
+    config: &ScopeConfig,
+) -> syn::Result<()> {
+    let mut diagnostics = Diagnostics::new();
+
+    for item in items {
+        let item = item.borrow();
+        let leading;
+        let attrs = match &*item {
+            Item::Verbatim(tokens) => {
+                leading = lazy::leading_attrs(tokens);
+                &leading
+            }
+            item => match scanned_attrs(item, &config.scan_set) {
+                Some(attrs) => attrs,
+                None => continue,
+            },
+        };
+
+        for attr in attrs {
+            // path-qualified attributes belong to other crates
+            let ident = match attr.path().get_ident() {
+                Some(ident) => ident.to_string(),
+                None => continue,
+            };
+            if BUILTIN_ATTRIBUTES.contains(&ident.as_str()) {
+                continue;
+            }
+
+            if marks
+                .iter()
+                .any(|mark| config.match_mode.matches(&ident, mark))
+            {
+                continue;
+            }
+
+            if let Some(mark) = suggest(&ident, marks) {
+                diagnostics.emit(
+                    attr.span(),
+                    format!("unknown mark `{ident}`, did you mean `{mark}`?"),
+                );
+            }
+        }
+    }
+
+    diagnostics.finish()
+}
+
+/// Returns an error for every mark on an item whose kind is not part of
+/// [ScopeConfig::scan_set], such marks would otherwise be skipped silently
+pub(crate) fn check_unscanned_marks(
+    items: &[Rc<RefCell<Item>>],
+    marks: &[&str],
+    config: &ScopeConfig,
+) -> syn::Result<()> {
+    let mut diagnostics = Diagnostics::new();
+
+    for item in items {
+        for mark in marks {
+            lazy::parse_if_marked(item, mark, config.match_mode);
+        }
+
+        let item = item.borrow();
+        let kind = ItemKind::of(&item);
+        if config.scan_set.contains(kind) {
+            continue;
+        }
+
+        let leading;
+        let attrs = match &*item {
+            Item::Verbatim(tokens) => {
+                leading = lazy::leading_attrs(tokens);
+                &leading
+            }
+            item => item_attrs(item).unwrap_or_default(),
+        };
+
+        for mark in marks {
+            if let Some((index, ident)) = find_attribute(attrs, mark, config.match_mode) {
+                diagnostics.emit(
+                    attrs[index].span(),
+                    format!("`#[{ident}]` is not supported on items of kind `{kind}`"),
+                );
+            }
+        }
+    }
+
+    diagnostics.finish()
+}
+
+/// Why an item is not scanned for marks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The kind of the item is not part of [ScopeConfig::scan_set]
+    ExcludedKind(ItemKind),
+    /// syn could not parse the item and verbatim items are not part of [ScopeConfig::scan_set]
+    Unsupported,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ExcludedKind(kind) => write!(f, "items of kind `{kind}` are not scanned"),
+            Self::Unsupported => f.write_str("the item could not be parsed"),
+        }
+    }
+}
+
+/// An item that is never matched by mark queries
+#[derive(Debug, Clone)]
+pub struct SkippedItem {
+    pub item: Rc<RefCell<Item>>,
+    pub reason: SkipReason,
+}
+
+impl MacroScope {
+    /// Returns all items that mark queries skip, in source order
+    ///
+    /// Useful to warn about marks that have no effect or to adjust [ScopeConfig::scan_set]. Parses
+    /// all items of a lazily parsed scope
+    pub fn skipped_items(&self) -> Vec<SkippedItem> {
+        self.scanned_items()
+            .into_iter()
+            .filter_map(|item| {
+                // unparsable items stay verbatim and are reported as such
+                let _ = lazy::parse(&item);
+
+                let reason = match ItemKind::of(&item.borrow()) {
+                    kind if self.config.scan_set.contains(kind) => return None,
+                    ItemKind::Verbatim => SkipReason::Unsupported,
+                    kind => SkipReason::ExcludedKind(kind),
+                };
+                Some(SkippedItem { item, reason })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,127 @@
+//! Comparing two scopes, e.g. against a snapshot of a previous version
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use quote::ToTokens;
+use syn::Item;
+
+use crate::{item_ident, lazy, ItemKind, MacroScope};
+
+/// Identifies an item across scopes by its kind and name
+///
+/// Impl blocks are named after their header like `Display for Foo`, other items without a name
+/// like `use` declarations by their tokens
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ItemKey {
+    pub kind: ItemKind,
+    pub name: String,
+}
+
+impl ItemKey {
+    pub fn of(item: &Item) -> Self {
+        let name = match (item, item_ident(item)) {
+            (_, Some(ident)) => ident.to_string(),
+            (Item::Impl(item), _) => {
+                let self_ty = item.self_ty.to_token_stream();
+                match &item.trait_ {
+                    Some((_, path, _)) => format!("{} for {self_ty}", path.to_token_stream()),
+                    None => self_ty.to_string(),
+                }
+            }
+            (item, _) => item.to_token_stream().to_string(),
+        };
+
+        Self {
+            kind: ItemKind::of(item),
+            name,
+        }
+    }
+}
+
+impl fmt::Display for ItemKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind, self.name)
+    }
+}
+
+/// The items that differ between two scopes, see [MacroScope::diff]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopeDiff {
+    /// Items only in the new scope
+    pub added: Vec<ItemKey>,
+    /// Items only in the old scope
+    pub removed: Vec<ItemKey>,
+    /// Items in both scopes with different tokens
+    pub changed: Vec<ItemKey>,
+}
+
+impl ScopeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The token hashes of the items of a scope by their key, see [MacroScope::token_hashes]
+pub type TokenHashes = BTreeMap<ItemKey, u64>;
+
+/// Returns a hash of the tokens of `item`, spans are ignored
+pub(crate) fn token_hash(item: &Item) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.to_token_stream().to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+impl MacroScope {
+    /// Returns the hashes of the tokens of all scanned items by their key, a snapshot that can be
+    /// compared against later with [MacroScope::changed_since]
+    ///
+    /// The hashes are stable between compilations with the same compiler, but not across Rust
+    /// versions. Parses all items of a lazily parsed scope, unparsable items are hashed by their
+    /// tokens
+    pub fn token_hashes(&self) -> TokenHashes {
+        self.scanned_items()
+            .iter()
+            .map(|item| {
+                let _ = lazy::parse(item);
+                let item = item.borrow();
+                (ItemKey::of(&item), token_hash(&item))
+            })
+            .collect()
+    }
+
+    /// Compares the items of `self` against a `snapshot` taken with [MacroScope::token_hashes]
+    ///
+    /// If several items share a key only the last one is compared. The keys in each list are
+    /// sorted
+    pub fn changed_since(&self, snapshot: &TokenHashes) -> ScopeDiff {
+        let new = self.token_hashes();
+        let mut diff = ScopeDiff::default();
+
+        for (key, hash) in &new {
+            match snapshot.get(key) {
+                None => diff.added.push(key.clone()),
+                Some(old) if old != hash => diff.changed.push(key.clone()),
+                Some(_) => (),
+            }
+        }
+        diff.removed = snapshot
+            .keys()
+            .filter(|key| !new.contains_key(key))
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    /// Compares the items of `self` against the items of an `old` version of the scope
+    ///
+    /// Items are matched by [ItemKey] and compared by their tokens, spans are ignored, see
+    /// [MacroScope::changed_since]
+    pub fn diff(&self, old: &MacroScope) -> ScopeDiff {
+        self.changed_since(&old.token_hashes())
+    }
+}
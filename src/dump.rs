@@ -0,0 +1,68 @@
+//! Opt-in dumps of the scope before and after expansion
+//!
+//! Set `MACRO_SCOPE_DUMP` to a directory to get two files per expansion, e.g.
+//! `my_crate.scope.0.pre.rs` and `my_crate.scope.0.post.rs`. The files are prefixed with the
+//! crate being compiled, so crates built in parallel into the same directory don't overwrite
+//! each other
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use proc_macro2::TokenStream;
+
+pub(crate) const DUMP_VAR: &str = "MACRO_SCOPE_DUMP";
+const CRATE_VAR: &str = "CARGO_CRATE_NAME";
+
+/// Counts expansions in this process, so multiple invocations don't overwrite each other
+static INVOCATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the directory to dump to if dumping is enabled
+pub(crate) fn dump_dir() -> Option<PathBuf> {
+    std::env::var_os(DUMP_VAR).map(PathBuf::from)
+}
+
+/// Returns a new id for an expansion
+pub(crate) fn next_invocation() -> usize {
+    INVOCATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Formats `tokens` with prettyplease, falling back to the raw tokens if they aren't a valid file
+pub(crate) fn pretty(tokens: &TokenStream) -> String {
+    match syn::parse2::<syn::File>(tokens.clone()) {
+        Ok(file) => prettyplease::unparse(&file),
+        Err(_) => tokens.to_string(),
+    }
+}
+
+/// Writes `tokens` to `<dir>/<crate>.<name>.<invocation>.<stage>.rs`
+///
+/// Dumping is a debugging aid, failing to write is reported on stderr but never fails the
+/// expansion
+pub(crate) fn write(dir: &Path, name: &str, invocation: usize, stage: &str, tokens: &TokenStream) {
+    write_text(
+        dir,
+        name,
+        invocation,
+        &format!("{stage}.rs"),
+        &pretty(tokens),
+    );
+}
+
+/// Writes `text` to `<dir>/<crate>.<name>.<invocation>.<suffix>`
+///
+/// The crate is taken from `CARGO_CRATE_NAME`, which cargo sets while compiling the crate that
+/// invokes the macro, and left out if it isn't set
+pub(crate) fn write_text(dir: &Path, name: &str, invocation: usize, suffix: &str, text: &str) {
+    let file = match std::env::var(CRATE_VAR) {
+        Ok(krate) => format!("{krate}.{name}.{invocation}.{suffix}"),
+        Err(_) => format!("{name}.{invocation}.{suffix}"),
+    };
+    let path = dir.join(file);
+
+    if let Err(e) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, text)) {
+        eprintln!("macro_scope: failed to write {}: {e}", path.display());
+    }
+}
@@ -0,0 +1,382 @@
+//! Helpers for emitting generated code
+
+use std::cell::RefCell;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{parse_quote, spanned::Spanned, Attribute, Ident, Item, Visibility};
+
+use crate::{item_attrs_mut, item_ident, item_vis_mut, lazy, Diagnostics, MacroScope, MetaTree};
+
+/// Adds `attr` to every item in `tokens`
+///
+/// Fails if `tokens` can't be parsed as a sequence of items, or if one of the items is only
+/// parsed as verbatim tokens, which can't carry attributes
+pub fn with_attr(attr: &Attribute, tokens: TokenStream) -> syn::Result<TokenStream> {
+    let mut file: syn::File = syn::parse2(tokens)?;
+    let mut diagnostics = Diagnostics::new();
+
+    for item in &mut file.items {
+        match item_attrs_mut(item) {
+            Some(attrs) => attrs.insert(0, attr.clone()),
+            None => diagnostics.emit(
+                item.span(),
+                format!(
+                    "can't add `#[{}]` to an item syn can't parse",
+                    attr.meta.to_token_stream()
+                ),
+            ),
+        }
+    }
+
+    diagnostics.finish()?;
+    Ok(file.into_token_stream())
+}
+
+/// Gates every item in `tokens` on a `cfg(...)` predicate as returned by
+/// [MarkedItem::cfg](crate::MarkedItem::cfg)
+///
+/// Fails like [with_attr], code that can't be gated is an error instead of compiled
+/// unconditionally
+pub fn with_cfg(cfg: &MetaTree, tokens: TokenStream) -> syn::Result<TokenStream> {
+    let attr: Attribute = parse_quote!(#[#cfg]);
+    with_attr(&attr, tokens)
+}
+
+/// Adds `#[allow(...)]` with all of `lints` to every item in `tokens`, does nothing if `lints` is
+/// empty
+pub fn with_allow(lints: &[syn::Path], tokens: TokenStream) -> syn::Result<TokenStream> {
+    if lints.is_empty() {
+        return Ok(tokens);
+    }
+
+    let attr: Attribute = parse_quote!(#[allow(#(#lints),*)]);
+    with_attr(&attr, tokens)
+}
+
+/// Documents every item in `tokens` as generated from the item `source` by `#[mark]`, so the
+/// expanded code and rustdoc can be traced back to the marked source
+///
+/// Macro invocations are skipped, doc comments on them are unused
+pub fn with_provenance(source: &str, mark: &str, tokens: TokenStream) -> syn::Result<TokenStream> {
+    let doc = format!("generated from `{source}` by #[{mark}]");
+    let attr: Attribute = parse_quote!(#[doc = #doc]);
+    let mut file: syn::File = syn::parse2(tokens)?;
+
+    for item in &mut file.items {
+        if matches!(item, Item::Macro(_)) {
+            continue;
+        }
+        if let Some(attrs) = item_attrs_mut(item) {
+            attrs.insert(0, attr.clone());
+        }
+    }
+
+    Ok(file.into_token_stream())
+}
+
+/// Adds `#[automatically_derived]` to every impl block in `tokens`
+pub fn automatically_derived(tokens: TokenStream) -> syn::Result<TokenStream> {
+    let mut file: syn::File = syn::parse2(tokens)?;
+
+    for item in &mut file.items {
+        if let Item::Impl(item) = item {
+            item.attrs.push(parse_quote!(#[automatically_derived]));
+        }
+    }
+
+    Ok(file.into_token_stream())
+}
+
+/// Returns the path of a `pub(in path)` restriction as seen from a module one level deeper, or
+/// [None] if it doesn't change, e.g. `super::super` for `pub(super)`
+fn nested_vis_path(path: &syn::Path) -> Option<syn::Path> {
+    let first = &path.segments.first()?.ident;
+    if first == "self" {
+        let mut path = path.clone();
+        path.segments[0].ident = Ident::new("super", first.span());
+        Some(path)
+    } else if first == "super" {
+        Some(parse_quote!(super::#path))
+    } else {
+        None
+    }
+}
+
+/// Moves every item in `tokens` into a `#[doc(hidden)]` module named `module` and re-exports the
+/// named items next to it, so they stay usable under their own names
+///
+/// The module imports everything from its parent. Items are re-exported with their visibility,
+/// `pub(super)` and `pub(in path)` restrictions are adjusted for the extra module level so the
+/// items stay visible where they were before. Private items become `pub(super)` and are imported
+/// privately. Macros are not re-exported
+pub fn in_hidden_module(module: &Ident, tokens: TokenStream) -> syn::Result<TokenStream> {
+    let mut file: syn::File = syn::parse2(tokens)?;
+    let mut reexports = TokenStream::new();
+
+    for item in &mut file.items {
+        if matches!(item, Item::Macro(_)) {
+            continue;
+        }
+        let Some(ident) = item_ident(item).cloned() else {
+            continue;
+        };
+        if ident == *module {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!("`{module}` is already used as the name of the module of generated code"),
+            ));
+        }
+        let Some(vis) = item_vis_mut(item) else {
+            continue;
+        };
+
+        match vis {
+            Visibility::Inherited => {
+                *vis = parse_quote!(pub(super));
+                reexports.extend(quote!(use #module::#ident;));
+            }
+            Visibility::Restricted(restricted) => {
+                reexports.extend(quote!(#restricted use #module::#ident;));
+                if let Some(path) = nested_vis_path(&restricted.path) {
+                    *vis = parse_quote!(pub(in #path));
+                }
+            }
+            Visibility::Public(_) => reexports.extend(quote!(#vis use #module::#ident;)),
+        }
+    }
+
+    let items = &file.items;
+    Ok(quote! {
+        #[doc(hidden)]
+        mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+            #(#items)*
+        }
+        #reexports
+    })
+}
+
+/// Builds common attributes and attaches them to items of a scope, the constructive counterpart
+/// to scanning and removing marks
+///
+/// ```
+/// # use macro_scope::{emit::Attrs, MacroScope};
+/// # use quote::quote;
+/// let scope: MacroScope = "mod scope { #[ffi] struct Foo; }".parse().unwrap();
+///
+/// for marked in scope.get_marked("ffi") {
+///     Attrs::new()
+///         .derive(quote!(Clone, Copy))
+///         .repr(quote!(C))
+///         .doc("exported to C")
+///         .apply(&marked.item);
+/// }
+///
+/// let tokens = quote::ToTokens::to_token_stream(&scope).to_string();
+/// assert!(tokens.contains("# [derive (Clone , Copy)] # [repr (C)]"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Attrs {
+    attrs: Vec<Attribute>,
+}
+
+impl Attrs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an arbitrary attribute
+    pub fn attr(mut self, attr: Attribute) -> Self {
+        self.attrs.push(attr);
+        self
+    }
+
+    /// Adds `#[derive(traits)]`, e.g. `derive(quote!(Debug, Clone))`
+    pub fn derive(self, traits: impl ToTokens) -> Self {
+        self.attr(parse_quote!(#[derive(#traits)]))
+    }
+
+    /// Adds `#[cfg(predicate)]`, e.g. `cfg(quote!(feature = "net"))`
+    pub fn cfg(self, predicate: impl ToTokens) -> Self {
+        self.attr(parse_quote!(#[cfg(#predicate)]))
+    }
+
+    /// Adds `#[repr(reprs)]`, e.g. `repr(quote!(C))`
+    pub fn repr(self, reprs: impl ToTokens) -> Self {
+        self.attr(parse_quote!(#[repr(#reprs)]))
+    }
+
+    /// Adds a line of documentation, rendered like a `///` comment
+    pub fn doc(self, doc: &str) -> Self {
+        let doc = format!(" {doc}");
+        self.attr(parse_quote!(#[doc = #doc]))
+    }
+
+    /// Returns the built attributes
+    pub fn build(self) -> Vec<Attribute> {
+        self.attrs
+    }
+
+    /// Appends the attributes to the outer attributes of `item`
+    ///
+    /// Items syn can't parse get the attributes inserted after their leading attributes
+    pub fn apply(&self, item: &RefCell<Item>) {
+        let mut item = item.borrow_mut();
+
+        if let Item::Verbatim(tokens) = &mut *item {
+            if let Some((mut attrs, rest)) = lazy::split_attrs(tokens) {
+                attrs.extend(self.attrs.iter().cloned());
+                *tokens = lazy::join_attrs(&attrs, rest);
+            }
+        } else if let Some(attrs) = item_attrs_mut(&mut item) {
+            attrs.extend(self.attrs.iter().cloned());
+        }
+    }
+}
+
+/// A compile time check generated by [Assertions]
+#[derive(Debug, Clone)]
+enum Check {
+    Implements(TokenStream),
+    Size { op: TokenStream, size: usize },
+    Align(usize),
+}
+
+/// Builds static assertions that are checked for every marked type, e.g. for marks that require
+/// their types to be `Send` or to fit into a fixed size
+///
+/// ```
+/// # use macro_scope::{emit::Assertions, MacroScope};
+/// # use quote::quote;
+/// let scope: MacroScope = "mod scope { #[component] struct Position(f32, f32); }"
+///     .parse()
+///     .unwrap();
+///
+/// let checks = Assertions::new()
+///     .implements(quote!(Send + Sync))
+///     .max_size(64)
+///     .for_marked(&scope, "component")
+///     .unwrap();
+/// assert!(checks.to_string().contains("size_of :: < Position >"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Assertions {
+    checks: Vec<Check>,
+}
+
+impl Assertions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asserts that the type implements `bounds`, e.g. `implements(quote!(Send + 'static))`
+    pub fn implements(mut self, bounds: impl ToTokens) -> Self {
+        self.checks
+            .push(Check::Implements(bounds.into_token_stream()));
+        self
+    }
+
+    /// Asserts that the type is exactly `size` bytes large
+    pub fn size(mut self, size: usize) -> Self {
+        self.checks.push(Check::Size {
+            op: quote!(==),
+            size,
+        });
+        self
+    }
+
+    /// Asserts that the type is at most `size` bytes large
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.checks.push(Check::Size {
+            op: quote!(<=),
+            size,
+        });
+        self
+    }
+
+    /// Asserts that the type has an alignment of `align` bytes
+    pub fn align(mut self, align: usize) -> Self {
+        self.checks.push(Check::Align(align));
+        self
+    }
+
+    /// Returns the assertions for the type `ident`
+    ///
+    /// Trait bounds are checked for all instantiations of generic types, size and alignment
+    /// checks need a concrete type and fail for generic ones
+    pub fn for_type(&self, ident: &Ident, generics: &syn::Generics) -> syn::Result<TokenStream> {
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        let mut tokens = TokenStream::new();
+
+        for check in &self.checks {
+            tokens.extend(match check {
+                Check::Implements(bounds) => quote! {
+                    const _: () = {
+                        fn assert_impl<T: ?::core::marker::Sized + #bounds>() {}
+                        #[allow(dead_code)]
+                        fn check #impl_generics () #where_clause {
+                            assert_impl::<#ident #ty_generics>();
+                        }
+                    };
+                },
+                Check::Size { .. } | Check::Align(_) if !generics.params.is_empty() => {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "size and alignment can only be asserted for types without generics",
+                    ));
+                }
+                Check::Size { op, size } => {
+                    let message = format!("the size of `{ident}` has to be {op} {size} bytes");
+                    quote! {
+                        const _: () = ::core::assert!(
+                            ::core::mem::size_of::<#ident>() #op #size,
+                            #message
+                        );
+                    }
+                }
+                Check::Align(align) => {
+                    let message = format!("`{ident}` has to be aligned to {align} bytes");
+                    quote! {
+                        const _: () = ::core::assert!(
+                            ::core::mem::align_of::<#ident>() == #align,
+                            #message
+                        );
+                    }
+                }
+            });
+        }
+
+        Ok(tokens)
+    }
+
+    /// Returns the assertions for every struct, enum and union marked with `mark`, other marked
+    /// items are reported
+    pub fn for_marked(&self, scope: &MacroScope, mark: &str) -> syn::Result<TokenStream> {
+        let mut diagnostics = Diagnostics::new();
+        let mut tokens = TokenStream::new();
+
+        for marked in scope.get_marked(mark) {
+            let item = marked.item.borrow();
+            let (ident, generics) = match &*item {
+                Item::Struct(i) => (&i.ident, &i.generics),
+                Item::Enum(i) => (&i.ident, &i.generics),
+                Item::Union(i) => (&i.ident, &i.generics),
+                _ => {
+                    diagnostics.emit(
+                        marked.mark.span(),
+                        "static assertions are only supported on structs, enums and unions",
+                    );
+                    continue;
+                }
+            };
+            if let Some(checks) = diagnostics.check(self.for_type(ident, generics)) {
+                tokens.extend(checks);
+            }
+        }
+
+        diagnostics.finish()?;
+        Ok(tokens)
+    }
+}
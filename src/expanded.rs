@@ -0,0 +1,47 @@
+//! Tags on items that were already expanded, so applying a scope macro twice or nesting scope
+//! macros doesn't generate the same code again
+
+use std::cell::RefCell;
+
+use syn::{parse_quote, Attribute, Item};
+
+use crate::{item_attrs, item_attrs_mut, lazy};
+
+/// Returns the tag recording that the template of `mark` ran on an item
+///
+/// `cfg_attr(any(), ..)` is never active, so the compiler ignores the tag while it stays visible
+/// to scope macros that see the item later
+fn tag(mark: &str) -> Attribute {
+    parse_quote!(#[cfg_attr(any(), macro_scope(expanded = #mark))])
+}
+
+/// Returns `true` if the template of `mark` already ran on `item`
+pub(crate) fn is_expanded(item: &RefCell<Item>, mark: &str) -> bool {
+    let tag = tag(mark);
+    let item = item.borrow();
+
+    match &*item {
+        Item::Verbatim(tokens) => {
+            lazy::split_attrs(tokens).is_some_and(|(attrs, _)| attrs.contains(&tag))
+        }
+        item => item_attrs(item).is_some_and(|attrs| attrs.contains(&tag)),
+    }
+}
+
+/// Records on `item` that the template of `mark` ran on it
+///
+/// The tag is inserted at `index`, where the matched mark was, so the remaining attributes keep
+/// their order and doc comments written after the mark stay next to the item
+pub(crate) fn tag_expanded(item: &RefCell<Item>, mark: &str, index: usize) {
+    let tag = tag(mark);
+    let mut item = item.borrow_mut();
+
+    if let Item::Verbatim(tokens) = &mut *item {
+        if let Some((mut attrs, rest)) = lazy::split_attrs(tokens) {
+            attrs.insert(index.min(attrs.len()), tag);
+            *tokens = lazy::join_attrs(&attrs, rest);
+        }
+    } else if let Some(attrs) = item_attrs_mut(&mut item) {
+        attrs.insert(index.min(attrs.len()), tag);
+    }
+}
@@ -0,0 +1,148 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{spanned::Spanned, FnArg, ItemStruct, ItemTrait, TraitItem};
+
+use crate::{Diagnostics, MacroScope, MarkedItem};
+
+/// Generates the delegating methods of the dispatch impl
+fn delegate(item: &ItemTrait, variants: &[&syn::Ident]) -> syn::Result<TokenStream> {
+    let mut diagnostics = Diagnostics::new();
+    let mut methods = TokenStream::new();
+
+    for trait_item in &item.items {
+        let method = match trait_item {
+            TraitItem::Fn(method) => method,
+            other => {
+                diagnostics.emit(other.span(), "only methods can be dispatched");
+                continue;
+            }
+        };
+
+        let mut sig = method.sig.clone();
+        if sig.receiver().is_none() {
+            diagnostics.emit(
+                sig.ident.span(),
+                "methods without `self` can't be dispatched",
+            );
+            continue;
+        }
+
+        let mut args = Vec::new();
+        for (i, input) in sig.inputs.iter_mut().enumerate() {
+            if let FnArg::Typed(typed) = input {
+                let arg = format_ident!("__arg{i}");
+                *typed.pat = syn::parse_quote!(#arg);
+                args.push(arg);
+            }
+        }
+
+        let ident = &sig.ident;
+        let dot_await = match sig.asyncness {
+            Some(_) => quote!(.await),
+            None => TokenStream::new(),
+        };
+        let call = quote!(#ident(#(#args),*) #dot_await);
+        methods.extend(quote! {
+            #sig {
+                match self {
+                    #(Self::#variants(v) => v.#call,)*
+                }
+            }
+        });
+    }
+
+    diagnostics.finish()?;
+    Ok(methods)
+}
+
+/// Generates an enum with a variant for every struct marked with `variant_mark` for every trait
+/// marked with `trait_mark`, together with an impl of the trait that delegates to the variants
+/// and `From` impls for every variant
+///
+/// The enum is called `<Trait>Enum` unless it is named in the mark, e.g.
+/// `#[dispatch(name = "AnyShape")]`. Only non-generic traits and structs are supported, and all
+/// trait items have to be methods taking `self`
+///
+/// See the [module docs](crate::generate#marks) for how the marks are handled
+pub fn enum_dispatch(
+    scope: &MacroScope,
+    trait_mark: &str,
+    variant_mark: &str,
+) -> syn::Result<TokenStream> {
+    let mut diagnostics = Diagnostics::new();
+
+    let traits: Vec<MarkedItem<ItemTrait>> = scope
+        .get_marked(trait_mark)
+        .into_iter()
+        .filter_map(|m| diagnostics.check(m.to_owned_item().try_into()))
+        .collect();
+    let variants: Vec<MarkedItem<ItemStruct>> = scope
+        .get_marked(variant_mark)
+        .into_iter()
+        .filter_map(|m| diagnostics.check(m.to_owned_item().try_into()))
+        .collect();
+
+    for variant in &variants {
+        let generics = &variant.item.generics;
+        if !generics.params.is_empty() {
+            diagnostics.emit(generics.span(), "generic variants are not supported");
+        }
+    }
+
+    let mut tokens = TokenStream::new();
+    let idents: Vec<_> = variants.iter().map(|v| &v.item.ident).collect();
+
+    for marked in &traits {
+        let item = &marked.item;
+        if !item.generics.params.is_empty() {
+            diagnostics.emit(item.generics.span(), "generic traits are not supported");
+            continue;
+        }
+
+        let name = match marked.meta_tree().and_then(|t| t.get_str("name")) {
+            Ok(name) => name,
+            Err(e) => {
+                diagnostics.push(e);
+                continue;
+            }
+        };
+        let enum_ident = match name {
+            Some(name) => format_ident!("{name}", span = marked.mark.span()),
+            None => format_ident!("{}Enum", item.ident),
+        };
+
+        let Some(methods) = diagnostics.check(delegate(item, &idents)) else {
+            continue;
+        };
+
+        let vis = &item.vis;
+        let trait_ident = &item.ident;
+        tokens.extend(quote! {
+            #vis enum #enum_ident {
+                #(#idents(#idents),)*
+            }
+
+            impl #trait_ident for #enum_ident {
+                #methods
+            }
+
+            #(
+                impl ::core::convert::From<#idents> for #enum_ident {
+                    fn from(value: #idents) -> Self {
+                        Self::#idents(value)
+                    }
+                }
+            )*
+        });
+    }
+
+    diagnostics.finish()?;
+    Ok(tokens)
+}
+
+impl MacroScope {
+    /// See [enum_dispatch](crate::generate::enum_dispatch)
+    pub fn enum_dispatch(&self, trait_mark: &str, variant_mark: &str) -> syn::Result<TokenStream> {
+        enum_dispatch(self, trait_mark, variant_mark)
+    }
+}
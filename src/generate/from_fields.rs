@@ -0,0 +1,233 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{spanned::Spanned, Fields, Ident, ItemStruct};
+
+use crate::{Diagnostics, MacroScope, MarkedItem, MetaTree};
+
+/// Returns the named fields of a struct or an error pointing at the struct
+fn named_fields(item: &ItemStruct) -> syn::Result<Vec<&Ident>> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item.generics.span(),
+            "generic structs are not supported",
+        ));
+    }
+
+    match &item.fields {
+        Fields::Named(fields) => Ok(fields
+            .named
+            .iter()
+            .filter_map(|f| f.ident.as_ref())
+            .collect()),
+        _ => Err(syn::Error::new(
+            item.ident.span(),
+            "only structs with named fields are supported",
+        )),
+    }
+}
+
+/// Name used to pair structs, the ident without the mark as a suffix, e.g. `user` for `UserDto`
+/// marked with `#[dto]`
+fn base_name(ident: &Ident, mark: &str) -> String {
+    let name = ident.to_string().to_lowercase();
+    match name.strip_suffix(&mark.to_lowercase()) {
+        Some(base) if !base.is_empty() => base.to_string(),
+        _ => name,
+    }
+}
+
+/// Generates `impl From<Source> for Target`, moving every field of the target out of the source
+fn from_impl(source: &ItemStruct, target: &ItemStruct, fields: &[&Ident]) -> TokenStream {
+    let source = &source.ident;
+    let target = &target.ident;
+
+    quote! {
+        impl ::core::convert::From<#source> for #target {
+            fn from(value: #source) -> Self {
+                Self {
+                    #(#fields: value.#fields,)*
+                }
+            }
+        }
+    }
+}
+
+/// A struct marked for conversions with the arguments of its mark
+struct Convertible {
+    item: ItemStruct,
+    /// The partner named with `with = Other`
+    with: Option<Ident>,
+    /// Whether `into` was requested, a conversion from this struct into its partner
+    into: bool,
+    /// Whether `from` was requested, a conversion from the partner into this struct
+    from: bool,
+}
+
+impl Convertible {
+    fn new(marked: MarkedItem<ItemStruct>) -> syn::Result<Self> {
+        let tree = marked.meta_tree()?;
+        let flag = |name: &str| tree.child(name).map(MetaTree::expect_bool).transpose();
+        Ok(Self {
+            with: tree.child("with").map(MetaTree::expect_ident).transpose()?,
+            into: flag("into")?.unwrap_or(false),
+            from: flag("from")?.unwrap_or(false),
+            item: marked.item,
+        })
+    }
+}
+
+/// Returns the index of the struct in `others` `item` is paired with
+///
+/// A `with` argument on either side takes precedence over pairing by name
+fn partner(item: &Convertible, mark: &str, others: &[Convertible], other: &str) -> Option<usize> {
+    if let Some(with) = &item.with {
+        return others.iter().position(|o| o.item.ident == *with);
+    }
+    others
+        .iter()
+        .position(|o| o.with.as_ref() == Some(&item.item.ident))
+        .or_else(|| {
+            others.iter().position(|o| {
+                o.with.is_none()
+                    && base_name(&item.item.ident, mark) == base_name(&o.item.ident, other)
+            })
+        })
+}
+
+/// Reports a struct without a partner, at the `with` argument if it names a missing struct
+fn unpaired(diagnostics: &mut Diagnostics, item: &Convertible, other: &str) {
+    match &item.with {
+        Some(with) => diagnostics.emit(
+            with.span(),
+            format!("no struct `{with}` is marked with `#[{other}]`"),
+        ),
+        None => diagnostics.emit(
+            item.item.ident.span(),
+            format!(
+                "no struct marked with `#[{other}]` to pair `{}` with, name it like this struct \
+                 or add `with = Other` to the mark",
+                item.item.ident
+            ),
+        ),
+    }
+}
+
+/// Generates `From` conversions between structs marked with `a` and structs marked with `b`
+///
+/// Structs are paired by name without the mark as a suffix, so `#[dto] struct UserDto` is paired
+/// with `#[entity] struct UserEntity` or `#[entity] struct User`. A `with` argument pairs structs
+/// explicitly instead, e.g. `#[dto(with = Account)] struct UserDto`. Structs without a partner
+/// are reported
+///
+/// For every pair a conversion is generated in each direction where all fields of the target
+/// exist in the source, fields only the source has are dropped. Pairs without any possible
+/// conversion are reported with their unmatched fields. The `into` and `from` arguments request
+/// a direction, e.g. `#[dto(into)]` only generates `impl From<UserDto> for User`, and every field
+/// of the target missing in the source is reported
+///
+/// See the [module docs](crate::generate#marks) for how the marks are handled
+pub fn from_conversions(scope: &MacroScope, a: &str, b: &str) -> syn::Result<TokenStream> {
+    let mut diagnostics = Diagnostics::new();
+
+    let structs = |mark: &str, diagnostics: &mut Diagnostics| -> Vec<Convertible> {
+        scope
+            .get_marked(mark)
+            .into_iter()
+            .filter_map(|m| {
+                let item: syn::Result<MarkedItem<ItemStruct>> = m.to_owned_item().try_into();
+                diagnostics.check(item.and_then(Convertible::new))
+            })
+            .collect()
+    };
+
+    let a_structs = structs(a, &mut diagnostics);
+    let b_structs = structs(b, &mut diagnostics);
+    let mut paired = vec![false; b_structs.len()];
+    let mut tokens = TokenStream::new();
+
+    for a_item in &a_structs {
+        let Some(index) = partner(a_item, a, &b_structs, b) else {
+            unpaired(&mut diagnostics, a_item, b);
+            continue;
+        };
+        paired[index] = true;
+        let b_item = &b_structs[index];
+
+        let (Some(a_fields), Some(b_fields)) = (
+            diagnostics.check(named_fields(&a_item.item)),
+            diagnostics.check(named_fields(&b_item.item)),
+        ) else {
+            continue;
+        };
+
+        let a_only: Vec<_> = a_fields.iter().filter(|f| !b_fields.contains(f)).collect();
+        let b_only: Vec<_> = b_fields.iter().filter(|f| !a_fields.contains(f)).collect();
+
+        let a_to_b = a_item.into || b_item.from;
+        let b_to_a = a_item.from || b_item.into;
+        if a_to_b || b_to_a {
+            // `missing` are the fields of the target the source doesn't have
+            let mut convert =
+                |source: &ItemStruct, target: &ItemStruct, fields, missing: &[&&Ident]| {
+                    for field in missing {
+                        diagnostics.emit(
+                            field.span(),
+                            format!(
+                                "`{}` has no field `{field}` to convert into `{}`",
+                                source.ident, target.ident
+                            ),
+                        );
+                    }
+                    if missing.is_empty() {
+                        tokens.extend(from_impl(source, target, fields));
+                    }
+                };
+            if a_to_b {
+                convert(&a_item.item, &b_item.item, &b_fields, &b_only);
+            }
+            if b_to_a {
+                convert(&b_item.item, &a_item.item, &a_fields, &a_only);
+            }
+            continue;
+        }
+
+        if b_only.is_empty() {
+            tokens.extend(from_impl(&a_item.item, &b_item.item, &b_fields));
+        }
+        if a_only.is_empty() {
+            tokens.extend(from_impl(&b_item.item, &a_item.item, &a_fields));
+        }
+
+        if !a_only.is_empty() && !b_only.is_empty() {
+            let list = |fields: &[&&Ident]| {
+                let names: Vec<_> = fields.iter().map(|f| format!("`{f}`")).collect();
+                names.join(", ")
+            };
+            diagnostics.emit(
+                a_item.item.ident.span(),
+                format!(
+                    "no conversion between `{a}` and `{b}` possible, unmatched fields {} in `{a}` \
+                     and {} in `{b}`",
+                    list(&a_only),
+                    list(&b_only),
+                    a = a_item.item.ident,
+                    b = b_item.item.ident,
+                ),
+            );
+        }
+    }
+
+    for (b_item, _) in b_structs.iter().zip(paired).filter(|(_, paired)| !paired) {
+        unpaired(&mut diagnostics, b_item, a);
+    }
+
+    diagnostics.finish()?;
+    Ok(tokens)
+}
+
+impl MacroScope {
+    /// See [from_conversions](crate::generate::from_conversions)
+    pub fn from_conversions(&self, a: &str, b: &str) -> syn::Result<TokenStream> {
+        from_conversions(self, a, b)
+    }
+}
@@ -0,0 +1,176 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{spanned::Spanned, visit::Visit, FnArg, ItemTrait, ReturnType, TraitItemFn, Type};
+
+use crate::{Diagnostics, MacroScope, MarkedItem};
+
+/// Finds types that can't be named in the signature of a closure
+#[derive(Default)]
+struct Unnameable(Option<proc_macro2::Span>);
+
+impl<'ast> Visit<'ast> for Unnameable {
+    fn visit_type(&mut self, ty: &'ast Type) {
+        match ty {
+            Type::ImplTrait(_) => self.0 = Some(ty.span()),
+            Type::Path(path) if path.qself.is_none() && path.path.segments[0].ident == "Self" => {
+                self.0 = Some(ty.span())
+            }
+            _ => syn::visit::visit_type(self, ty),
+        }
+    }
+}
+
+/// Returns the field and the method of the mock for a required trait method
+fn mock_method(method: &TraitItemFn, mock: &syn::Ident) -> syn::Result<(TokenStream, TokenStream)> {
+    let sig = &method.sig;
+    if sig.asyncness.is_some() || !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.ident.span(),
+            "async and generic methods can't be mocked",
+        ));
+    }
+    let Some(receiver) = sig.receiver() else {
+        return Err(syn::Error::new(
+            sig.ident.span(),
+            "methods without `self` can't be mocked",
+        ));
+    };
+
+    let mut unnameable = Unnameable::default();
+    for input in &sig.inputs {
+        if let FnArg::Typed(typed) = input {
+            unnameable.visit_type(&typed.ty);
+        }
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        unnameable.visit_type(ty);
+        if matches!(**ty, Type::Reference(_)) {
+            unnameable.0 = Some(ty.span());
+        }
+    }
+    if let Some(span) = unnameable.0 {
+        return Err(syn::Error::new(
+            span,
+            "`impl Trait`, `Self` and returned references can't be mocked",
+        ));
+    }
+
+    let mut sig = sig.clone();
+    let mut args = Vec::new();
+    let mut types = Vec::new();
+    for (i, input) in sig.inputs.iter_mut().enumerate() {
+        if let FnArg::Typed(typed) = input {
+            let arg = format_ident!("__arg{i}");
+            *typed.pat = syn::parse_quote!(#arg);
+            args.push(arg);
+            types.push(typed.ty.clone());
+        }
+    }
+
+    let ident = &sig.ident;
+    let output = &sig.output;
+    let mutable = receiver.reference.is_some() && receiver.mutability.is_some();
+    let (closure, field) = match mutable {
+        true => (quote!(FnMut), quote!(&mut self.#ident)),
+        false => (quote!(Fn), quote!(&self.#ident)),
+    };
+    let message = format!("`{mock}::{ident}` was called but not configured");
+
+    let field_decl = quote! {
+        pub #ident: ::core::option::Option<
+            ::std::boxed::Box<dyn #closure(#(#types),*) #output>
+        >,
+    };
+    let method = quote! {
+        #sig {
+            match #field {
+                ::core::option::Option::Some(f) => f(#(#args),*),
+                ::core::option::Option::None => ::core::panic!(#message),
+            }
+        }
+    };
+    Ok((field_decl, method))
+}
+
+/// Generates a mock struct for every trait marked with `mark`, e.g. `MockStore` for
+/// `#[mockable] trait Store`
+///
+/// The mock has a public field for every method without a default body, holding an optional
+/// closure the method calls. Methods with a default body keep it, methods whose closure isn't set
+/// panic. Only non-generic traits without associated types and consts are supported, and the
+/// mocked methods have to take `self` and must not mention `Self`, `impl Trait` or return a
+/// reference
+///
+/// See the [module docs](crate::generate#marks) for how the marks are handled
+///
+/// ```
+/// # use macro_scope::MacroScope;
+/// let scope: MacroScope = "mod scope { #[mockable] trait Clock { fn now(&self) -> u64; } }"
+///     .parse()
+///     .unwrap();
+///
+/// let mocks = scope.mocks("mockable").unwrap().to_string();
+/// assert!(mocks.contains("struct MockClock"));
+/// ```
+pub fn mocks(scope: &MacroScope, mark: &str) -> syn::Result<TokenStream> {
+    let mut diagnostics = Diagnostics::new();
+    let mut tokens = TokenStream::new();
+
+    for marked in scope.get_marked(mark) {
+        let Some(marked) =
+            diagnostics.check(MarkedItem::<ItemTrait>::try_from(marked.to_owned_item()))
+        else {
+            continue;
+        };
+        let item = &marked.item;
+        if !item.generics.params.is_empty() {
+            diagnostics.emit(item.generics.span(), "generic traits can't be mocked");
+            continue;
+        }
+
+        let items = marked.trait_items();
+        if let Some(ty) = items.types.first() {
+            diagnostics.emit(ty.ident.span(), "associated types can't be mocked");
+            continue;
+        }
+        if let Some(c) = items.consts.first() {
+            diagnostics.emit(c.ident.span(), "associated consts can't be mocked");
+            continue;
+        }
+
+        let trait_ident = &item.ident;
+        let mock = format_ident!("Mock{trait_ident}");
+        let mut fields = TokenStream::new();
+        let mut methods = TokenStream::new();
+        for method in items.required_methods() {
+            if let Some((field, method)) = diagnostics.check(mock_method(method, &mock)) {
+                fields.extend(field);
+                methods.extend(method);
+            }
+        }
+
+        let vis = &item.vis;
+        let unsafety = &item.unsafety;
+        tokens.extend(quote! {
+            #[allow(clippy::type_complexity)]
+            #[derive(Default)]
+            #vis struct #mock {
+                #fields
+            }
+
+            #unsafety impl #trait_ident for #mock {
+                #methods
+            }
+        });
+    }
+
+    diagnostics.finish()?;
+    Ok(tokens)
+}
+
+impl MacroScope {
+    /// See [mocks](crate::generate::mocks)
+    pub fn mocks(&self, mark: &str) -> syn::Result<TokenStream> {
+        mocks(self, mark)
+    }
+}
@@ -0,0 +1,25 @@
+//! Generators that combine several marked items of a scope
+//!
+//! Ordinary derives only see a single item, these generators use the fact that the scope can see
+//! all items of the module at once
+//!
+//! # Marks
+//!
+//! The generators find their items with [MacroScope::get_marked], so the marks are handled
+//! according to [ScopeConfig::mark_policy]. With the default policy the marks are removed and a
+//! second call finds no marked items
+//!
+//! [MacroScope::get_marked]: crate::MacroScope::get_marked
+//! [ScopeConfig::mark_policy]: crate::ScopeConfig::mark_policy
+
+mod dispatch;
+mod from_fields;
+mod mock;
+mod reexport;
+mod shadow;
+
+pub use dispatch::enum_dispatch;
+pub use from_fields::from_conversions;
+pub use mock::mocks;
+pub use reexport::reexports;
+pub use shadow::shadow_module;
@@ -0,0 +1,60 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{spanned::Spanned, Ident, Visibility};
+
+use crate::{item_ident, item_vis, Diagnostics, MacroScope};
+
+/// Generates a module named `module` that re-exports every item marked with `mark`, e.g. a
+/// `prelude` built from `#[prelude]` marks
+///
+/// An item is re-exported under another name if it is given in the mark, e.g.
+/// `#[prelude(rename = "Shape")]`. Items in nested modules scanned with
+/// [ScopeConfig::recursive](crate::ScopeConfig::recursive) are re-exported through their module
+/// path. Only `pub` items can be re-exported, other items and items without a name are reported
+///
+/// See the [module docs](crate::generate#marks) for how the marks are handled
+pub fn reexports(scope: &MacroScope, mark: &str, module: &Ident) -> syn::Result<TokenStream> {
+    let mut diagnostics = Diagnostics::new();
+    let mut uses = TokenStream::new();
+
+    for (path, marked) in scope.group_by_module(mark) {
+        for marked in marked {
+            let item = marked.item.borrow();
+            let Some(ident) = item_ident(&item) else {
+                diagnostics.emit(marked.mark.span(), "only named items can be re-exported");
+                continue;
+            };
+            if !matches!(item_vis(&item), Some(Visibility::Public(_))) {
+                diagnostics.emit(
+                    ident.span(),
+                    format!("`{ident}` needs to be `pub` to be re-exported by `#[{mark}]`"),
+                );
+                continue;
+            }
+
+            let rename = match marked.meta_tree().and_then(|t| t.get_str("rename")) {
+                Ok(rename) => rename.map(|name| format_ident!("{name}", span = marked.mark.span())),
+                Err(e) => {
+                    diagnostics.push(e);
+                    continue;
+                }
+            };
+            let rename = rename.map(|name| quote!(as #name));
+            uses.extend(quote!(pub use super::#(#path::)*#ident #rename;));
+        }
+    }
+
+    diagnostics.finish()?;
+    Ok(quote! {
+        pub mod #module {
+            #uses
+        }
+    })
+}
+
+impl MacroScope {
+    /// See [reexports](crate::generate::reexports)
+    pub fn reexports(&self, mark: &str, module: &Ident) -> syn::Result<TokenStream> {
+        reexports(self, mark, module)
+    }
+}
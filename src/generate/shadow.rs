@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, visit_mut::VisitMut, Ident, Item, Path};
+
+use crate::{item_ident, item_ident_mut, lazy, MacroScope};
+
+/// Rewrites single identifier paths inside the shadow module, renamed items point to their copy,
+/// the remaining items of the scope are reached through `super`
+struct Rewrite {
+    renamed: HashMap<Ident, Ident>,
+    outer: HashSet<Ident>,
+}
+
+impl VisitMut for Rewrite {
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        if path.leading_colon.is_none() {
+            let first = &mut path.segments[0].ident;
+            if let Some(renamed) = self.renamed.get(first) {
+                *first = renamed.clone();
+            } else if self.outer.contains(first) {
+                *path = parse_quote!(super::#path);
+            }
+        }
+
+        syn::visit_mut::visit_path_mut(self, path);
+    }
+}
+
+/// Copies all items marked with `mark` into a new module named `module`, with `suffix` appended
+/// to their names, e.g. `ffi::FooRaw` for every `#[ffi] struct Foo`
+///
+/// References between the copied items are renamed, references to other items of the scope are
+/// rewritten to go through `super`. The `use` declarations of the scope are repeated in the
+/// module, see [MacroScope::child_uses]. The original items are left in place
+///
+/// See the [module docs](crate::generate#marks) for how the marks are handled
+pub fn shadow_module(scope: &MacroScope, mark: &str, module: &Ident, suffix: &str) -> TokenStream {
+    let mut items: Vec<Item> = scope
+        .get_marked(mark)
+        .iter()
+        .map(|marked| marked.item.borrow().clone())
+        .collect();
+
+    let renamed: HashMap<Ident, Ident> = items
+        .iter()
+        .filter_map(item_ident)
+        .map(|ident| {
+            (
+                ident.clone(),
+                format_ident!("{ident}{suffix}", span = ident.span()),
+            )
+        })
+        .collect();
+    let outer = scope
+        .items
+        .iter()
+        .filter_map(|item| {
+            // unparsed items have no name yet
+            let _ = lazy::parse(item);
+            item_ident(&item.borrow()).cloned()
+        })
+        .filter(|ident| !renamed.contains_key(ident))
+        .collect();
+
+    let mut rewrite = Rewrite { renamed, outer };
+    for item in &mut items {
+        if let Some(ident) = item_ident_mut(item) {
+            *ident = rewrite.renamed[ident].clone();
+        }
+        rewrite.visit_item_mut(item);
+    }
+
+    let uses = scope.child_uses();
+    quote! {
+        mod #module {
+            #uses
+            #(#items)*
+        }
+    }
+}
+
+impl MacroScope {
+    /// See [shadow_module](crate::generate::shadow_module)
+    pub fn shadow_module(&self, mark: &str, module: &Ident, suffix: &str) -> TokenStream {
+        shadow_module(self, mark, module, suffix)
+    }
+}
@@ -0,0 +1,238 @@
+//! Generic parameters in the types of fields and variants
+
+use std::collections::HashMap;
+
+use syn::{
+    visit::Visit, visit_mut::VisitMut, Expr, Field, GenericArgument, GenericParam, Generics, Ident,
+    Lifetime, Type,
+};
+
+/// The generic parameters of an item that a type refers to, in the order they are declared
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GenericUsage {
+    pub types: Vec<Ident>,
+    pub lifetimes: Vec<Lifetime>,
+    pub consts: Vec<Ident>,
+}
+
+impl GenericUsage {
+    /// Returns the parameters of `generics` used by `ty`
+    pub fn of(ty: &Type, generics: &Generics) -> Self {
+        let mut collector = Collector {
+            generics,
+            found: Vec::new(),
+            lifetimes: Vec::new(),
+        };
+        collector.visit_type(ty);
+
+        let mut usage = Self::default();
+        for param in &generics.params {
+            match param {
+                GenericParam::Type(p) if collector.found.contains(&p.ident) => {
+                    usage.types.push(p.ident.clone())
+                }
+                GenericParam::Const(p) if collector.found.contains(&p.ident) => {
+                    usage.consts.push(p.ident.clone())
+                }
+                GenericParam::Lifetime(p) if collector.lifetimes.contains(&p.lifetime) => {
+                    usage.lifetimes.push(p.lifetime.clone())
+                }
+                _ => (),
+            }
+        }
+        usage
+    }
+
+    /// Returns the parameters of `generics` used by the type of `field`
+    pub fn of_field(field: &Field, generics: &Generics) -> Self {
+        Self::of(&field.ty, generics)
+    }
+
+    /// Returns `true` if the type is the same for every instantiation of the item
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.lifetimes.is_empty() && self.consts.is_empty()
+    }
+}
+
+struct Collector<'a> {
+    generics: &'a Generics,
+    found: Vec<Ident>,
+    lifetimes: Vec<Lifetime>,
+}
+
+impl Collector<'_> {
+    fn is_param(&self, ident: &Ident) -> bool {
+        self.generics.params.iter().any(|param| match param {
+            GenericParam::Type(p) => &p.ident == ident,
+            GenericParam::Const(p) => &p.ident == ident,
+            GenericParam::Lifetime(_) => false,
+        })
+    }
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        // `T::Output` and `N` refer to parameters, `crate::T` doesn't
+        if path.leading_colon.is_none() {
+            let first = &path.segments[0].ident;
+            if self.is_param(first) && !self.found.contains(first) {
+                self.found.push(first.clone());
+            }
+        }
+        syn::visit::visit_path(self, path);
+    }
+
+    fn visit_lifetime(&mut self, lifetime: &'ast Lifetime) {
+        if !self.lifetimes.contains(lifetime) {
+            self.lifetimes.push(lifetime.clone());
+        }
+    }
+}
+
+/// Replaces generic parameters with concrete types, lifetimes and const values, e.g. to
+/// instantiate generated code for `Foo<u8, 4>` from the definition of `Foo<T, const N: usize>`
+///
+/// ```
+/// # use macro_scope::generics::Substitution;
+/// # use quote::ToTokens;
+/// let ty: syn::Type = syn::parse_quote!(&'a [T; N]);
+/// let ty = Substitution::new()
+///     .ty(syn::parse_quote!(T), syn::parse_quote!(u8))
+///     .lifetime(syn::parse_quote!('a), syn::parse_quote!('static))
+///     .konst(syn::parse_quote!(N), syn::parse_quote!(4))
+///     .apply(&ty);
+/// assert_eq!(ty.to_token_stream().to_string(), "& 'static [u8 ; 4]");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Substitution {
+    types: HashMap<Ident, Type>,
+    lifetimes: HashMap<Lifetime, Lifetime>,
+    consts: HashMap<Ident, Expr>,
+}
+
+impl Substitution {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ty(mut self, param: Ident, ty: Type) -> Self {
+        self.types.insert(param, ty);
+        self
+    }
+
+    pub fn lifetime(mut self, param: Lifetime, lifetime: Lifetime) -> Self {
+        self.lifetimes.insert(param, lifetime);
+        self
+    }
+
+    pub fn konst(mut self, param: Ident, value: Expr) -> Self {
+        self.consts.insert(param, value);
+        self
+    }
+
+    /// Returns `ty` with all parameters replaced
+    pub fn apply(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        self.apply_mut(&mut ty);
+        ty
+    }
+
+    /// Replaces the parameters in anything syn can visit, e.g. a whole generated item
+    pub fn apply_mut<T: Substitute + ?Sized>(&self, node: &mut T) {
+        node.substitute(&mut Substituter(self));
+    }
+
+    fn single_ident<'a>(&self, path: &'a syn::Path) -> Option<&'a Ident> {
+        match path.leading_colon {
+            None => path.get_ident(),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Syntax tree nodes a [Substitution] can be applied to
+pub trait Substitute {
+    #[doc(hidden)]
+    fn substitute(&mut self, substituter: &mut Substituter);
+}
+
+macro_rules! impl_substitute {
+    ($($ty:ty => $visit:ident,)*) => {
+        $(
+            impl Substitute for $ty {
+                fn substitute(&mut self, substituter: &mut Substituter) {
+                    substituter.$visit(self);
+                }
+            }
+        )*
+    };
+}
+
+impl_substitute! {
+    Type => visit_type_mut,
+    Expr => visit_expr_mut,
+    syn::Item => visit_item_mut,
+    syn::File => visit_file_mut,
+    Field => visit_field_mut,
+    syn::Fields => visit_fields_mut,
+}
+
+#[doc(hidden)]
+pub struct Substituter<'a>(&'a Substitution);
+
+impl VisitMut for Substituter<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(path) = ty {
+            if path.qself.is_none() {
+                if let Some(replacement) = self
+                    .0
+                    .single_ident(&path.path)
+                    .and_then(|ident| self.0.types.get(ident))
+                {
+                    *ty = replacement.clone();
+                    return;
+                }
+            }
+        }
+        syn::visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_generic_argument_mut(&mut self, arg: &mut GenericArgument) {
+        // const arguments like the `N` in `Foo<N>` are parsed as types
+        if let GenericArgument::Type(Type::Path(path)) = arg {
+            if let Some(value) = self
+                .0
+                .single_ident(&path.path)
+                .and_then(|ident| self.0.consts.get(ident))
+            {
+                // anything but literals and blocks needs braces as a const argument
+                *arg = match value {
+                    Expr::Lit(_) | Expr::Block(_) => GenericArgument::Const(value.clone()),
+                    value => GenericArgument::Const(syn::parse_quote!({ #value })),
+                };
+                return;
+            }
+        }
+        syn::visit_mut::visit_generic_argument_mut(self, arg);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Path(path) = expr {
+            if let Some(value) = self
+                .0
+                .single_ident(&path.path)
+                .and_then(|ident| self.0.consts.get(ident))
+            {
+                *expr = value.clone();
+                return;
+            }
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if let Some(replacement) = self.0.lifetimes.get(lifetime) {
+            *lifetime = replacement.clone();
+        }
+    }
+}
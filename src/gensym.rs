@@ -0,0 +1,44 @@
+//! Unique names for generated items that stay the same between compilations
+
+use std::{
+    cell::Cell,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::Ident;
+
+use crate::MacroScope;
+
+/// The state of [MacroScope::gensym]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Gensym {
+    seed: Cell<Option<u64>>,
+    counter: Cell<usize>,
+}
+
+impl MacroScope {
+    /// Returns a new identifier like `__prefix_1f3a9c_0` that is unique within the scope
+    ///
+    /// The names are derived from a hash of the scope at the first call and a counter, not from
+    /// global state, so compiling the same input again produces the same names and incremental
+    /// builds don't churn. Different scopes get different names as long as their tokens differ
+    pub fn gensym(&self, prefix: &str) -> Ident {
+        let seed = match self.gensym.seed.get() {
+            Some(seed) => seed,
+            None => {
+                let mut hasher = DefaultHasher::new();
+                self.to_token_stream().to_string().hash(&mut hasher);
+                let seed = hasher.finish() & 0xff_ffff;
+                self.gensym.seed.set(Some(seed));
+                seed
+            }
+        };
+
+        let n = self.gensym.counter.get();
+        self.gensym.counter.set(n + 1);
+        Ident::new(&format!("__{prefix}_{seed:06x}_{n}"), Span::call_site())
+    }
+}
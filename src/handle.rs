@@ -0,0 +1,142 @@
+//! Cheap handles to the items of a scope
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+};
+
+use syn::{Attribute, Item};
+
+use crate::{diff, item_attrs, lazy, MacroScope, MarkedItem, SharedMarkedItem};
+
+thread_local! {
+    /// Counts the calls of [ItemHandle::write], indices built before a write are stale
+    static WRITES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns how often items were written through handles on this thread
+pub(crate) fn writes() -> u64 {
+    WRITES.with(Cell::get)
+}
+
+/// A reference to an item of a [MacroScope] that only gives access through closures
+///
+/// Cloning a handle never copies the item, and the borrow ends with the closure, so extracting
+/// e.g. the name of a large item doesn't require cloning it or holding a [std::cell::Ref]
+///
+/// ```
+/// # use macro_scope::MacroScope;
+/// let scope: MacroScope = "mod scope { #[system] fn update() {} }".parse().unwrap();
+///
+/// let marked = &scope.get_marked_handles("system")[0];
+/// let name = marked.item.read(|item| match item {
+///     syn::Item::Fn(f) => f.sig.ident.to_string(),
+///     _ => unreachable!(),
+/// });
+/// assert_eq!(name, "update");
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct ItemHandle(Rc<RefCell<Item>>);
+
+impl ItemHandle {
+    /// Calls `f` with the item borrowed immutably
+    ///
+    /// Panics if the item is currently borrowed by [ItemHandle::write]
+    pub fn read<R>(&self, f: impl FnOnce(&Item) -> R) -> R {
+        f(&self.0.borrow())
+    }
+
+    /// Calls `f` with the item borrowed mutably, changes are visible to the scope
+    ///
+    /// Renames are picked up by [MacroScope::find]. Panics if the item is currently borrowed
+    pub fn write<R>(&self, f: impl FnOnce(&mut Item) -> R) -> R {
+        WRITES.with(|writes| writes.set(writes.get() + 1));
+        f(&mut self.0.borrow_mut())
+    }
+
+    /// Returns a hash of the tokens of the item, spans are ignored, see
+    /// [MacroScope::token_hashes]
+    pub fn token_hash(&self) -> u64 {
+        let _ = lazy::parse(&self.0);
+        diff::token_hash(&self.0.borrow())
+    }
+
+    /// Returns `true` if both handles refer to the same item
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Returns the shared item the handle refers to
+    pub fn shared(&self) -> &Rc<RefCell<Item>> {
+        &self.0
+    }
+}
+
+impl From<Rc<RefCell<Item>>> for ItemHandle {
+    fn from(item: Rc<RefCell<Item>>) -> Self {
+        Self(item)
+    }
+}
+
+impl fmt::Debug for ItemHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ItemHandle").field(&self.0.borrow()).finish()
+    }
+}
+
+impl SharedMarkedItem<Item> {
+    /// Converts the shared item into an [ItemHandle]
+    pub fn into_handle(self) -> MarkedItem<ItemHandle> {
+        self.map(ItemHandle)
+    }
+}
+
+impl MacroScope {
+    /// Same as [MacroScope::get_marked] but with the items behind [ItemHandle]s
+    pub fn get_marked_handles(&self, mark: &str) -> Vec<MarkedItem<ItemHandle>> {
+        self.get_marked(mark)
+            .into_iter()
+            .map(SharedMarkedItem::into_handle)
+            .collect()
+    }
+
+    /// Same as [MacroScope::scanned_items] but as [ItemHandle]s
+    pub fn item_handles(&self) -> Vec<ItemHandle> {
+        self.scanned_items().into_iter().map(ItemHandle).collect()
+    }
+
+    /// Returns every outer attribute of every [scanned item](MacroScope::scanned_items) together
+    /// with the item, in source order, independent of marks and [ScopeConfig::scan_set]
+    ///
+    /// The attributes of lazily parsed items are read without parsing the items
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// let scope: MacroScope = "mod scope { #[deprecated] fn old() {} fn new() {} }"
+    ///     .parse()
+    ///     .unwrap();
+    ///
+    /// let deprecated = scope
+    ///     .attributes()
+    ///     .filter(|(_, attr)| attr.path().is_ident("deprecated"))
+    ///     .count();
+    /// assert_eq!(deprecated, 1);
+    /// ```
+    ///
+    /// [ScopeConfig::scan_set]: crate::ScopeConfig::scan_set
+    pub fn attributes(&self) -> impl Iterator<Item = (ItemHandle, Attribute)> {
+        let mut attributes = Vec::new();
+
+        for item in self.scanned_items() {
+            let attrs = match &*item.borrow() {
+                Item::Verbatim(tokens) => lazy::leading_attrs(tokens),
+                item => item_attrs(item).map(<[_]>::to_vec).unwrap_or_default(),
+            };
+            let handle = ItemHandle(item);
+            attributes.extend(attrs.into_iter().map(|attr| (handle.clone(), attr)));
+        }
+
+        attributes.into_iter()
+    }
+}
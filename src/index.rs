@@ -0,0 +1,184 @@
+//! Indices from attribute names and item names to the items
+//!
+//! Without an index every query walks the attributes of every item. The index is built in one
+//! pass and turns a query into a lookup of the matching names, only the candidates it returns are
+//! checked for the mark. [MatchMode::Exact] is a hash lookup and [MatchMode::Prefix] a binary
+//! search in the sorted names, [MatchMode::Contains] still checks every distinct attribute name,
+//! but not every item
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use syn::{Item, Type};
+
+use crate::{handle, item_attrs, item_ident, lazy, ItemHandle, MacroScope, MatchMode};
+
+/// Positions in [MacroScope::scanned_items] by attribute name
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AttrIndex {
+    names: HashMap<String, Vec<usize>>,
+    /// The keys of `names` in sorted order, for prefix queries
+    sorted: Vec<String>,
+}
+
+impl AttrIndex {
+    fn build(items: &[Rc<RefCell<Item>>]) -> Self {
+        let mut names: HashMap<String, Vec<usize>> = HashMap::new();
+
+        for (position, item) in items.iter().enumerate() {
+            let item = item.borrow();
+            let leading;
+            let attrs = match &*item {
+                Item::Verbatim(tokens) => {
+                    leading = lazy::leading_attrs(tokens);
+                    &leading
+                }
+                item => item_attrs(item).unwrap_or_default(),
+            };
+
+            for attr in attrs {
+                if let Some(ident) = attr.path().get_ident() {
+                    let positions = names.entry(ident.to_string()).or_default();
+                    if positions.last() != Some(&position) {
+                        positions.push(position);
+                    }
+                }
+            }
+        }
+
+        let mut sorted: Vec<_> = names.keys().cloned().collect();
+        sorted.sort_unstable();
+        Self { names, sorted }
+    }
+
+    /// Returns the sorted positions of all items with an attribute matching `mark`
+    pub(crate) fn candidates(&self, mark: &str, mode: MatchMode) -> Vec<usize> {
+        let mut positions: Vec<usize> = match mode {
+            MatchMode::Exact => self.names.get(mark).cloned().unwrap_or_default(),
+            MatchMode::Prefix => {
+                let start = self.sorted.partition_point(|name| name.as_str() < mark);
+                self.sorted[start..]
+                    .iter()
+                    .take_while(|name| name.starts_with(mark))
+                    .flat_map(|name| self.names[name].iter().copied())
+                    .collect()
+            }
+            mode => self
+                .names
+                .iter()
+                .filter(|(name, _)| mode.matches(name, mark))
+                .flat_map(|(_, positions)| positions.iter().copied())
+                .collect(),
+        };
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+}
+
+/// Scanned items by name, in source order
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IdentIndex {
+    names: HashMap<String, Vec<Rc<RefCell<Item>>>>,
+    /// [handle::writes] when the index was built
+    writes: u64,
+}
+
+impl IdentIndex {
+    fn build(items: &[Rc<RefCell<Item>>]) -> Self {
+        let mut names: HashMap<String, Vec<_>> = HashMap::new();
+
+        for item in items {
+            // unparsed items have no name yet
+            let _ = lazy::parse(item);
+            if let Some(ident) = item_ident(&item.borrow()) {
+                names
+                    .entry(ident.to_string())
+                    .or_default()
+                    .push(item.clone());
+            }
+        }
+
+        Self {
+            names,
+            writes: handle::writes(),
+        }
+    }
+}
+
+impl MacroScope {
+    /// Returns the scanned items named `name` in source order, e.g. a struct and the functions
+    /// of the same name
+    ///
+    /// The lookup goes through an index of all names that is built on the first call and
+    /// rebuilt once the scope changes through its own methods or an item is changed with
+    /// [ItemHandle::write]. Call [MacroScope::drop_index] after renaming items through
+    /// [ItemHandle::shared] directly. Parses all items of a lazily parsed scope
+    pub fn find(&self, name: &str) -> Vec<ItemHandle> {
+        let stale = match &*self.idents.borrow() {
+            Some(index) => index.writes != handle::writes(),
+            None => true,
+        };
+        if stale {
+            *self.idents.borrow_mut() = Some(IdentIndex::build(&self.scanned_items()));
+        }
+
+        let idents = self.idents.borrow();
+        let items = idents.as_ref().and_then(|index| index.names.get(name));
+        items
+            .into_iter()
+            .flatten()
+            .map(|item| ItemHandle::from(item.clone()))
+            .collect()
+    }
+
+    /// Returns the struct, enum, union, trait or type alias of the scope that `ty` refers to,
+    /// e.g. the struct an impl block is for or the local type of a field
+    ///
+    /// Only paths with a single segment, optionally after `self::`, are resolved. See
+    /// [MacroScope::find]
+    pub fn definition_of(&self, ty: &Type) -> Option<ItemHandle> {
+        let Type::Path(path) = ty else {
+            return None;
+        };
+        let segments: Vec<_> = path.path.segments.iter().collect();
+        let ident = match (path.qself.is_none(), segments.as_slice()) {
+            (true, [ident]) => &ident.ident,
+            (true, [first, ident]) if first.ident == "self" => &ident.ident,
+            _ => return None,
+        };
+
+        self.find(&ident.to_string()).into_iter().find(|item| {
+            item.read(|item| {
+                matches!(
+                    item,
+                    Item::Struct(_)
+                        | Item::Enum(_)
+                        | Item::Union(_)
+                        | Item::Trait(_)
+                        | Item::Type(_)
+                )
+            })
+        })
+    }
+
+    /// Builds an index of the attributes of all scanned items, speeding up repeated queries for
+    /// different marks on large scopes
+    ///
+    /// Queries with [MatchMode::Exact] and [MatchMode::Prefix] only look at the matching names,
+    /// queries with the default [MatchMode::Contains] compare the mark with every distinct
+    /// attribute name of the scope
+    ///
+    /// The index is a snapshot, attributes added to items by hand afterwards are not found.
+    /// Pushing items, lifting modules and rolling back a transaction drop the index, call this
+    /// again to rebuild it
+    pub fn build_index(&self) {
+        *self.index.borrow_mut() = Some(AttrIndex::build(&self.scanned_items()));
+    }
+
+    /// Drops the index built by [MacroScope::build_index] and the index of names used by
+    /// [MacroScope::find]
+    pub fn drop_index(&self) {
+        *self.index.borrow_mut() = None;
+        *self.idents.borrow_mut() = None;
+    }
+}
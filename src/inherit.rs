@@ -0,0 +1,64 @@
+//! Marks on nested modules that apply to the items inside of them
+
+use syn::Attribute;
+
+use crate::{item_attrs_mut, lazy, take_flag, ItemKind, MacroScope};
+
+impl MacroScope {
+    /// Moves marks matching one of `marks` from nested modules onto the items inside of them
+    ///
+    /// `#[component] mod shapes { struct Circle; struct Square; }` is scanned like two marked
+    /// structs. Items of kinds outside of [ScopeConfig::scan_set] are skipped, nested modules
+    /// pass the marks on to their items and single items opt out with
+    /// `#[macro_scope(no_inherit)]`, which is removed from every emitted item. Only modules lifted by [ScopeConfig::recursive] are
+    /// considered, marks listed in [ScopeConfig::inherit_marks] are moved while parsing with
+    /// [MacroScope::parse_with_config]
+    ///
+    /// [ScopeConfig::scan_set]: crate::ScopeConfig::scan_set
+    /// [ScopeConfig::recursive]: crate::ScopeConfig::recursive
+    /// [ScopeConfig::inherit_marks]: crate::ScopeConfig::inherit_marks
+    pub fn inherit_marks(&self, marks: &[&str]) {
+        if marks.is_empty() {
+            return;
+        }
+        let mode = self.config.match_mode;
+
+        // lifted modules are stored before the modules nested in them, so marks moved onto a
+        // nested module are passed on once its own turn comes
+        for (module, children) in &self.modules {
+            let inherited: Vec<Attribute> = {
+                let mut module = module.borrow_mut();
+                let Some(attrs) = item_attrs_mut(&mut module) else {
+                    continue;
+                };
+
+                let (inherited, kept) = std::mem::take(attrs).into_iter().partition(|attr| {
+                    attr.path()
+                        .get_ident()
+                        .is_some_and(|i| marks.iter().any(|m| mode.matches(&i.to_string(), m)))
+                });
+                *attrs = kept;
+                inherited
+            };
+            if inherited.is_empty() {
+                continue;
+            }
+
+            for child in children {
+                // items that don't parse can't be checked for their kind
+                let _ = lazy::parse(child);
+                let mut child = child.borrow_mut();
+
+                let kind = ItemKind::of(&child);
+                if kind != ItemKind::Mod && !self.config.scan_set.contains(kind) {
+                    continue;
+                }
+                if let Some(attrs) = item_attrs_mut(&mut child) {
+                    if !take_flag(attrs, "no_inherit") {
+                        attrs.extend(inherited.iter().cloned());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Marks on the fields and variants of scanned items
+
+use std::cell::RefCell;
+
+use syn::{Attribute, Fields, Item};
+
+use crate::{lazy, MacroScope, MatchMode};
+
+/// Removes all attributes matching one of `marks`
+fn strip(attrs: &mut Vec<Attribute>, marks: &[&str], mode: MatchMode) {
+    attrs.retain(|attr| {
+        !attr.path().get_ident().is_some_and(|ident| {
+            marks
+                .iter()
+                .any(|mark| mode.matches(&ident.to_string(), mark))
+        })
+    });
+}
+
+fn strip_fields(fields: &mut Fields, marks: &[&str], mode: MatchMode) {
+    for field in fields.iter_mut() {
+        strip(&mut field.attrs, marks, mode);
+    }
+}
+
+/// Returns `true` if an unparsed item may contain one of `marks`, without parsing it
+fn may_contain(item: &RefCell<Item>, marks: &[&str]) -> bool {
+    match &*item.borrow() {
+        Item::Verbatim(tokens) => {
+            let tokens = tokens.to_string();
+            marks.iter().any(|mark| tokens.contains(mark))
+        }
+        _ => true,
+    }
+}
+
+impl MacroScope {
+    /// Removes the marks on fields and variants of the scanned structs, enums and unions
+    ///
+    /// Inner marks are read by templates but are not real attributes, so they have to be
+    /// removed before the items are emitted. Marks listed in [ScopeConfig::inner_marks] are
+    /// stripped by [MacroScope::expand] after all templates ran
+    ///
+    /// [ScopeConfig::inner_marks]: crate::ScopeConfig::inner_marks
+    pub fn strip_inner_marks(&self, marks: &[&str]) {
+        if marks.is_empty() {
+            return;
+        }
+        let mode = self.config.match_mode;
+
+        let items = self.scanned_items();
+        for item in items.iter().filter(|item| may_contain(item, marks)) {
+            // items that don't parse are left for the compiler to report
+            let _ = lazy::parse(item);
+
+            match &mut *item.borrow_mut() {
+                Item::Struct(item) => strip_fields(&mut item.fields, marks, mode),
+                Item::Enum(item) => {
+                    for variant in &mut item.variants {
+                        strip(&mut variant.attrs, marks, mode);
+                        strip_fields(&mut variant.fields, marks, mode);
+                    }
+                }
+                Item::Union(item) => {
+                    for field in &mut item.fields.named {
+                        strip(&mut field.attrs, marks, mode);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
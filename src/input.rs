@@ -0,0 +1,74 @@
+//! The different inputs a scope macro can be applied to
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{
+    parse::{Parse, ParseStream},
+    Item, ItemMod,
+};
+
+use crate::{MacroScope, ScopeConfig};
+
+/// The tokens a scope macro was applied to, normalized so every kind of input can be turned into
+/// a [MacroScope] with [MacroScope::from_input]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeInput {
+    /// A module, `#[my_scope] mod scope { .. }`
+    Module(ItemMod),
+    /// Any other single item, e.g. `#[my_scope] struct Foo;` or an impl block
+    SingleItem(Box<Item>),
+    /// Several items or inner attributes, e.g. the content of a file read by a build script or
+    /// the input of a function-like macro
+    File(syn::File),
+}
+
+impl Parse for ScopeInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut file: syn::File = input.parse()?;
+
+        if file.shebang.is_some() || !file.attrs.is_empty() || file.items.len() != 1 {
+            return Ok(Self::File(file));
+        }
+
+        Ok(match file.items.pop().unwrap() {
+            Item::Mod(module) => Self::Module(module),
+            item => Self::SingleItem(Box::new(item)),
+        })
+    }
+}
+
+impl ToTokens for ScopeInput {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Self::Module(module) => module.to_tokens(tokens),
+            Self::SingleItem(item) => item.to_tokens(tokens),
+            Self::File(file) => file.to_tokens(tokens),
+        }
+    }
+}
+
+impl MacroScope {
+    /// Creates a scope from any [ScopeInput] according to `config`
+    ///
+    /// Modules become the scope like with [MacroScope::parse_with_config], other inputs become a
+    /// scope without a module shell. The input is already parsed, so [ScopeConfig::lazy] has no
+    /// effect
+    ///
+    /// ```
+    /// # use macro_scope::{MacroScope, ScopeInput};
+    /// let input: ScopeInput = syn::parse_str("#[system] fn update() {}").unwrap();
+    /// assert!(matches!(input, ScopeInput::SingleItem(_)));
+    ///
+    /// let scope = MacroScope::from_input(input, Default::default()).unwrap();
+    /// assert_eq!(scope.get_marked("system").len(), 1);
+    /// ```
+    pub fn from_input(input: ScopeInput, config: ScopeConfig) -> syn::Result<Self> {
+        let scope = match input {
+            ScopeInput::Module(module) => Self::from_module(module),
+            ScopeInput::SingleItem(item) => Self::from_items(vec![*item]),
+            ScopeInput::File(file) => Self::from_file(file),
+        };
+
+        scope.configure(config)
+    }
+}
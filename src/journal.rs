@@ -0,0 +1,117 @@
+//! Opt-in log of the mutations performed on a scope, for debugging multi-pass pipelines
+
+use std::fmt;
+
+use quote::ToTokens;
+use syn::Attribute;
+
+use crate::{ItemKey, MacroScope, MarkPolicy, SharedMarkedItem};
+
+/// A single change to the items of a scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mutation {
+    /// A mark was removed from an item by a scan
+    RemovedMark { item: ItemKey, mark: String },
+    /// A mark was replaced by another attribute, see [MarkPolicy::ReplaceWith]
+    ReplacedMark {
+        item: ItemKey,
+        mark: String,
+        with: String,
+    },
+    /// An item was appended to the scope
+    PushedItem(ItemKey),
+    /// An item was changed in place, only recorded by passes calling [MacroScope::record]
+    ReplacedItem(ItemKey),
+    /// A [transaction](MacroScope::transaction) failed and its changes were undone
+    RolledBack,
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RemovedMark { item, mark } => write!(f, "removed `{mark}` from {item}"),
+            Self::ReplacedMark { item, mark, with } => {
+                write!(f, "replaced `{mark}` with `{with}` on {item}")
+            }
+            Self::PushedItem(item) => write!(f, "pushed {item}"),
+            Self::ReplacedItem(item) => write!(f, "replaced {item}"),
+            Self::RolledBack => f.write_str("rolled back a transaction"),
+        }
+    }
+}
+
+/// A [Mutation] together with the [Pipeline](crate::Pipeline) pass that performed it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub pass: Option<String>,
+    pub mutation: Mutation,
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.pass {
+            Some(pass) => write!(f, "[{pass}] {}", self.mutation),
+            None => write!(f, "{}", self.mutation),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Journal {
+    pub pass: Option<String>,
+    pub entries: Vec<JournalEntry>,
+}
+
+fn attr_string(attr: &Attribute) -> String {
+    format!("#[{}]", attr.meta.to_token_stream())
+}
+
+impl MacroScope {
+    /// Appends a mutation to the journal if [MacroScope::journaling] is enabled
+    ///
+    /// Scans and pushes are recorded automatically, passes that change items in place record
+    /// that themselves
+    pub fn record(&self, mutation: Mutation) {
+        if !self.journaling {
+            return;
+        }
+
+        let mut journal = self.journal.borrow_mut();
+        let pass = journal.pass.clone();
+        journal.entries.push(JournalEntry { pass, mutation });
+    }
+
+    /// Returns all recorded mutations in the order they were performed
+    pub fn journal(&self) -> Vec<JournalEntry> {
+        self.journal.borrow().entries.clone()
+    }
+
+    /// Sets the pass future mutations are attributed to
+    pub(crate) fn set_pass(&self, pass: Option<String>) {
+        self.journal.borrow_mut().pass = pass;
+    }
+
+    /// Records how the marks of freshly scanned items were handled
+    pub(crate) fn record_marks<'a>(
+        &self,
+        marked: impl IntoIterator<Item = &'a SharedMarkedItem<syn::Item>>,
+    ) {
+        if !self.journaling {
+            return;
+        }
+
+        for marked in marked {
+            let item = ItemKey::of(&marked.item.borrow());
+            let mark = attr_string(&marked.mark);
+            match &self.config.mark_policy {
+                MarkPolicy::Remove => self.record(Mutation::RemovedMark { item, mark }),
+                MarkPolicy::Keep => (),
+                MarkPolicy::ReplaceWith(with) => self.record(Mutation::ReplacedMark {
+                    item,
+                    mark,
+                    with: attr_string(with),
+                }),
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{collections::HashSet, fmt};
+
+use syn::Item;
+
+/// The kind of an [Item] without its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ItemKind {
+    Const,
+    Enum,
+    ExternCrate,
+    Fn,
+    ForeignMod,
+    Impl,
+    Macro,
+    Mod,
+    Static,
+    Struct,
+    Trait,
+    TraitAlias,
+    Type,
+    Union,
+    Use,
+    Verbatim,
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 16] = [
+        Self::Const,
+        Self::Enum,
+        Self::ExternCrate,
+        Self::Fn,
+        Self::ForeignMod,
+        Self::Impl,
+        Self::Macro,
+        Self::Mod,
+        Self::Static,
+        Self::Struct,
+        Self::Trait,
+        Self::TraitAlias,
+        Self::Type,
+        Self::Union,
+        Self::Use,
+        Self::Verbatim,
+    ];
+
+    pub fn of(item: &Item) -> Self {
+        use Item as I;
+
+        match item {
+            I::Const(_) => Self::Const,
+            I::Enum(_) => Self::Enum,
+            I::ExternCrate(_) => Self::ExternCrate,
+            I::Fn(_) => Self::Fn,
+            I::ForeignMod(_) => Self::ForeignMod,
+            I::Impl(_) => Self::Impl,
+            I::Macro(_) => Self::Macro,
+            I::Mod(_) => Self::Mod,
+            I::Static(_) => Self::Static,
+            I::Struct(_) => Self::Struct,
+            I::Trait(_) => Self::Trait,
+            I::TraitAlias(_) => Self::TraitAlias,
+            I::Type(_) => Self::Type,
+            I::Union(_) => Self::Union,
+            I::Use(_) => Self::Use,
+            _ => Self::Verbatim,
+        }
+    }
+
+    /// The keyword used to declare items of this kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Const => "const",
+            Self::Enum => "enum",
+            Self::ExternCrate => "extern crate",
+            Self::Fn => "fn",
+            Self::ForeignMod => "extern block",
+            Self::Impl => "impl",
+            Self::Macro => "macro",
+            Self::Mod => "mod",
+            Self::Static => "static",
+            Self::Struct => "struct",
+            Self::Trait => "trait",
+            Self::TraitAlias => "trait alias",
+            Self::Type => "type",
+            Self::Union => "union",
+            Self::Use => "use",
+            Self::Verbatim => "verbatim item",
+        }
+    }
+}
+
+impl ItemKind {
+    /// Inverse of [ItemKind::name]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The item kinds that are scanned for marks
+///
+/// Defaults to structs, enums, traits, functions, modules and extern crates, together with
+/// verbatim items syn can't parse, whose marks are only found at the token level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanSet(pub HashSet<ItemKind>);
+
+impl ScanSet {
+    /// Scan only the given kinds
+    pub fn only(kinds: impl IntoIterator<Item = ItemKind>) -> Self {
+        Self(kinds.into_iter().collect())
+    }
+
+    pub fn contains(&self, kind: ItemKind) -> bool {
+        self.0.contains(&kind)
+    }
+}
+
+impl Default for ScanSet {
+    fn default() -> Self {
+        use ItemKind as K;
+        Self::only([
+            K::Struct,
+            K::Enum,
+            K::Trait,
+            K::Fn,
+            K::Mod,
+            K::ExternCrate,
+            K::Verbatim,
+        ])
+    }
+}
@@ -0,0 +1,260 @@
+//! Lazy parsing of scope items
+//!
+//! The module body is split into the token ranges of its items without parsing them, every item
+//! is stored as an [Item::Verbatim] and only parsed into its real variant once a mark is found in
+//! its leading attributes. Items that are never needed are emitted as the tokens they were written
+//! as
+
+#[cfg(feature = "full")]
+use std::cell::RefCell;
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+#[cfg(feature = "full")]
+use quote::ToTokens;
+#[cfg(feature = "full")]
+use syn::{
+    braced,
+    parse::{ParseStream, Parser},
+    token, Attribute, Ident, Item, ItemMod, Token, Visibility,
+};
+
+#[cfg(feature = "full")]
+use crate::{find_attribute, MatchMode};
+
+/// Parses the module shell and splits its content into unparsed [Item::Verbatim] items
+#[cfg(feature = "full")]
+pub(crate) fn parse_module(input: ParseStream) -> syn::Result<(ItemMod, Vec<Item>)> {
+    let mut attrs = input.call(Attribute::parse_outer)?;
+    let vis: Visibility = input.parse()?;
+    let unsafety: Option<Token![unsafe]> = input.parse()?;
+    let mod_token: Token![mod] = input.parse()?;
+    let ident: Ident = input.parse()?;
+
+    if input.peek(Token![;]) {
+        let semi = Some(input.parse()?);
+        return Ok((
+            ItemMod {
+                attrs,
+                vis,
+                unsafety,
+                mod_token,
+                ident,
+                content: None,
+                semi,
+            },
+            Vec::new(),
+        ));
+    }
+
+    let content;
+    let brace: token::Brace = braced!(content in input);
+    attrs.extend(content.call(Attribute::parse_inner)?);
+    let items = split_items(content.parse()?)
+        .into_iter()
+        .map(Item::Verbatim)
+        .collect();
+
+    let module = ItemMod {
+        attrs,
+        vis,
+        unsafety,
+        mod_token,
+        ident,
+        content: Some((brace, Vec::new())),
+        semi: None,
+    };
+
+    Ok((module, items))
+}
+
+/// Returns `true` if the item starting with `tokens` always ends with a `;`, even if it
+/// contains braces
+fn ends_with_semi(tokens: &[TokenTree]) -> bool {
+    let idents: Vec<String> = tokens
+        .iter()
+        .filter_map(|tt| match tt {
+            TokenTree::Ident(ident) => Some(ident.to_string()),
+            _ => None,
+        })
+        .take(3)
+        .collect();
+
+    let mut idents = idents.iter().map(String::as_str);
+    let mut first = idents.next();
+    if first == Some("pub") {
+        first = idents.next();
+    }
+
+    match first {
+        Some("static" | "type" | "use") => true,
+        Some("const") => !matches!(idents.next(), Some("fn" | "unsafe" | "async" | "extern")),
+        Some("extern") => idents.next() == Some("crate"),
+        _ => false,
+    }
+}
+
+/// Splits the tokens of a module body into the tokens of its items
+///
+/// Items end with a `;` or with a brace group outside of generic arguments, except for items like
+/// `const` and `use` that can contain braces but always end with a `;`
+pub(crate) fn split_items(tokens: TokenStream) -> Vec<TokenStream> {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut items = Vec::new();
+    let mut start = 0;
+
+    while start < tokens.len() {
+        // skip the outer attributes, their brackets are never a terminator
+        let mut i = start;
+        while let (Some(TokenTree::Punct(p)), Some(TokenTree::Group(g))) =
+            (tokens.get(i), tokens.get(i + 1))
+        {
+            if p.as_char() != '#' || g.delimiter() != Delimiter::Bracket {
+                break;
+            }
+            i += 2;
+        }
+
+        let semi_only = ends_with_semi(&tokens[i..]);
+        let mut angle_depth = 0usize;
+        let mut prev_joint: Option<char> = None;
+
+        while i < tokens.len() {
+            let tt = &tokens[i];
+            i += 1;
+
+            match tt {
+                TokenTree::Punct(p) => {
+                    match p.as_char() {
+                        ';' if semi_only || angle_depth == 0 => break,
+                        '<' => angle_depth += 1,
+                        // `->` and `=>` are not closing angle brackets
+                        '>' if !matches!(prev_joint, Some('-' | '=')) => {
+                            angle_depth = angle_depth.saturating_sub(1)
+                        }
+                        _ => (),
+                    }
+                    prev_joint = (p.spacing() == Spacing::Joint).then(|| p.as_char());
+                    continue;
+                }
+                TokenTree::Group(g)
+                    if g.delimiter() == Delimiter::Brace && !semi_only && angle_depth == 0 =>
+                {
+                    break
+                }
+                _ => (),
+            }
+
+            prev_joint = None;
+        }
+
+        items.push(tokens[start..i].iter().cloned().collect());
+        start = i;
+    }
+
+    items
+}
+
+/// Returns `true` if the unparsed item is a module
+#[cfg(feature = "full")]
+pub(crate) fn is_module(tokens: &TokenStream) -> bool {
+    let mut tokens = tokens.clone().into_iter().peekable();
+
+    loop {
+        match tokens.next() {
+            // attributes and the visibility restriction
+            Some(TokenTree::Punct(p)) if p.as_char() == '#' => {
+                tokens.next();
+            }
+            Some(TokenTree::Group(_)) => (),
+            Some(TokenTree::Ident(ident)) => match ident.to_string().as_str() {
+                "pub" | "unsafe" => (),
+                keyword => return keyword == "mod",
+            },
+            _ => return false,
+        }
+    }
+}
+
+/// Splits the outer attributes at the start of `tokens` from the remaining tokens
+#[cfg(feature = "full")]
+pub(crate) fn split_attrs(tokens: &TokenStream) -> Option<(Vec<Attribute>, TokenStream)> {
+    let parser = |input: ParseStream| {
+        let attrs = input.call(Attribute::parse_outer)?;
+        Ok((attrs, input.parse::<TokenStream>()?))
+    };
+
+    parser.parse2(tokens.clone()).ok()
+}
+
+/// Parses only the outer attributes at the start of `tokens`
+#[cfg(feature = "full")]
+pub(crate) fn leading_attrs(tokens: &TokenStream) -> Vec<Attribute> {
+    split_attrs(tokens)
+        .map(|(attrs, _)| attrs)
+        .unwrap_or_default()
+}
+
+/// Joins attributes and the remaining tokens of an item split by [split_attrs]
+#[cfg(feature = "full")]
+pub(crate) fn join_attrs(attrs: &[Attribute], rest: TokenStream) -> TokenStream {
+    let mut tokens: TokenStream = attrs.iter().map(|attr| attr.to_token_stream()).collect();
+    tokens.extend(rest);
+    tokens
+}
+
+/// Token level check whether one of the leading attributes starts with an identifier matching
+/// `mark`, without parsing the attributes
+#[cfg(feature = "full")]
+pub(crate) fn leading_attr_matches(tokens: &TokenStream, mark: &str, mode: MatchMode) -> bool {
+    let mut tokens = tokens.clone().into_iter();
+
+    while let (Some(TokenTree::Punct(p)), Some(TokenTree::Group(g))) =
+        (tokens.next(), tokens.next())
+    {
+        if p.as_char() != '#' || g.delimiter() != Delimiter::Bracket {
+            break;
+        }
+
+        if let Some(TokenTree::Ident(ident)) = g.stream().into_iter().next() {
+            if mode.matches(&ident.to_string(), mark) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Parses an unparsed item if one of its leading attributes matches `mark`
+///
+/// Items that fail to parse are kept as they are, so the compiler reports the error at the
+/// original tokens
+#[cfg(feature = "full")]
+pub(crate) fn parse_if_marked(item: &RefCell<Item>, mark: &str, mode: MatchMode) {
+    let tokens = match &*item.borrow() {
+        Item::Verbatim(tokens) => tokens.clone(),
+        _ => return,
+    };
+
+    if !leading_attr_matches(&tokens, mark, mode)
+        || find_attribute(&leading_attrs(&tokens), mark, mode).is_none()
+    {
+        return;
+    }
+
+    if let Ok(parsed) = syn::parse2::<Item>(tokens) {
+        *item.borrow_mut() = parsed;
+    }
+}
+
+/// Parses an unparsed item into its real variant
+#[cfg(feature = "full")]
+pub(crate) fn parse(item: &RefCell<Item>) -> syn::Result<()> {
+    let tokens = match &*item.borrow() {
+        Item::Verbatim(tokens) => tokens.clone(),
+        _ => return Ok(()),
+    };
+
+    *item.borrow_mut() = syn::parse2(tokens)?;
+    Ok(())
+}
@@ -1,68 +1,587 @@
+use std::{cell::RefCell, rc::Rc};
+#[cfg(feature = "full")]
+use std::{collections::HashMap, str::FromStr};
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+#[cfg(feature = "full")]
+use proc_macro2::TokenStream;
+#[cfg(feature = "full")]
+use quote::{quote, quote_spanned, ToTokens};
+#[cfg(feature = "full")]
+use syn::{
+    parse::{Parse, Parser},
+    parse_quote, Item, ItemMod,
+};
+use syn::{spanned::Spanned, Attribute, Expr, ExprLit, Lit, Meta, MetaNameValue};
 
-use syn::{parse::Parse, Attribute, Item, ItemMod, ItemStruct, ItemTrait};
+#[cfg(feature = "full")]
+mod analysis;
+#[cfg(feature = "full")]
+pub mod build;
+mod config;
+#[cfg(feature = "full")]
+mod convert;
+#[cfg(feature = "full")]
+mod diagnostics;
+#[cfg(feature = "full")]
+mod diff;
+#[cfg(feature = "full")]
+mod dump;
+#[cfg(feature = "full")]
+pub mod emit;
+#[cfg(feature = "full")]
+mod expanded;
+#[cfg(feature = "full")]
+pub mod generate;
+#[cfg(feature = "full")]
+pub mod generics;
+#[cfg(feature = "full")]
+mod gensym;
+#[cfg(feature = "full")]
+mod handle;
+#[cfg(feature = "full")]
+mod index;
+#[cfg(feature = "full")]
+mod inherit;
+#[cfg(feature = "full")]
+mod inner;
+#[cfg(feature = "full")]
+mod input;
+#[cfg(feature = "full")]
+mod journal;
+#[cfg(feature = "full")]
+mod kind;
+mod lazy;
+mod location;
+mod meta;
+#[cfg(feature = "full")]
+mod metadata;
+mod minimal;
+#[cfg(feature = "full")]
+mod nested;
+#[cfg(feature = "full")]
+mod panic;
+#[cfg(feature = "full")]
+mod params;
+#[cfg(feature = "full")]
+mod pipeline;
+#[cfg(feature = "full")]
+mod plugin;
+#[cfg(feature = "full")]
+mod relations;
+#[cfg(feature = "full")]
+mod rename;
+#[cfg(feature = "full")]
+mod report;
+#[cfg(feature = "full")]
+pub mod schema;
+#[cfg(feature = "full")]
+mod select;
+#[cfg(feature = "full")]
+mod template;
+#[cfg(feature = "full")]
+mod testing;
+#[cfg(feature = "full")]
+mod trait_items;
+#[cfg(feature = "full")]
+mod transaction;
+#[cfg(feature = "full")]
+mod uses;
+#[cfg(feature = "full")]
+mod visibility;
+
+#[cfg(feature = "full")]
+pub use analysis::{ArgValue, AttributeAnalysis, ImplAnalysis, ItemAnalysis, ScopeAnalysis};
+pub use config::MatchMode;
+#[cfg(feature = "full")]
+pub use config::{MarkPolicy, MissingBody, ScopeConfig};
+#[cfg(feature = "full")]
+pub use diagnostics::{Diagnostics, SkipReason, SkippedItem};
+#[cfg(feature = "full")]
+pub use diff::{ItemKey, ScopeDiff, TokenHashes};
+#[cfg(feature = "full")]
+pub use handle::ItemHandle;
+#[cfg(feature = "full")]
+pub use input::ScopeInput;
+#[cfg(feature = "full")]
+pub use journal::{JournalEntry, Mutation};
+#[cfg(feature = "full")]
+pub use kind::{ItemKind, ScanSet};
+pub use location::SourceLocation;
+pub use meta::MetaTree;
+pub use minimal::{MinimalFn, MinimalItem, MinimalScope};
+#[cfg(feature = "full")]
+pub use params::MarkedParam;
+#[cfg(feature = "full")]
+pub use pipeline::{Pass, Pipeline};
+#[cfg(feature = "full")]
+pub use plugin::{PluginSet, ScopePlugin};
+#[cfg(feature = "full")]
+pub use rename::RenameReport;
+#[cfg(feature = "full")]
+pub use report::{ExpansionReport, MarkReport};
+#[cfg(feature = "full")]
+pub use select::Selector;
+#[cfg(feature = "full")]
+use template::Templates;
+#[cfg(feature = "full")]
+pub use template::{MarkHandler, Template};
+#[cfg(feature = "full")]
+pub use trait_items::TraitItems;
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
-struct MarkedItem<T> {
+pub struct MarkedItem<T> {
     pub mark: Attribute,
     pub item: T,
+    /// Position of the mark in the attributes of the item when it was found
+    pub index: usize,
+    /// Position of the item in [MacroScope::scanned_items] when it was found, sorting marked
+    /// items by their [source key](MarkedItem::source_key) recovers the source order
+    pub position: usize,
+}
+
+/// Where a mark was found in the scope, ordered like the marks appear in the source
+///
+/// Marks are ordered by their item and then by their position in the attributes of the item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceOrder {
+    /// See [MarkedItem::position]
+    pub position: usize,
+    /// See [MarkedItem::index]
+    pub index: usize,
 }
 
-type SharedMarkedItem<T> = MarkedItem<Rc<RefCell<T>>>;
+pub type SharedMarkedItem<T> = MarkedItem<Rc<RefCell<T>>>;
 
 impl<T> MarkedItem<T> {
     pub fn new(mark: Attribute, item: T) -> Self {
-        Self { mark, item }
+        Self {
+            mark,
+            item,
+            index: 0,
+            position: 0,
+        }
+    }
+
+    /// Returns the key to sort marked items by to recover the source order, e.g. after combining
+    /// the items of several marks
+    ///
+    /// ```
+    /// # #[cfg(feature = "full")] {
+    /// # use macro_scope::{MacroScope, MarkedItem};
+    /// let scope: MacroScope = "mod scope { #[b] fn first() {} #[a] fn second() {} }"
+    ///     .parse()
+    ///     .unwrap();
+    ///
+    /// let mut marked = scope.get_marked("a");
+    /// marked.extend(scope.get_marked("b"));
+    /// marked.sort_by_key(MarkedItem::source_key);
+    /// assert_eq!(marked[0].mark_name(), "b");
+    /// # }
+    /// ```
+    pub fn source_key(&self) -> SourceOrder {
+        SourceOrder {
+            position: self.position,
+            index: self.index,
+        }
+    }
+
+    /// Replaces the item, keeping everything known about the mark
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> MarkedItem<U> {
+        MarkedItem {
+            mark: self.mark,
+            item: f(self.item),
+            index: self.index,
+            position: self.position,
+        }
+    }
+
+    /// Separates the item from the mark
+    pub fn split(self) -> (T, MarkedItem<()>) {
+        let mut item = None;
+        let marked = self.map(|i| item = Some(i));
+        (item.unwrap(), marked)
+    }
+
+    /// Returns the parsed mark, syn parses the [Meta] together with the attribute so this never
+    /// fails
+    pub fn meta(&self) -> &Meta {
+        &self.mark.meta
+    }
+
+    /// Returns `true` for marks without arguments like `#[system]`
+    pub fn is_path(&self) -> bool {
+        matches!(self.mark.meta, Meta::Path(_))
+    }
+
+    /// Returns the arguments of a list mark like `#[system(order = 1)]`
+    pub fn list(&self) -> Option<&syn::MetaList> {
+        match &self.mark.meta {
+            Meta::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Returns the assignment of a name-value mark like `#[route = "/users"]`
+    pub fn name_value(&self) -> Option<&MetaNameValue> {
+        match &self.mark.meta {
+            Meta::NameValue(nv) => Some(nv),
+            _ => None,
+        }
+    }
+
+    /// Returns the literal of a name-value mark like `#[route = "/users"]`
+    pub fn value(&self) -> Option<&Lit> {
+        match &self.name_value()?.value {
+            Expr::Lit(ExprLit { lit, .. }) => Some(lit),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a name-value mark if it is a string literal
+    pub fn value_str(&self) -> Option<String> {
+        match self.value()? {
+            Lit::Str(s) => Some(s.value()),
+            _ => None,
+        }
+    }
+
+    /// Parses the arguments of the mark into a [MetaTree]
+    pub fn meta_tree(&self) -> syn::Result<MetaTree> {
+        MetaTree::from_attribute(&self.mark)
+    }
+
+    /// Returns where the mark was written, which is right above the marked item
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation::of(self.mark.span())
+    }
+
+    /// Returns the `cfg(...)` argument of a conditional mark like `#[mark(cfg(feature = "net"))]`
+    ///
+    /// Code generated for the item by [MacroScope::expand] is gated on the same predicate
+    pub fn cfg(&self) -> Option<MetaTree> {
+        let tree = self.meta_tree().ok()?;
+        tree.children()
+            .iter()
+            .find(|child| child.is("cfg") && matches!(child, MetaTree::List(..)))
+            .cloned()
+    }
+}
+
+#[cfg(feature = "full")]
+impl SharedMarkedItem<Item> {
+    /// Puts the mark back at the position it was found at
+    ///
+    /// Does nothing if the mark is still there, e.g. when it was scanned with
+    /// [MarkPolicy::Keep]
+    pub fn restore_mark(&self) {
+        restore(&self.mark, self.index, &mut self.item.borrow_mut());
+    }
+}
+
+#[cfg(feature = "full")]
+impl MarkedItem<Item> {
+    /// Puts the mark back at the position it was found at
+    pub fn restore_mark(&mut self) {
+        restore(&self.mark, self.index, &mut self.item);
+    }
+}
+
+#[cfg(feature = "full")]
+impl MarkedItem<syn::ItemExternCrate> {
+    /// Returns the name the marked crate is available under, the rename of
+    /// `extern crate foo as bar;` if present
+    pub fn crate_ident(&self) -> &syn::Ident {
+        match &self.item.rename {
+            Some((_, rename)) => rename,
+            None => &self.item.ident,
+        }
     }
 }
 
-/// Returns the index of the first [Attribute] that contains a given name if found
-fn find_attribute(attrs: &[Attribute], name: &str) -> Option<(usize, String)> {
+/// Inserts `mark` back into the attributes of `item` at `index`
+#[cfg(feature = "full")]
+fn restore(mark: &Attribute, index: usize, item: &mut Item) {
+    if let Item::Verbatim(tokens) = item {
+        if let Some((mut attrs, rest)) = lazy::split_attrs(tokens) {
+            if attrs.get(index) != Some(mark) {
+                attrs.insert(index.min(attrs.len()), mark.clone());
+                *tokens = lazy::join_attrs(&attrs, rest);
+            }
+        }
+        return;
+    }
+
+    if let Some(attrs) = item_attrs_mut(item) {
+        if attrs.get(index) != Some(mark) {
+            attrs.insert(index.min(attrs.len()), mark.clone());
+        }
+    }
+}
+
+/// Which items are emitted by [MacroScope::expand]
+#[cfg(feature = "full")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EmitMode {
+    /// The whole module with all of its items
+    #[default]
+    All,
+    /// Only the items carrying one of the given marks, without the surrounding module
+    OnlyMarked(Vec<String>),
+}
+
+/// The code a template generated for a single marked item
+#[cfg(feature = "full")]
+#[derive(Debug, Clone)]
+pub struct Generated {
+    /// The mark of the template that generated the code
+    pub template: String,
+    pub item: SharedMarkedItem<Item>,
+    pub tokens: TokenStream,
+    /// The test items generated by [Template::tests], without the surrounding test module
+    pub tests: TokenStream,
+}
+
+#[cfg(feature = "full")]
+impl Generated {
+    /// Concatenates the generated code and returns it with the matched items
+    fn concat(generated: Vec<Generated>) -> (TokenStream, Matched) {
+        let mut tokens = TokenStream::new();
+        let mut matched = Vec::new();
+
+        for generated in generated {
+            tokens.extend(generated.tokens);
+            matched.push((generated.template, generated.item.item));
+        }
+
+        (tokens, matched)
+    }
+}
+
+/// The result of [MacroScope::expand_per_item]
+#[cfg(feature = "full")]
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    /// The items according to [MacroScope::emit_mode], without generated code
+    pub passthrough: TokenStream,
+    /// All generated code in the order it was generated, followed by the merged test module
+    pub generated: TokenStream,
+    /// The generated code for every marked item, in the same order
+    pub per_item: Vec<Generated>,
+}
+
+/// Returns the index of the first [Attribute] that matches a given name if found
+fn find_attribute(attrs: &[Attribute], name: &str, mode: MatchMode) -> Option<(usize, String)> {
     for (index, struct_attrib) in attrs.iter().enumerate() {
         let path = struct_attrib.path();
 
-        match path.get_ident() {
-            Some(ident) => {
-                let ident = ident.to_string();
-                if ident.contains(name) {
-                    return Some((index, ident));
-                }
+        if let Some(ident) = path.get_ident() {
+            let ident = ident.to_string();
+            if mode.matches(&ident, name) {
+                return Some((index, ident));
             }
-            None => (),
         }
     }
 
     None
 }
 
-/// Return all Items that contain the given mark, also removes the mark from the item and return it
-/// as a [MarkedItem]
+/// Returns the attributes of any item except [Item::Verbatim]
+#[cfg(feature = "full")]
+fn item_attrs(item: &Item) -> Option<&[Attribute]> {
+    use Item as I;
+
+    match item {
+        I::Const(i) => Some(&i.attrs),
+        I::Enum(i) => Some(&i.attrs),
+        I::ExternCrate(i) => Some(&i.attrs),
+        I::Fn(i) => Some(&i.attrs),
+        I::ForeignMod(i) => Some(&i.attrs),
+        I::Impl(i) => Some(&i.attrs),
+        I::Macro(i) => Some(&i.attrs),
+        I::Mod(i) => Some(&i.attrs),
+        I::Static(i) => Some(&i.attrs),
+        I::Struct(i) => Some(&i.attrs),
+        I::Trait(i) => Some(&i.attrs),
+        I::TraitAlias(i) => Some(&i.attrs),
+        I::Type(i) => Some(&i.attrs),
+        I::Union(i) => Some(&i.attrs),
+        I::Use(i) => Some(&i.attrs),
+        _ => None,
+    }
+}
+
+/// Mutable version of [item_attrs]
+#[cfg(feature = "full")]
+fn item_attrs_mut(item: &mut Item) -> Option<&mut Vec<Attribute>> {
+    use Item as I;
+
+    match item {
+        I::Const(i) => Some(&mut i.attrs),
+        I::Enum(i) => Some(&mut i.attrs),
+        I::ExternCrate(i) => Some(&mut i.attrs),
+        I::Fn(i) => Some(&mut i.attrs),
+        I::ForeignMod(i) => Some(&mut i.attrs),
+        I::Impl(i) => Some(&mut i.attrs),
+        I::Macro(i) => Some(&mut i.attrs),
+        I::Mod(i) => Some(&mut i.attrs),
+        I::Static(i) => Some(&mut i.attrs),
+        I::Struct(i) => Some(&mut i.attrs),
+        I::Trait(i) => Some(&mut i.attrs),
+        I::TraitAlias(i) => Some(&mut i.attrs),
+        I::Type(i) => Some(&mut i.attrs),
+        I::Union(i) => Some(&mut i.attrs),
+        I::Use(i) => Some(&mut i.attrs),
+        _ => None,
+    }
+}
+
+/// Returns the visibility of items that have one, [None] for impls, macros and the like
+#[cfg(feature = "full")]
+fn item_vis(item: &Item) -> Option<&syn::Visibility> {
+    use Item as I;
+
+    match item {
+        I::Const(i) => Some(&i.vis),
+        I::Enum(i) => Some(&i.vis),
+        I::ExternCrate(i) => Some(&i.vis),
+        I::Fn(i) => Some(&i.vis),
+        I::Mod(i) => Some(&i.vis),
+        I::Static(i) => Some(&i.vis),
+        I::Struct(i) => Some(&i.vis),
+        I::Trait(i) => Some(&i.vis),
+        I::TraitAlias(i) => Some(&i.vis),
+        I::Type(i) => Some(&i.vis),
+        I::Union(i) => Some(&i.vis),
+        I::Use(i) => Some(&i.vis),
+        _ => None,
+    }
+}
+
+/// Mutable version of [item_vis]
+#[cfg(feature = "full")]
+fn item_vis_mut(item: &mut Item) -> Option<&mut syn::Visibility> {
+    use Item as I;
+
+    match item {
+        I::Const(i) => Some(&mut i.vis),
+        I::Enum(i) => Some(&mut i.vis),
+        I::ExternCrate(i) => Some(&mut i.vis),
+        I::Fn(i) => Some(&mut i.vis),
+        I::Mod(i) => Some(&mut i.vis),
+        I::Static(i) => Some(&mut i.vis),
+        I::Struct(i) => Some(&mut i.vis),
+        I::Trait(i) => Some(&mut i.vis),
+        I::TraitAlias(i) => Some(&mut i.vis),
+        I::Type(i) => Some(&mut i.vis),
+        I::Union(i) => Some(&mut i.vis),
+        I::Use(i) => Some(&mut i.vis),
+        _ => None,
+    }
+}
+
+/// Returns the name of items that introduce one, [None] for impls, uses and the like
+#[cfg(feature = "full")]
+fn item_ident(item: &Item) -> Option<&syn::Ident> {
+    use Item as I;
+
+    match item {
+        I::Const(i) => Some(&i.ident),
+        I::Enum(i) => Some(&i.ident),
+        I::ExternCrate(i) => Some(&i.ident),
+        I::Fn(i) => Some(&i.sig.ident),
+        I::Macro(i) => i.ident.as_ref(),
+        I::Mod(i) => Some(&i.ident),
+        I::Static(i) => Some(&i.ident),
+        I::Struct(i) => Some(&i.ident),
+        I::Trait(i) => Some(&i.ident),
+        I::TraitAlias(i) => Some(&i.ident),
+        I::Type(i) => Some(&i.ident),
+        I::Union(i) => Some(&i.ident),
+        _ => None,
+    }
+}
+
+/// Mutable version of [item_ident]
+#[cfg(feature = "full")]
+fn item_ident_mut(item: &mut Item) -> Option<&mut syn::Ident> {
+    use Item as I;
+
+    match item {
+        I::Const(i) => Some(&mut i.ident),
+        I::Enum(i) => Some(&mut i.ident),
+        I::ExternCrate(i) => Some(&mut i.ident),
+        I::Fn(i) => Some(&mut i.sig.ident),
+        I::Macro(i) => i.ident.as_mut(),
+        I::Mod(i) => Some(&mut i.ident),
+        I::Static(i) => Some(&mut i.ident),
+        I::Struct(i) => Some(&mut i.ident),
+        I::Trait(i) => Some(&mut i.ident),
+        I::TraitAlias(i) => Some(&mut i.ident),
+        I::Type(i) => Some(&mut i.ident),
+        I::Union(i) => Some(&mut i.ident),
+        _ => None,
+    }
+}
+
+/// Returns the attributes of an item if its kind is part of `scan_set`
+#[cfg(feature = "full")]
+fn scanned_attrs<'a>(item: &'a Item, scan_set: &ScanSet) -> Option<&'a [Attribute]> {
+    if !scan_set.contains(ItemKind::of(item)) {
+        return None;
+    }
+
+    item_attrs(item)
+}
+
+/// Mutable version of [scanned_attrs]
+#[cfg(feature = "full")]
+fn scanned_attrs_mut<'a>(item: &'a mut Item, scan_set: &ScanSet) -> Option<&'a mut Vec<Attribute>> {
+    if !scan_set.contains(ItemKind::of(item)) {
+        return None;
+    }
+
+    item_attrs_mut(item)
+}
+
+/// Return all Items that contain the given mark, the mark is returned as part of a [MarkedItem]
+/// and handled on the item according to [ScopeConfig::mark_policy]
 ///
 /// We use a attribute macro as a way to mark items, so that we can further process them in the
 /// proc_macros
+///
+/// `items` are the candidates together with their position in the scope
+#[cfg(feature = "full")]
 fn get_items_by_mark_prefix<'a>(
-    items: &[Rc<RefCell<Item>>],
-    mark: &'a str,
+    items: impl IntoIterator<Item = (usize, &'a Rc<RefCell<Item>>)>,
+    mark: &str,
+    config: &ScopeConfig,
 ) -> HashMap<String, Vec<SharedMarkedItem<Item>>> {
     let mut marked_items: HashMap<String, Vec<SharedMarkedItem<Item>>> = HashMap::new();
+    let scan_set = &config.scan_set;
 
-    use Item as I;
+    for (position, item) in items {
+        lazy::parse_if_marked(item, mark, config.match_mode);
 
-    for item in items {
-        let mut i = item.borrow_mut();
-        let attrs = match *i {
-            I::Struct(ItemStruct { ref mut attrs, .. }) => attrs,
-            I::Trait(ItemTrait { ref mut attrs, .. }) => attrs,
-            I::Mod(syn::ItemMod { ref mut attrs, .. }) => attrs,
-            I::Enum(syn::ItemEnum { ref mut attrs, .. }) => attrs,
-            I::Fn(syn::ItemFn { ref mut attrs, .. }) => attrs,
-            _ => continue,
+        // only look at the attributes first, most items are not marked and don't need to be
+        // borrowed mutably
+        let found = scanned_attrs(&item.borrow(), scan_set)
+            .and_then(|a| find_attribute(a, mark, config.match_mode));
+        let marked = match found {
+            Some((indx, attr_ident)) => {
+                let mut i = item.borrow_mut();
+                scanned_attrs_mut(&mut i, scan_set)
+                    .map(|attrs| (take_mark(attrs, indx, config), indx, attr_ident))
+            }
+            None if has_verbatim_mark(&item.borrow(), mark, config) => {
+                take_verbatim_mark(&mut item.borrow_mut(), mark, config)
+            }
+            None => None,
         };
 
-        if let Some((indx, attr_ident)) = find_attribute(attrs, mark) {
-            let a = attrs.remove(indx);
-            let marked_item = MarkedItem::new(a, item.clone());
+        if let Some((a, indx, attr_ident)) = marked {
+            let mut marked_item = MarkedItem::new(a, item.clone());
+            marked_item.index = indx;
+            marked_item.position = position;
             match marked_items.get_mut(&attr_ident) {
                 Some(marked) => marked.push(marked_item),
                 None => {
@@ -75,25 +594,1027 @@ fn get_items_by_mark_prefix<'a>(
     marked_items
 }
 
+/// Handles the mark at `index` according to [ScopeConfig::mark_policy] and returns it
+#[cfg(feature = "full")]
+fn take_mark(attrs: &mut Vec<Attribute>, index: usize, config: &ScopeConfig) -> Attribute {
+    match &config.mark_policy {
+        MarkPolicy::Remove => attrs.remove(index),
+        MarkPolicy::Keep => attrs[index].clone(),
+        MarkPolicy::ReplaceWith(attr) => std::mem::replace(&mut attrs[index], (**attr).clone()),
+    }
+}
+
+/// Returns `true` if `item` is an unparsed item whose leading attributes match `mark`
+#[cfg(feature = "full")]
+fn has_verbatim_mark(item: &Item, mark: &str, config: &ScopeConfig) -> bool {
+    match item {
+        Item::Verbatim(tokens) => {
+            config.scan_set.contains(ItemKind::Verbatim)
+                && lazy::leading_attr_matches(tokens, mark, config.match_mode)
+        }
+        _ => false,
+    }
+}
+
+/// Finds `mark` in the leading attributes of an item syn could not parse and handles it on the
+/// raw tokens, so exotic items can still be marked
+#[cfg(feature = "full")]
+fn take_verbatim_mark(
+    item: &mut Item,
+    mark: &str,
+    config: &ScopeConfig,
+) -> Option<(Attribute, usize, String)> {
+    if !has_verbatim_mark(item, mark, config) {
+        return None;
+    }
+    let Item::Verbatim(tokens) = item else {
+        return None;
+    };
+
+    let (mut attrs, rest) = lazy::split_attrs(tokens)?;
+    let (index, attr_ident) = find_attribute(&attrs, mark, config.match_mode)?;
+    let mark = take_mark(&mut attrs, index, config);
+    *tokens = lazy::join_attrs(&attrs, rest);
+
+    Some((mark, index, attr_ident))
+}
+
+/// Returns `true` if the item carries an attribute containing `mark`, without removing it
+#[cfg(feature = "full")]
+fn has_mark(item: &RefCell<Item>, mark: &str, config: &ScopeConfig) -> bool {
+    lazy::parse_if_marked(item, mark, config.match_mode);
+
+    let item = item.borrow();
+    match &*item {
+        Item::Verbatim(tokens) if config.scan_set.contains(ItemKind::Verbatim) => {
+            lazy::leading_attr_matches(tokens, mark, config.match_mode)
+                && find_attribute(&lazy::leading_attrs(tokens), mark, config.match_mode).is_some()
+        }
+        item => scanned_attrs(item, &config.scan_set)
+            .is_some_and(|attrs| find_attribute(attrs, mark, config.match_mode).is_some()),
+    }
+}
+
+/// The `#[macro_scope(flag)]` attributes understood by the scope, removed from all emitted items
+#[cfg(feature = "full")]
+const FLAGS: &[&str] = &["skip", "no_inherit"];
+
+/// Removes all [FLAGS] from `item` and the items of its inline module content
+///
+/// Unparsed items are only parsed if they mention a flag
+#[cfg(feature = "full")]
+fn strip_flags(item: &mut Item) {
+    if let Item::Verbatim(tokens) = item {
+        if !tokens.to_string().contains("macro_scope") {
+            return;
+        }
+        match syn::parse2(tokens.clone()) {
+            Ok(parsed) => *item = parsed,
+            Err(_) => return,
+        }
+    }
+
+    if let Some(attrs) = item_attrs_mut(item) {
+        for flag in FLAGS {
+            take_flag(attrs, flag);
+        }
+    }
+    if let Item::Mod(ItemMod {
+        content: Some((_, content)),
+        ..
+    }) = item
+    {
+        content.iter_mut().for_each(strip_flags);
+    }
+}
+
+/// Removes `#[macro_scope(flag)]` from the attributes, returns `true` if it was present
+#[cfg(feature = "full")]
+fn take_flag(attrs: &mut Vec<Attribute>, flag: &str) -> bool {
+    let is_flag = |attr: &Attribute| {
+        attr.path().is_ident("macro_scope")
+            && attr.parse_args::<syn::Ident>().is_ok_and(|arg| arg == flag)
+    };
+
+    let len = attrs.len();
+    attrs.retain(|attr| !is_flag(attr));
+    attrs.len() != len
+}
+
+const MISSING_BODY: &str = "macro_scope requires an inline module body";
+
+/// A nested module item together with its lifted content
+#[cfg(feature = "full")]
+type NestedModule = (Rc<RefCell<Item>>, Vec<Rc<RefCell<Item>>>);
+
+/// Items matched by a template, together with the template's mark
+#[cfg(feature = "full")]
+type Matched = Vec<(String, Rc<RefCell<Item>>)>;
+
+#[cfg(feature = "full")]
 #[derive(Debug, Clone, Default)]
-struct MacroScope {
+pub struct MacroScope {
     pub items: Vec<Rc<RefCell<Item>>>,
+    /// The module the items were parsed from, without its content
+    ///
+    /// Emitted unchanged around the items, so scopes without matching marks round-trip to the
+    /// same tokens they were parsed from
+    pub module: Option<ItemMod>,
+    pub config: ScopeConfig,
+    /// The content of nested inline modules in [MacroScope::items], only filled if the scope was
+    /// parsed with [ScopeConfig::recursive]
+    modules: Vec<NestedModule>,
+    /// Add `#[automatically_derived]` to impl blocks pushed with [MacroScope::push_item] or
+    /// [MacroScope::push_tokens] and to impl blocks generated by templates
+    pub automatically_derived: bool,
+    /// Document every item generated by a template with the item and mark it was generated
+    /// from, see [emit::with_provenance]
+    pub provenance: bool,
+    /// Lints allowed on every item generated by a template, e.g. `dead_code` or
+    /// `clippy::pedantic`, see [emit::with_allow]
+    pub allow: Vec<syn::Path>,
+    /// Paths referenced by generated code, imported with `use` next to the generated code, see
+    /// [MacroScope::use_path]
+    pub prelude: Vec<syn::Path>,
+    /// Tag every item a template ran on and skip tagged items, so expanding the same items
+    /// again, e.g. when scope macros are nested or applied twice, doesn't generate duplicate code
+    ///
+    /// The tag is an inactive `#[cfg_attr(any(), macro_scope(expanded = "mark"))]` attribute
+    /// that stays on the emitted item
+    pub idempotent: bool,
+    /// Emit the generated code inside a `#[doc(hidden)]` module with this name, e.g.
+    /// `__macro_scope_generated`, and re-export the generated items next to it, see
+    /// [emit::in_hidden_module]
+    ///
+    /// Expanding fails if the name is already used by an item of the scope
+    pub hidden_module: Option<String>,
+    /// Turn panics of templates into errors at the item they were expanding, instead of
+    /// aborting the whole macro with an opaque panic
+    ///
+    /// The panic hook still runs, so the panic message is printed as usual
+    pub catch_panics: bool,
+    /// Record the mutations performed on the scope, see [MacroScope::journal]
+    pub journaling: bool,
+    journal: RefCell<journal::Journal>,
+    gensym: gensym::Gensym,
+    index: RefCell<Option<index::AttrIndex>>,
+    idents: RefCell<Option<index::IdentIndex>>,
+    metadata: metadata::Metadata,
+    /// Which items are returned by [MacroScope::expand]
+    pub emit_mode: EmitMode,
+    templates: Templates,
 }
 
-impl Parse for MacroScope {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let item: ItemMod = input.parse()?;
+#[cfg(feature = "full")]
+impl MacroScope {
+    /// Registers a [Template] that is run on every item marked with `mark` during
+    /// [MacroScope::expand]
+    pub fn register(&mut self, mark: impl Into<String>, template: impl Template + 'static) {
+        self.templates.0.push((mark.into(), Rc::new(template)));
+    }
+
+    /// Returns the marks of all registered templates
+    pub fn marks(&self) -> impl Iterator<Item = &str> {
+        self.templates.0.iter().map(|(mark, _)| mark.as_str())
+    }
+
+    /// Runs all registered templates in registration order and returns the module together
+    /// with the generated code
+    ///
+    /// Matched marks are removed from the items, the generated code is placed at the end of
+    /// the module. With [EmitMode::OnlyMarked] only the selected items and the generated code
+    /// are returned, without the module
+    pub fn expand(&mut self) -> syn::Result<TokenStream> {
+        let mut diagnostics = Diagnostics::new();
+        let tokens = self.expand_into(&mut diagnostics);
+        diagnostics.finish()?;
+        Ok(tokens)
+    }
+
+    /// Same as [MacroScope::expand] but never fails, errors are emitted as `compile_error!`s
+    /// after the items and the code generated for the items whose templates succeeded
+    ///
+    /// The items of the scope are always emitted, so IDE features like completion keep working
+    /// on them while the macro reports problems
+    pub fn expand_resilient(&mut self) -> TokenStream {
+        let mut diagnostics = Diagnostics::new();
+        let mut tokens = self.expand_into(&mut diagnostics);
+        tokens.extend(diagnostics.to_compile_errors());
+        tokens
+    }
+
+    /// Implementation of [MacroScope::expand], records errors in `diagnostics` and emits what
+    /// succeeded
+    fn expand_into(&mut self, diagnostics: &mut Diagnostics) -> TokenStream {
+        let dump = self.dump_pre();
+        let per_item = self.generate(diagnostics);
+        self.write_report(&per_item);
+        let tests = diagnostics
+            .check(self.merge_tests(&per_item))
+            .unwrap_or_default();
+        let (generated, matched) = Generated::concat(per_item);
+        let mut generated = diagnostics
+            .check(self.with_hidden_module(self.with_prelude(generated)))
+            .unwrap_or_default();
+        generated.extend(tests);
+        self.strip_configured_marks();
+
+        let tokens = match &self.emit_mode {
+            EmitMode::All => self.emit(generated),
+            EmitMode::OnlyMarked(_) => {
+                let mut tokens = self.passthrough(matched);
+                tokens.extend(generated);
+                tokens
+            }
+        };
+
+        if let Some((dir, invocation)) = dump {
+            dump::write(&dir, &self.name(), invocation, "post", &tokens);
+            self.dump_journal(&dir, invocation);
+        }
+        tokens
+    }
+
+    /// Same as [MacroScope::expand] but returns the passthrough items and the generated code as
+    /// separate streams, so the caller decides where the generated code is placed
+    pub fn expand_split(&mut self) -> syn::Result<(TokenStream, TokenStream)> {
+        let expansion = self.expand_per_item()?;
+        Ok((expansion.passthrough, expansion.generated))
+    }
+
+    /// Same as [MacroScope::expand_split] but additionally returns the code generated for every
+    /// marked item, so callers can place it next to the item or post-process it
+    pub fn expand_per_item(&mut self) -> syn::Result<Expansion> {
+        let dump = self.dump_pre();
+        let mut diagnostics = Diagnostics::new();
+        let per_item = self.generate(&mut diagnostics);
+        diagnostics.finish()?;
+        self.write_report(&per_item);
+        let tests = self.merge_tests(&per_item)?;
+        let (generated, matched) = Generated::concat(per_item.clone());
+        let mut generated = self.with_hidden_module(self.with_prelude(generated))?;
+        generated.extend(tests);
+        self.strip_configured_marks();
+        let passthrough = self.passthrough(matched);
+
+        if let Some((dir, invocation)) = dump {
+            let mut tokens = passthrough.clone();
+            tokens.extend(generated.clone());
+            dump::write(&dir, &self.name(), invocation, "post", &tokens);
+            self.dump_journal(&dir, invocation);
+        }
+        Ok(Expansion {
+            passthrough,
+            generated,
+            per_item,
+        })
+    }
+
+    /// Declares a path referenced by generated code like `::my_framework::Registry`, so templates
+    /// can refer to it by its last segment
+    ///
+    /// A `use` for every declared path is emitted together with the generated code, paths whose
+    /// name is already declared in the scope and paths declared twice are skipped
+    pub fn use_path(&mut self, path: syn::Path) {
+        if !self.prelude.contains(&path) {
+            self.prelude.push(path);
+        }
+    }
+
+    /// Returns the names declared by the items directly in the scope
+    ///
+    /// Unparsed items of lazy scopes are parsed from a copy of their tokens, so the items stay
+    /// untouched
+    fn declared_idents(&self) -> Vec<syn::Ident> {
+        self.items
+            .iter()
+            .filter_map(|item| match &*item.borrow() {
+                Item::Verbatim(tokens) => {
+                    let item = syn::parse2::<Item>(tokens.clone()).ok()?;
+                    item_ident(&item).cloned()
+                }
+                item => item_ident(item).cloned(),
+            })
+            .collect()
+    }
+
+    /// Prepends the `use` declarations of [MacroScope::prelude] to non-empty `generated` code
+    fn with_prelude(&self, generated: TokenStream) -> TokenStream {
+        if generated.is_empty() {
+            return generated;
+        }
+
+        let declared = self.declared_idents();
+        let mut tokens = TokenStream::new();
+        for path in &self.prelude {
+            let name = path.segments.last().map(|s| &s.ident);
+            if name.is_some_and(|name| declared.contains(name)) {
+                continue;
+            }
+            tokens.extend(quote! {
+                #[allow(unused_imports)]
+                use #path;
+            });
+        }
+        tokens.extend(generated);
+        tokens
+    }
+
+    /// Moves non-empty `generated` code into [MacroScope::hidden_module] if it is set
+    fn with_hidden_module(&self, generated: TokenStream) -> syn::Result<TokenStream> {
+        let Some(name) = &self.hidden_module else {
+            return Ok(generated);
+        };
+        if generated.is_empty() {
+            return Ok(generated);
+        }
+
+        let module: syn::Ident = syn::parse_str(name)?;
+        if let Some(ident) = self.declared_idents().into_iter().find(|i| *i == module) {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!("`{module}` is already used as the name of the module of generated code"),
+            ));
+        }
+
+        emit::in_hidden_module(&module, generated)
+    }
 
-        let items = match item.content {
-            Some(c) => c.1,
-            None => return Ok(Default::default()),
+    /// Strips the inner marks listed in [ScopeConfig::inner_marks]
+    fn strip_configured_marks(&self) {
+        let marks: Vec<_> = self.config.inner_marks.iter().map(String::as_str).collect();
+        self.strip_inner_marks(&marks);
+    }
+
+    /// Returns the name of the scope module, used for dump files
+    fn name(&self) -> String {
+        match &self.module {
+            Some(module) => module.ident.to_string(),
+            None => "scope".into(),
+        }
+    }
+
+    /// Dumps the scope before processing if `MACRO_SCOPE_DUMP` is set, returns where to dump the
+    /// result to
+    fn dump_pre(&self) -> Option<(std::path::PathBuf, usize)> {
+        let dir = dump::dump_dir()?;
+        let invocation = dump::next_invocation();
+        dump::write(
+            &dir,
+            &self.name(),
+            invocation,
+            "pre",
+            &self.to_token_stream(),
+        );
+        Some((dir, invocation))
+    }
+
+    /// Dumps the journal next to the expansion if journaling is enabled
+    fn dump_journal(&self, dir: &std::path::Path, invocation: usize) {
+        if !self.journaling {
+            return;
+        }
+
+        let journal: String = self.journal().iter().map(|e| format!("{e}\n")).collect();
+        dump::write_text(dir, &self.name(), invocation, "journal.txt", &journal);
+    }
+
+    /// Runs all registered templates, returns the code generated for every matched item
+    ///
+    /// Items whose template fails are skipped and the error is recorded in `diagnostics`
+    fn generate(&mut self, diagnostics: &mut Diagnostics) -> Vec<Generated> {
+        if self.config.strict {
+            let marks: Vec<_> = self.marks().collect();
+            let items = self.scanned_items();
+            let mut checks = Diagnostics::new();
+            checks.check(diagnostics::check_unknown_marks(
+                &items,
+                &marks,
+                &self.config,
+            ));
+            checks.check(diagnostics::check_unscanned_marks(
+                &items,
+                &marks,
+                &self.config,
+            ));
+            if let Err(e) = checks.finish() {
+                diagnostics.push(e);
+                return Vec::new();
+            }
+        }
+
+        let mut generated = Vec::new();
+
+        for (mark, template) in &self.templates.0 {
+            let mut marked: Vec<_> = self.get_items_by_mark_prefix(mark).into_iter().collect();
+            marked.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            for (_, items) in marked {
+                for item in &items {
+                    if self.idempotent && expanded::is_expanded(&item.item, mark) {
+                        continue;
+                    }
+                    let Some(generated_item) =
+                        diagnostics.check(self.generate_item(mark, template, item))
+                    else {
+                        continue;
+                    };
+                    // failed items stay untagged, so the next expansion tries them again
+                    if self.idempotent {
+                        expanded::tag_expanded(&item.item, mark, item.index);
+                    }
+                    generated.push(generated_item);
+                }
+            }
+        }
+
+        generated
+    }
+
+    /// Runs the `template` registered for `mark` on a single marked item and applies the
+    /// configured wrapping
+    fn generate_item(
+        &self,
+        mark: &str,
+        template: &Rc<dyn Template>,
+        item: &SharedMarkedItem<Item>,
+    ) -> syn::Result<Generated> {
+        let run = |f: &dyn Fn() -> syn::Result<TokenStream>| match self.catch_panics {
+            true => panic::catch(mark, item, f),
+            false => f(),
+        };
+        let mut tokens = run(&|| template.expand(item))?;
+        if self.config.strict {
+            self.check_visibility(&tokens)?;
+        }
+        let mut tests = run(&|| template.tests(item))?;
+        if self.provenance {
+            let source = ItemKey::of(&item.item.borrow()).name;
+            tokens = emit::with_provenance(&source, &item.mark_name(), tokens)?;
+        }
+        if self.automatically_derived {
+            tokens = emit::automatically_derived(tokens)?;
+        }
+        tokens = emit::with_allow(&self.allow, tokens)?;
+        if let Some(cfg) = item.cfg() {
+            tokens = emit::with_cfg(&cfg, tokens)?;
+            tests = emit::with_cfg(&cfg, tests)?;
+        }
+
+        Ok(Generated {
+            template: mark.to_string(),
+            item: item.clone(),
+            tokens,
+            tests,
+        })
+    }
+
+    /// Emits the items according to [MacroScope::emit_mode], without generated code
+    fn passthrough(&self, mut matched: Matched) -> TokenStream {
+        let marks = match &self.emit_mode {
+            EmitMode::All => return self.emit(TokenStream::new()),
+            EmitMode::OnlyMarked(marks) => marks,
         };
 
-        let items: Vec<_> = items
+        for mark in marks {
+            if self.marks().any(|m| m == mark) {
+                continue;
+            }
+            let marked = self.get_items_by_mark_prefix(mark);
+            for item in marked.into_values().flatten() {
+                matched.push((mark.clone(), item.item));
+            }
+        }
+
+        self.scanned_items()
+            .iter()
+            .filter(|item| {
+                matched
+                    .iter()
+                    .any(|(mark, m)| marks.contains(mark) && Rc::ptr_eq(m, item))
+            })
+            .map(|item| self.emit_item(item).to_token_stream())
+            .collect()
+    }
+
+    /// Creates a scope without a module shell from already parsed items
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// let scope = MacroScope::from_items(vec![
+    ///     syn::parse_quote!(#[system] fn a() {}),
+    ///     syn::parse_quote!(struct B;),
+    /// ]);
+    /// assert_eq!(scope.get_marked("system").len(), 1);
+    /// ```
+    pub fn from_items(items: Vec<Item>) -> Self {
+        Self {
+            items: items
+                .into_iter()
+                .map(|item| Rc::new(RefCell::new(item)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a scope without a module shell from the items of a file, the inner attributes of
+    /// the file are dropped
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// let file: syn::File = syn::parse_quote! {
+    ///     #[system]
+    ///     fn a() {}
+    /// };
+    /// let scope = MacroScope::from_file(file);
+    /// assert_eq!(scope.get_marked("system").len(), 1);
+    /// ```
+    pub fn from_file(file: syn::File) -> Self {
+        Self::from_items(file.items)
+    }
+
+    /// Creates a scope from a module, the module without its content is kept as the shell
+    fn from_module(mut module: ItemMod) -> Self {
+        let items = match &mut module.content {
+            Some(c) => std::mem::take(&mut c.1),
+            None => Vec::new(),
+        };
+
+        Self {
+            module: Some(module),
+            ..Self::from_items(items)
+        }
+    }
+
+    /// Parses a scope according to `config`
+    pub fn parse_with_config(tokens: TokenStream, config: ScopeConfig) -> syn::Result<Self> {
+        let module = match (config.lazy, config.lenient) {
+            (true, _) => Self::parse_lazy(tokens)?,
+            (false, true) => Self::parse_lenient(tokens)?,
+            (false, false) => syn::parse2::<Self>(tokens)?,
+        };
+
+        module.configure(config)
+    }
+
+    /// Creates a scope from the arguments and the annotated item of an attribute macro
+    ///
+    /// The arguments are applied to the default [ScopeConfig] with [ScopeConfig::apply_args] and
+    /// the item is parsed as a [ScopeInput], see [MacroScope::from_input]. A single item like an
+    /// impl block or a struct becomes a scope with just that item and no module shell
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// let scope = MacroScope::from_attr_macro(
+    ///     "match_mode = \"exact\"".parse().unwrap(),
+    ///     "#[system] fn update() {}".parse().unwrap(),
+    /// )
+    /// .unwrap();
+    /// assert!(scope.module.is_none());
+    /// assert_eq!(scope.get_marked("system").len(), 1);
+    /// ```
+    pub fn from_attr_macro(attr: TokenStream, item: TokenStream) -> syn::Result<Self> {
+        let mut config = ScopeConfig::default();
+        config.apply_args(attr)?;
+
+        if (config.lazy || config.lenient) && lazy::is_module(&item) {
+            return Self::parse_with_config(item, config);
+        }
+
+        Self::from_input(syn::parse2(item)?, config)
+    }
+
+    /// Applies `config` to a freshly parsed scope
+    fn configure(mut self, config: ScopeConfig) -> syn::Result<Self> {
+        self.config = config;
+        if let Some(shell) = &self.module {
+            if shell.content.is_none() && self.config.missing_body == MissingBody::Error {
+                return Err(syn::Error::new(shell.ident.span(), MISSING_BODY));
+            }
+        }
+        if self.config.recursive {
+            let items = self.items.clone();
+            self.lift_modules(&items, 1);
+        }
+
+        let marks: Vec<_> = self
+            .config
+            .inherit_marks
+            .iter()
+            .map(String::as_str)
+            .collect();
+        self.inherit_marks(&marks);
+
+        Ok(self)
+    }
+
+    /// Parses a scope without parsing its items
+    ///
+    /// Items are stored as [Item::Verbatim] tokens and only parsed once a scan finds a mark on
+    /// them, the remaining items are emitted as they were written. Use [MacroScope::parse_items]
+    /// before inspecting [MacroScope::items] directly
+    pub fn parse_lazy(tokens: TokenStream) -> syn::Result<Self> {
+        let (module, items) = lazy::parse_module.parse2(tokens)?;
+
+        Ok(Self {
+            items: items
+                .into_iter()
+                .map(|item| Rc::new(RefCell::new(item)))
+                .collect(),
+            module: Some(module),
+            config: ScopeConfig {
+                lazy: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Parses a scope and keeps the items syn fails to parse as [Item::Verbatim] tokens, see
+    /// [ScopeConfig::lenient]
+    fn parse_lenient(tokens: TokenStream) -> syn::Result<Self> {
+        let scope = Self::parse_lazy(tokens)?;
+        for item in &scope.items {
+            // invalid items are reported by the compiler once they are emitted
+            let _ = lazy::parse(item);
+        }
+        Ok(scope)
+    }
+
+    /// Moves the content of nested inline modules into shared items, so they can be scanned
+    ///
+    /// `depth` is the nesting level of the content, modules deeper than
+    /// [ScopeConfig::max_depth] and modules marked with `#[macro_scope(skip)]` are left as they
+    /// are
+    fn lift_modules(&mut self, items: &[Rc<RefCell<Item>>], depth: usize) {
+        if self.config.max_depth.is_some_and(|max| depth > max) {
+            return;
+        }
+        self.drop_index();
+
+        for item in items {
+            if let Item::Verbatim(tokens) = &*item.borrow() {
+                if !lazy::is_module(tokens) {
+                    continue;
+                }
+            }
+            // modules that don't parse are left for the compiler to report
+            let _ = lazy::parse(item);
+
+            let content = match &mut *item.borrow_mut() {
+                Item::Mod(ItemMod {
+                    attrs,
+                    content: Some((_, content)),
+                    ..
+                }) => {
+                    if take_flag(attrs, "skip") {
+                        continue;
+                    }
+                    std::mem::take(content)
+                }
+                _ => continue,
+            };
+
+            let children: Vec<_> = content
+                .into_iter()
+                .map(|item| Rc::new(RefCell::new(item)))
+                .collect();
+            self.modules.push((item.clone(), children.clone()));
+            self.lift_modules(&children, depth + 1);
+        }
+    }
+
+    /// Returns the content of a nested module lifted by [ScopeConfig::recursive]
+    fn children(&self, module: &Rc<RefCell<Item>>) -> Option<&[Rc<RefCell<Item>>]> {
+        self.modules
+            .iter()
+            .find(|(m, _)| Rc::ptr_eq(m, module))
+            .map(|(_, children)| children.as_slice())
+    }
+
+    /// Returns all items that are scanned for marks in source order, including the items of
+    /// nested modules if the scope was parsed with [ScopeConfig::recursive]
+    pub fn scanned_items(&self) -> Vec<Rc<RefCell<Item>>> {
+        let mut scanned = Vec::new();
+        self.collect_items(&self.items, &mut scanned);
+        scanned
+    }
+
+    fn collect_items(&self, items: &[Rc<RefCell<Item>>], scanned: &mut Vec<Rc<RefCell<Item>>>) {
+        for item in items {
+            scanned.push(item.clone());
+            if let Some(children) = self.children(item) {
+                self.collect_items(children, scanned);
+            }
+        }
+    }
+
+    /// Returns a copy of the item with the content of lifted nested modules put back in
+    fn emit_item(&self, item: &Rc<RefCell<Item>>) -> Item {
+        let mut emitted = item.borrow().clone();
+
+        if let Some(children) = self.children(item) {
+            if let Item::Mod(ItemMod {
+                content: Some((_, content)),
+                ..
+            }) = &mut emitted
+            {
+                *content = children.iter().map(|c| self.emit_item(c)).collect();
+            }
+        }
+
+        strip_flags(&mut emitted);
+        emitted
+    }
+
+    /// Parses all items of a lazily parsed scope
+    pub fn parse_items(&mut self) -> syn::Result<()> {
+        for item in &self.scanned_items() {
+            lazy::parse(item)?;
+        }
+
+        Ok(())
+    }
+
+    /// Appends an item to the scope, it is emitted after the items already in the scope
+    pub fn push_item(&mut self, mut item: Item) -> Rc<RefCell<Item>> {
+        if self.automatically_derived {
+            if let Item::Impl(i) = &mut item {
+                i.attrs.push(parse_quote!(#[automatically_derived]));
+            }
+        }
+
+        self.record(journal::Mutation::PushedItem(ItemKey::of(&item)));
+        self.drop_index();
+        let item = Rc::new(RefCell::new(item));
+        self.items.push(item.clone());
+        if self.config.recursive {
+            self.lift_modules(std::slice::from_ref(&item), 1);
+        }
+        item
+    }
+
+    /// Parses `tokens` as a sequence of items and appends them to the scope
+    pub fn push_tokens(&mut self, tokens: TokenStream) -> syn::Result<Vec<Rc<RefCell<Item>>>> {
+        let file: syn::File = syn::parse2(tokens)?;
+        Ok(file
+            .items
             .into_iter()
-            .map(|item| Rc::new(RefCell::new(item)))
+            .map(|item| self.push_item(item))
+            .collect())
+    }
+
+    /// Returns all items in the scope marked with `mark`, grouped by the name of the matched mark
+    ///
+    /// The marks are handled on the items according to [ScopeConfig::mark_policy]
+    pub fn get_items_by_mark_prefix(
+        &self,
+        mark: &str,
+    ) -> HashMap<String, Vec<SharedMarkedItem<Item>>> {
+        let items = self.scanned_items();
+        let marked = match &*self.index.borrow() {
+            Some(index) => {
+                let candidates = index.candidates(mark, self.config.match_mode);
+                get_items_by_mark_prefix(
+                    candidates.into_iter().map(|i| (i, &items[i])),
+                    mark,
+                    &self.config,
+                )
+            }
+            None => get_items_by_mark_prefix(items.iter().enumerate(), mark, &self.config),
+        };
+        self.record_marks(marked.values().flatten());
+        marked
+    }
+
+    /// Same as [MacroScope::get_items_by_mark_prefix] but as a single list in source order
+    pub fn get_marked(&self, mark: &str) -> Vec<SharedMarkedItem<Item>> {
+        let mut marked: Vec<_> = self
+            .get_items_by_mark_prefix(mark)
+            .into_values()
+            .flatten()
             .collect();
+        marked.sort_by_key(MarkedItem::source_key);
+        marked
+    }
+
+    /// Returns all items marked with `mark` parsed into `T`, in source order
+    ///
+    /// The items are parsed from their tokens without the mark, errors for all items that don't
+    /// fit `T` are combined
+    pub fn get_marked_as<T: Parse>(&self, mark: &str) -> syn::Result<Vec<MarkedItem<T>>> {
+        let marked = self.get_marked(mark);
+        let mut parsed = Vec::new();
+        let mut diagnostics = Diagnostics::new();
+
+        for marked in marked {
+            let (item, marked) = marked.split();
+            let tokens = item.borrow().to_token_stream();
+            if let Some(item) = diagnostics.check(syn::parse2::<T>(tokens)) {
+                parsed.push(marked.map(|_| item));
+            }
+        }
+
+        diagnostics.finish()?;
+        Ok(parsed)
+    }
+
+    /// Same as [MacroScope::get_items_by_mark_prefix] but additionally grouped by [ItemKind]
+    pub fn get_items_by_mark_and_kind(
+        &self,
+        mark: &str,
+    ) -> HashMap<(String, ItemKind), Vec<SharedMarkedItem<Item>>> {
+        let mut grouped: HashMap<(String, ItemKind), Vec<SharedMarkedItem<Item>>> = HashMap::new();
+
+        for (name, items) in self.get_items_by_mark_prefix(mark) {
+            for item in items {
+                let kind = ItemKind::of(&item.item.borrow());
+                grouped.entry((name.clone(), kind)).or_default().push(item);
+            }
+        }
+
+        grouped
+    }
+
+    /// Returns the items carrying all of the given marks, in source order
+    ///
+    /// Unlike [MacroScope::get_items_by_mark_prefix] this does not touch the marks
+    pub fn items_with_all(&self, marks: &[&str]) -> Vec<Rc<RefCell<Item>>> {
+        self.scanned_items()
+            .into_iter()
+            .filter(|item| marks.iter().all(|mark| has_mark(item, mark, &self.config)))
+            .collect()
+    }
+
+    /// Returns the items carrying at least one of the given marks, in source order
+    ///
+    /// Unlike [MacroScope::get_items_by_mark_prefix] this does not touch the marks
+    pub fn items_with_any(&self, marks: &[&str]) -> Vec<Rc<RefCell<Item>>> {
+        self.scanned_items()
+            .into_iter()
+            .filter(|item| marks.iter().any(|mark| has_mark(item, mark, &self.config)))
+            .collect()
+    }
+
+    /// Returns the items carrying `mark` with the argument `key = value`, in source order, e.g.
+    /// `items_with_mark_arg("register", "category", "audio")` for
+    /// `#[register(category = "audio")]`
+    ///
+    /// String values are compared by their content, other values like `category = audio` by
+    /// their tokens. Unlike [MacroScope::get_items_by_mark_prefix] this does not touch the marks,
+    /// so the same mark can be queried for several values
+    pub fn items_with_mark_arg(
+        &self,
+        mark: &str,
+        key: &str,
+        value: &str,
+    ) -> Vec<Rc<RefCell<Item>>> {
+        let matches = |attr: &Attribute| {
+            let Some(ident) = attr.path().get_ident() else {
+                return false;
+            };
+            if !self.config.match_mode.matches(&ident.to_string(), mark) {
+                return false;
+            }
+
+            let matched = MetaTree::from_attribute(attr).ok().and_then(|tree| {
+                let child = tree.child(key)?;
+                Some(match child.expect_str() {
+                    Ok(s) => s == value,
+                    Err(_) => match child {
+                        MetaTree::NameValue(_, expr) => expr.to_token_stream().to_string() == value,
+                        _ => false,
+                    },
+                })
+            });
+            matched.unwrap_or(false)
+        };
+
+        self.scanned_items()
+            .into_iter()
+            .filter(|item| {
+                if !has_mark(item, mark, &self.config) {
+                    return false;
+                }
+                let item = item.borrow();
+                match &*item {
+                    Item::Verbatim(tokens) => lazy::leading_attrs(tokens).iter().any(matches),
+                    item => item_attrs(item).is_some_and(|attrs| attrs.iter().any(matches)),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the path of every distinct attribute on the scanned items, in the order they first
+    /// appear in, e.g. `system`, `derive` or `ecs::component`
+    ///
+    /// With a `namespace` only paths like `namespace::mark` are returned. Attributes of lazily
+    /// parsed items are read without parsing the items
+    pub fn discover_marks(&self, namespace: Option<&str>) -> Vec<String> {
+        let mut marks: Vec<String> = Vec::new();
+
+        for item in self.scanned_items() {
+            let item = item.borrow();
+            let attrs = match &*item {
+                Item::Verbatim(tokens) if self.config.scan_set.contains(ItemKind::Verbatim) => {
+                    lazy::leading_attrs(tokens)
+                }
+                item => scanned_attrs(item, &self.config.scan_set)
+                    .unwrap_or_default()
+                    .to_vec(),
+            };
+
+            for attr in attrs {
+                let path = attr.path();
+                if let Some(namespace) = namespace {
+                    if path.segments.len() < 2 || path.segments[0].ident != namespace {
+                        continue;
+                    }
+                }
+
+                let segments: Vec<_> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+                let name = segments.join("::");
+                if !marks.contains(&name) {
+                    marks.push(name);
+                }
+            }
+        }
 
-        Ok(Self { items })
+        marks
+    }
+
+    /// Returns the items not carrying `mark`, optionally only items of the given kind
+    ///
+    /// Filtering by kind parses all items of a lazily parsed scope
+    pub fn items_without(&self, mark: &str, kind: Option<ItemKind>) -> Vec<Rc<RefCell<Item>>> {
+        self.scanned_items()
+            .into_iter()
+            .filter(|item| {
+                kind.is_none_or(|kind| {
+                    // unparsable items stay verbatim and are reported as such
+                    let _ = lazy::parse(item);
+                    ItemKind::of(&item.borrow()) == kind
+                })
+            })
+            .filter(|item| !has_mark(item, mark, &self.config))
+            .collect()
+    }
+
+    /// Re-emits the module shell with the current items followed by `generated`
+    fn emit(&self, generated: TokenStream) -> TokenStream {
+        let mut items: Vec<Item> = self.items.iter().map(|i| self.emit_item(i)).collect();
+        if !generated.is_empty() {
+            items.push(Item::Verbatim(generated));
+        }
+
+        match &self.module {
+            Some(module) => {
+                let mut module = module.clone();
+                match &mut module.content {
+                    Some((_, content)) => *content = items,
+                    None => {
+                        let span = module.ident.span();
+                        let mut tokens = module.into_token_stream();
+                        tokens.extend(items.iter().map(|i| i.to_token_stream()));
+                        if self.config.missing_body == MissingBody::Warn {
+                            tokens.extend(quote_spanned! {span=>
+                                const _: () = {
+                                    #[deprecated(note = #MISSING_BODY)]
+                                    struct MissingBody;
+                                    let _ = MissingBody;
+                                };
+                            });
+                        }
+                        return tokens;
+                    }
+                }
+                module.into_token_stream()
+            }
+            None => items.iter().map(|i| i.to_token_stream()).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "full")]
+impl ToTokens for MacroScope {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(self.emit(TokenStream::new()));
+    }
+}
+
+/// Parses a scope from source code, useful for testing outside of a proc_macro context
+#[cfg(feature = "full")]
+impl FromStr for MacroScope {
+    type Err = syn::Error;
+
+    fn from_str(s: &str) -> syn::Result<Self> {
+        syn::parse_str(s)
+    }
+}
+
+#[cfg(feature = "full")]
+impl Parse for MacroScope {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self::from_module(input.parse()?))
     }
 }
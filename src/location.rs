@@ -0,0 +1,32 @@
+use std::fmt;
+
+use proc_macro2::Span;
+
+/// Position of a span in the source code
+///
+/// Lines are 1-indexed and columns are 0-indexed, like [proc_macro2::LineColumn]. The file is
+/// whatever the compiler reports for the span, outside of a proc_macro context this is usually
+/// not a real path
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    pub fn of(span: Span) -> Self {
+        let start = span.start();
+        Self {
+            file: span.file(),
+            line: start.line,
+            column: start.column,
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column + 1)
+    }
+}
@@ -0,0 +1,307 @@
+use std::{fmt::Display, str::FromStr};
+
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token, Attribute, Expr, ExprLit, ExprUnary, Lit, LitBool, LitFloat, LitInt, Path, Token, Type,
+    UnOp,
+};
+
+/// Recursive representation of mark arguments like
+/// `#[schema(table(name = "users", index(col = "id")))]`
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetaTree {
+    /// A bare path, e.g. `skip`
+    Path(Path),
+    /// A path followed by a parenthesized list, e.g. `index(col = "id")`
+    List(Path, Vec<MetaTree>),
+    /// A path assigned to an expression, e.g. `name = "users"`
+    NameValue(Path, Box<Expr>),
+    /// A literal without a name, e.g. the `"id"` in `index("id")`
+    Lit(Lit),
+}
+
+impl MetaTree {
+    /// Parses the whole attribute, including its path
+    pub fn from_attribute(attr: &Attribute) -> syn::Result<Self> {
+        match &attr.meta {
+            syn::Meta::Path(path) => Ok(Self::Path(path.clone())),
+            syn::Meta::List(list) => {
+                let children = list.parse_args_with(Self::parse_children)?;
+                Ok(Self::List(list.path.clone(), children))
+            }
+            syn::Meta::NameValue(nv) => {
+                Ok(Self::NameValue(nv.path.clone(), Box::new(nv.value.clone())))
+            }
+        }
+    }
+
+    fn parse_children(input: ParseStream) -> syn::Result<Vec<Self>> {
+        let children = Punctuated::<Self, Token![,]>::parse_terminated(input)?;
+        Ok(children.into_iter().collect())
+    }
+
+    /// Returns the path of the node, [None] for literals
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) | Self::List(path, _) | Self::NameValue(path, _) => Some(path),
+            Self::Lit(_) => None,
+        }
+    }
+
+    /// Returns the name of the node if its path is a single identifier
+    pub fn name(&self) -> Option<String> {
+        self.path()?.get_ident().map(|ident| ident.to_string())
+    }
+
+    /// Returns `true` if the node is named `name`
+    pub fn is(&self, name: &str) -> bool {
+        self.path().is_some_and(|path| path.is_ident(name))
+    }
+
+    /// Returns the children of a list node, or an empty slice for any other node
+    pub fn children(&self) -> &[MetaTree] {
+        match self {
+            Self::List(_, children) => children,
+            _ => &[],
+        }
+    }
+
+    /// Returns the first child named `name`
+    pub fn child(&self, name: &str) -> Option<&MetaTree> {
+        self.children().iter().find(|child| child.is(name))
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Path(path) => path.span(),
+            Self::List(path, children) => children
+                .last()
+                .and_then(|last| path.span().join(last.span()))
+                .unwrap_or_else(|| path.span()),
+            Self::NameValue(path, value) => path
+                .span()
+                .join(value.span())
+                .unwrap_or_else(|| path.span()),
+            Self::Lit(lit) => lit.span(),
+        }
+    }
+
+    /// Returns the children of a list node or an error pointing at the node
+    pub fn expect_list(&self) -> syn::Result<&[MetaTree]> {
+        match self {
+            Self::List(_, children) => Ok(children),
+            _ => Err(self.error("expected a list like `name(...)`")),
+        }
+    }
+
+    /// Returns the literal of a name-value node or a literal node
+    pub fn expect_lit(&self) -> syn::Result<&Lit> {
+        match self {
+            Self::NameValue(_, value) => match &**value {
+                Expr::Lit(ExprLit { lit, .. }) => Ok(lit),
+                _ => Err(self.error("expected a literal like `name = \"value\"`")),
+            },
+            Self::Lit(lit) => Ok(lit),
+            _ => Err(self.error("expected a literal like `name = \"value\"`")),
+        }
+    }
+
+    pub fn expect_str(&self) -> syn::Result<String> {
+        match self.expect_lit()? {
+            Lit::Str(s) => Ok(s.value()),
+            lit => Err(syn::Error::new(lit.span(), "expected a string literal")),
+        }
+    }
+
+    pub fn expect_int<N>(&self) -> syn::Result<N>
+    where
+        N: FromStr,
+        N::Err: Display,
+    {
+        match self.expect_lit()? {
+            Lit::Int(i) => i.base10_parse(),
+            lit => Err(syn::Error::new(lit.span(), "expected an integer literal")),
+        }
+    }
+
+    /// Bare paths are treated as `true`, so `skip` and `skip = true` are equivalent
+    pub fn expect_bool(&self) -> syn::Result<bool> {
+        if let Self::Path(_) = self {
+            return Ok(true);
+        }
+
+        match self.expect_lit()? {
+            Lit::Bool(LitBool { value, .. }) => Ok(*value),
+            lit => Err(syn::Error::new(lit.span(), "expected a boolean literal")),
+        }
+    }
+
+    /// Returns the identifier assigned to a name-value node, e.g. `Other` in `with = Other`
+    pub fn expect_ident(&self) -> syn::Result<syn::Ident> {
+        match self {
+            Self::NameValue(_, value) => match &**value {
+                Expr::Path(path) if path.qself.is_none() => match path.path.get_ident() {
+                    Some(ident) => Ok(ident.clone()),
+                    None => Err(self.error("expected an identifier like `name = Other`")),
+                },
+                _ => Err(self.error("expected an identifier like `name = Other`")),
+            },
+            _ => Err(self.error("expected an identifier like `name = Other`")),
+        }
+    }
+
+    /// Returns the string value of the child `name` if present
+    pub fn get_str(&self, name: &str) -> syn::Result<Option<String>> {
+        self.child(name).map(Self::expect_str).transpose()
+    }
+
+    /// Converts a literal argument into tokens usable as a const generic argument or the value
+    /// of an associated const of type `ty`
+    ///
+    /// Supports the integer types, `bool`, `char`, `f32`, `f64` and `&str`. The literal is checked
+    /// against `ty`, including the range of integers, and negative values are wrapped in braces
+    /// so they are valid const generic arguments, e.g. `size = 4` becomes `4` for `usize` but
+    /// `size = -4` is an error for it
+    pub fn const_value(&self, ty: &Type) -> syn::Result<TokenStream> {
+        let (negative, lit) = self.signed_lit()?;
+        let ty_name = ty.to_token_stream().to_string().replace(' ', "");
+
+        let value = match (ty_name.as_str(), lit) {
+            (
+                "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64"
+                | "i128" | "isize",
+                Lit::Int(int),
+            ) => {
+                let digits = match negative {
+                    true => format!("-{}", int.base10_digits()),
+                    false => int.base10_digits().to_string(),
+                };
+                if !int_fits(&ty_name, &digits) {
+                    return Err(syn::Error::new(
+                        int.span(),
+                        format!("`{digits}` is out of range for `{ty_name}`"),
+                    ));
+                }
+                LitInt::new(int.base10_digits(), int.span()).into_token_stream()
+            }
+            ("f32" | "f64", Lit::Float(_) | Lit::Int(_)) => {
+                let digits = match lit {
+                    Lit::Float(f) => f.base10_digits().to_string(),
+                    Lit::Int(i) => format!("{}.0", i.base10_digits()),
+                    _ => unreachable!(),
+                };
+                LitFloat::new(&digits, lit.span()).into_token_stream()
+            }
+            ("bool", Lit::Bool(_))
+            | ("char", Lit::Char(_))
+            | ("&str" | "&'staticstr", Lit::Str(_))
+                if !negative =>
+            {
+                lit.to_token_stream()
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    self.span(),
+                    format!("expected a value of type `{ty_name}`"),
+                ))
+            }
+        };
+
+        Ok(match negative {
+            true => quote::quote_spanned!(lit.span()=> { -#value }),
+            false => value,
+        })
+    }
+
+    /// Like [MetaTree::expect_lit], but also accepts negated literals like `offset = -1`
+    fn signed_lit(&self) -> syn::Result<(bool, &Lit)> {
+        if let Self::NameValue(_, value) = self {
+            if let Expr::Unary(ExprUnary {
+                op: UnOp::Neg(_),
+                expr,
+                ..
+            }) = &**value
+            {
+                if let Expr::Lit(ExprLit { lit, .. }) = &**expr {
+                    return Ok((true, lit));
+                }
+            }
+        }
+
+        Ok((false, self.expect_lit()?))
+    }
+
+    fn error(&self, message: &str) -> syn::Error {
+        syn::Error::new(self.span(), message)
+    }
+}
+
+/// Returns `true` if the integer `digits` fits into the primitive integer type `ty`
+fn int_fits(ty: &str, digits: &str) -> bool {
+    match ty {
+        "u8" => digits.parse::<u8>().is_ok(),
+        "u16" => digits.parse::<u16>().is_ok(),
+        "u32" => digits.parse::<u32>().is_ok(),
+        "u64" => digits.parse::<u64>().is_ok(),
+        "u128" => digits.parse::<u128>().is_ok(),
+        "usize" => digits.parse::<usize>().is_ok(),
+        "i8" => digits.parse::<i8>().is_ok(),
+        "i16" => digits.parse::<i16>().is_ok(),
+        "i32" => digits.parse::<i32>().is_ok(),
+        "i64" => digits.parse::<i64>().is_ok(),
+        "i128" => digits.parse::<i128>().is_ok(),
+        "isize" => digits.parse::<isize>().is_ok(),
+        _ => false,
+    }
+}
+
+impl Parse for MetaTree {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Lit) {
+            return Ok(Self::Lit(input.parse()?));
+        }
+
+        let path = input.call(Path::parse_mod_style)?;
+
+        if input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            Ok(Self::List(path, Self::parse_children(&content)?))
+        } else if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Ok(Self::NameValue(path, input.parse()?))
+        } else {
+            Ok(Self::Path(path))
+        }
+    }
+}
+
+impl ToTokens for MetaTree {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Self::Path(path) => path.to_tokens(tokens),
+            Self::List(path, children) => {
+                path.to_tokens(tokens);
+                token::Paren::default().surround(tokens, |tokens| {
+                    for (i, child) in children.iter().enumerate() {
+                        if i > 0 {
+                            <Token![,]>::default().to_tokens(tokens);
+                        }
+                        child.to_tokens(tokens);
+                    }
+                });
+            }
+            Self::NameValue(path, value) => {
+                path.to_tokens(tokens);
+                <Token![=]>::default().to_tokens(tokens);
+                value.to_tokens(tokens);
+            }
+            Self::Lit(lit) => lit.to_tokens(tokens),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Data computed by passes, attached to items without changing them
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+
+use syn::Item;
+
+use crate::{ItemHandle, MacroScope};
+
+/// A value of the side-table, cloneable behind a `dyn`
+trait Value: Any {
+    fn clone_value(&self) -> Box<dyn Value>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any + Clone> Value for T {
+    fn clone_value(&self) -> Box<dyn Value> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// The address of an item and the type of a value
+type Key = (*const RefCell<Item>, TypeId);
+
+/// A value together with the item it is attached to
+type Entry = (Rc<RefCell<Item>>, Box<dyn Value>);
+
+/// The metadata of all items
+///
+/// Every entry keeps its item alive, so the address can't be reused by another item
+#[derive(Default)]
+pub(crate) struct Metadata {
+    entries: HashMap<Key, Entry>,
+}
+
+impl Metadata {
+    fn key<T: 'static>(item: &ItemHandle) -> Key {
+        (Rc::as_ptr(item.shared()), TypeId::of::<T>())
+    }
+}
+
+impl Clone for Metadata {
+    fn clone(&self) -> Self {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(key, (item, value))| (*key, (item.clone(), (**value).clone_value())))
+            .collect();
+        Self { entries }
+    }
+}
+
+impl fmt::Debug for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metadata")
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+impl MacroScope {
+    /// Returns the value of type `T` attached to `item`, see [MacroScope::set_metadata]
+    pub fn metadata<T: Any + Clone>(&self, item: &ItemHandle) -> Option<&T> {
+        let (_, value) = self.metadata.entries.get(&Metadata::key::<T>(item))?;
+        (**value).as_any().downcast_ref()
+    }
+
+    /// Returns the value of type `T` attached to `item`, attaching `T::default()` first if
+    /// there is none
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// #[derive(Clone, Default)]
+    /// struct Layout {
+    ///     size: usize,
+    /// }
+    ///
+    /// let mut scope: MacroScope = "mod scope { struct A(u32); }".parse().unwrap();
+    /// let item = scope.item_handles().remove(0);
+    ///
+    /// scope.metadata_mut::<Layout>(&item).size = 4;
+    /// assert_eq!(scope.metadata::<Layout>(&item).unwrap().size, 4);
+    /// ```
+    pub fn metadata_mut<T: Any + Clone + Default>(&mut self, item: &ItemHandle) -> &mut T {
+        let (_, value) = self
+            .metadata
+            .entries
+            .entry(Metadata::key::<T>(item))
+            .or_insert_with(|| (item.shared().clone(), Box::new(T::default())));
+        (**value)
+            .as_any_mut()
+            .downcast_mut()
+            .expect("metadata is keyed by its type")
+    }
+
+    /// Attaches `value` to `item` without changing the item, returns the value of the same type
+    /// attached before
+    ///
+    /// Passes of a [Pipeline](crate::Pipeline) can share analysis results like computed field
+    /// layouts this way, every item holds at most one value per type. Metadata is cloned with the
+    /// scope and rolled back by [MacroScope::transaction]
+    pub fn set_metadata<T: Any + Clone>(&mut self, item: &ItemHandle, value: T) -> Option<T> {
+        let previous = self.metadata.entries.insert(
+            Metadata::key::<T>(item),
+            (item.shared().clone(), Box::new(value)),
+        );
+        previous.and_then(|(_, value)| value.into_any().downcast().ok().map(|value| *value))
+    }
+
+    /// Removes the value of type `T` attached to `item` and returns it
+    pub fn take_metadata<T: Any + Clone>(&mut self, item: &ItemHandle) -> Option<T> {
+        let (_, value) = self.metadata.entries.remove(&Metadata::key::<T>(item))?;
+        value.into_any().downcast().ok().map(|value| *value)
+    }
+}
@@ -0,0 +1,267 @@
+//! Scanning of structs, enums and functions without syn's `full` feature
+//!
+//! Proc-macro crates that only look at marked structs, enums and functions can disable the
+//! default `full` feature, so syn is compiled without the syntax tree of all other items and of
+//! expressions. The module is split into its items at the token level like a lazily parsed
+//! scope, structs and enums are parsed into a [DeriveInput], functions only up to their name and
+//! all other items are kept as the tokens they were written as
+
+use std::{cell::RefCell, rc::Rc, str::FromStr};
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Parser},
+    token, AttrStyle, Attribute, DeriveInput, Ident, Token, Visibility,
+};
+
+use crate::{find_attribute, lazy, MarkedItem, MatchMode, SharedMarkedItem, MISSING_BODY};
+
+/// An item of a [MinimalScope]
+#[derive(Debug, Clone)]
+pub enum MinimalItem {
+    Struct(DeriveInput),
+    Enum(DeriveInput),
+    Fn(MinimalFn),
+    /// Any other item, and structs and enums syn fails to parse, which are left for the compiler
+    /// to report
+    Other(TokenStream),
+}
+
+/// A function parsed up to its name
+#[derive(Debug, Clone)]
+pub struct MinimalFn {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    /// The function after its visibility, from qualifiers like `async` to the body
+    pub tokens: TokenStream,
+}
+
+/// Returns the first identifier of an item after its visibility that isn't a function
+/// qualifier, e.g. `fn` for `const unsafe fn` or `impl` for `unsafe impl`
+fn keyword(tokens: &TokenStream) -> Option<Ident> {
+    let mut tokens = tokens.clone().into_iter();
+
+    loop {
+        match tokens.next()? {
+            TokenTree::Ident(ident) => match ident.to_string().as_str() {
+                "const" | "async" | "unsafe" | "extern" => (),
+                _ => return Some(ident),
+            },
+            // the ABI of `extern "C" fn`
+            TokenTree::Literal(_) => (),
+            _ => return None,
+        }
+    }
+}
+
+impl MinimalItem {
+    /// Parses the tokens of a single item as split by [lazy::split_items]
+    fn parse(tokens: TokenStream) -> Self {
+        let split = |input: ParseStream| {
+            let attrs = input.call(Attribute::parse_outer)?;
+            let vis: Visibility = input.parse()?;
+            Ok((attrs, vis, input.parse::<TokenStream>()?))
+        };
+        let Ok((attrs, vis, rest)) = split.parse2(tokens.clone()) else {
+            return Self::Other(tokens);
+        };
+
+        match keyword(&rest).map(|k| k.to_string()).as_deref() {
+            Some("struct") => syn::parse2(tokens.clone()).map_or(Self::Other(tokens), Self::Struct),
+            Some("enum") => syn::parse2(tokens.clone()).map_or(Self::Other(tokens), Self::Enum),
+            Some("fn") => {
+                let mut after_fn = rest.clone().into_iter().skip_while(|tt| match tt {
+                    TokenTree::Ident(ident) => ident != "fn",
+                    _ => true,
+                });
+                match (after_fn.next(), after_fn.next()) {
+                    (Some(_), Some(TokenTree::Ident(ident))) => Self::Fn(MinimalFn {
+                        attrs,
+                        vis,
+                        ident,
+                        tokens: rest,
+                    }),
+                    _ => Self::Other(tokens),
+                }
+            }
+            _ => Self::Other(tokens),
+        }
+    }
+
+    /// Returns the attributes of structs, enums and functions, other items have none
+    pub fn attrs(&self) -> &[Attribute] {
+        match self {
+            Self::Struct(item) | Self::Enum(item) => &item.attrs,
+            Self::Fn(item) => &item.attrs,
+            Self::Other(_) => &[],
+        }
+    }
+
+    /// Mutable version of [MinimalItem::attrs]
+    fn attrs_mut(&mut self) -> Option<&mut Vec<Attribute>> {
+        match self {
+            Self::Struct(item) | Self::Enum(item) => Some(&mut item.attrs),
+            Self::Fn(item) => Some(&mut item.attrs),
+            Self::Other(_) => None,
+        }
+    }
+
+    /// Returns the name of structs, enums and functions
+    pub fn ident(&self) -> Option<&Ident> {
+        match self {
+            Self::Struct(item) | Self::Enum(item) => Some(&item.ident),
+            Self::Fn(item) => Some(&item.ident),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl ToTokens for MinimalItem {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Self::Struct(item) | Self::Enum(item) => item.to_tokens(tokens),
+            Self::Fn(item) => item.to_tokens(tokens),
+            Self::Other(item) => item.to_tokens(tokens),
+        }
+    }
+}
+
+impl ToTokens for MinimalFn {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(self.attrs.iter().map(ToTokens::to_token_stream));
+        self.vis.to_tokens(tokens);
+        self.tokens.to_tokens(tokens);
+    }
+}
+
+/// A scope that only scans structs, enums and functions, so it builds without syn's `full`
+/// feature
+///
+/// Items are split at the token level, so every item syn supports can be in the module, but only
+/// structs, enums and functions can be marked
+///
+/// ```
+/// # use macro_scope::MinimalScope;
+/// # use quote::ToTokens;
+/// let scope: MinimalScope = "mod scope { #[system] async fn update() {} impl A {} }"
+///     .parse()
+///     .unwrap();
+///
+/// let marked = scope.get_marked("system");
+/// assert_eq!(marked[0].item.borrow().ident().unwrap(), "update");
+/// assert_eq!(
+///     scope.to_token_stream().to_string(),
+///     "mod scope { async fn update () { } impl A { } }"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct MinimalScope {
+    pub items: Vec<Rc<RefCell<MinimalItem>>>,
+    /// The attributes of the module, including its inner attributes
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub ident: Ident,
+    /// How attribute names are compared to marks
+    pub match_mode: MatchMode,
+    mod_token: Token![mod],
+    brace: token::Brace,
+}
+
+impl MinimalScope {
+    /// Returns all structs, enums and functions marked with `mark` in source order, the marks are
+    /// removed from the items
+    pub fn get_marked(&self, mark: &str) -> Vec<SharedMarkedItem<MinimalItem>> {
+        let mut marked = Vec::new();
+
+        for (position, item) in self.items.iter().enumerate() {
+            let Some((index, _)) = find_attribute(item.borrow().attrs(), mark, self.match_mode)
+            else {
+                continue;
+            };
+            let Some(attr) = item.borrow_mut().attrs_mut().map(|a| a.remove(index)) else {
+                continue;
+            };
+
+            let mut marked_item = MarkedItem::new(attr, item.clone());
+            marked_item.index = index;
+            marked_item.position = position;
+            marked.push(marked_item);
+        }
+
+        marked
+    }
+}
+
+impl ToTokens for MinimalScope {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let style = |inner: bool| {
+            move |attr: &&Attribute| matches!(attr.style, AttrStyle::Inner(_)) == inner
+        };
+
+        tokens.extend(
+            self.attrs
+                .iter()
+                .filter(style(false))
+                .map(ToTokens::to_token_stream),
+        );
+        self.vis.to_tokens(tokens);
+        self.mod_token.to_tokens(tokens);
+        self.ident.to_tokens(tokens);
+        self.brace.surround(tokens, |tokens| {
+            tokens.extend(
+                self.attrs
+                    .iter()
+                    .filter(style(true))
+                    .map(ToTokens::to_token_stream),
+            );
+            tokens.extend(
+                self.items
+                    .iter()
+                    .map(|item| item.borrow().to_token_stream()),
+            );
+        });
+    }
+}
+
+/// Parses a scope from source code, useful for testing outside of a proc_macro context
+impl FromStr for MinimalScope {
+    type Err = syn::Error;
+
+    fn from_str(s: &str) -> syn::Result<Self> {
+        syn::parse_str(s)
+    }
+}
+
+impl Parse for MinimalScope {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let mod_token = input.parse()?;
+        let ident = input.parse()?;
+
+        if input.peek(Token![;]) {
+            return Err(input.error(MISSING_BODY));
+        }
+
+        let content;
+        let brace = braced!(content in input);
+        attrs.extend(content.call(Attribute::parse_inner)?);
+        let items = lazy::split_items(content.parse()?)
+            .into_iter()
+            .map(|tokens| Rc::new(RefCell::new(MinimalItem::parse(tokens))))
+            .collect();
+
+        Ok(Self {
+            items,
+            attrs,
+            vis,
+            ident,
+            match_mode: MatchMode::default(),
+            mod_token,
+            brace,
+        })
+    }
+}
@@ -0,0 +1,115 @@
+//! Scope modules inside of other scopes, e.g. plugin scopes inside of an app scope
+
+use std::{cell::RefCell, rc::Rc};
+
+use syn::{Item, ItemMod};
+
+use crate::{find_attribute, item_attrs_mut, lazy, MacroScope, MarkedItem, SharedMarkedItem};
+
+impl MacroScope {
+    /// Returns the inline modules marked with `mark` that are scopes of their own, in source
+    /// order
+    ///
+    /// Unlike other queries this leaves the mark on the module. Every nested scope should be
+    /// passed to one of [MacroScope::defer_scope], [MacroScope::consume_scope] or
+    /// [MacroScope::merge_scope]
+    pub fn nested_scopes(&self, mark: &str) -> Vec<SharedMarkedItem<Item>> {
+        let mut nested = Vec::new();
+
+        for (position, item) in self.scanned_items().into_iter().enumerate() {
+            if let Item::Verbatim(tokens) = &*item.borrow() {
+                if !lazy::is_module(tokens) {
+                    continue;
+                }
+            }
+            let _ = lazy::parse(&item);
+
+            let found = match &*item.borrow() {
+                Item::Mod(ItemMod {
+                    attrs,
+                    content: Some(_),
+                    ..
+                }) => find_attribute(attrs, mark, self.config.match_mode)
+                    .map(|(index, _)| (index, attrs[index].clone())),
+                _ => None,
+            };
+
+            if let Some((index, attr)) = found {
+                nested.push(MarkedItem {
+                    mark: attr,
+                    item: item.clone(),
+                    index,
+                    position,
+                });
+            }
+        }
+
+        nested
+    }
+
+    /// Leaves a nested scope to its own macro, the module is emitted unchanged with its mark
+    /// and its items are no longer scanned by this scope
+    pub fn defer_scope(&mut self, nested: &SharedMarkedItem<Item>) {
+        self.unlift(&nested.item);
+    }
+
+    /// Takes a nested scope out of this scope and returns it as a separate [MacroScope]
+    /// parsed with the same config, e.g. to expand it with the templates of the outer macro
+    ///
+    /// The module is no longer emitted by this scope, the mark is removed from the returned
+    /// scope's module
+    pub fn consume_scope(&mut self, nested: &SharedMarkedItem<Item>) -> syn::Result<MacroScope> {
+        self.unlift(&nested.item);
+        remove_mark(nested);
+
+        self.drop_index();
+        self.items.retain(|item| !Rc::ptr_eq(item, &nested.item));
+        for (_, children) in &mut self.modules {
+            children.retain(|item| !Rc::ptr_eq(item, &nested.item));
+        }
+
+        let tokens = quote::ToTokens::to_token_stream(&*nested.item.borrow());
+        MacroScope::parse_with_config(tokens, self.config.clone())
+    }
+
+    /// Dissolves a nested scope into this scope, its mark is removed and its items are scanned
+    /// together with the items of this scope, even if it wasn't parsed with
+    /// [ScopeConfig::recursive](crate::ScopeConfig::recursive)
+    pub fn merge_scope(&mut self, nested: &SharedMarkedItem<Item>) {
+        remove_mark(nested);
+        if self.children(&nested.item).is_none() {
+            self.lift_modules(std::slice::from_ref(&nested.item), 1);
+        }
+    }
+
+    /// Puts the lifted content of `module` and the modules nested in it back into the module
+    fn unlift(&mut self, module: &Rc<RefCell<Item>>) {
+        let Some(children) = self.children(module).map(<[_]>::to_vec) else {
+            return;
+        };
+        self.drop_index();
+
+        let content: Vec<Item> = children.iter().map(|c| self.emit_item(c)).collect();
+        let mut nested = Vec::new();
+        self.collect_items(&children, &mut nested);
+        self.modules
+            .retain(|(m, _)| !Rc::ptr_eq(m, module) && !nested.iter().any(|n| Rc::ptr_eq(n, m)));
+
+        if let Item::Mod(ItemMod {
+            content: Some((_, c)),
+            ..
+        }) = &mut *module.borrow_mut()
+        {
+            *c = content;
+        }
+    }
+}
+
+/// Removes the mark of `nested` from its module
+fn remove_mark(nested: &SharedMarkedItem<Item>) {
+    if let Some(attrs) = item_attrs_mut(&mut nested.item.borrow_mut()) {
+        if let Some(index) = attrs.iter().position(|attr| *attr == nested.mark) {
+            attrs.remove(index);
+        }
+    }
+}
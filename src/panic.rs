@@ -0,0 +1,37 @@
+//! Panics of templates turned into errors at the item being expanded
+
+use std::panic::{self, AssertUnwindSafe};
+
+use syn::Item;
+
+use crate::{Diagnostics, ItemKey, SharedMarkedItem};
+
+/// Returns the message a panic was started with
+fn message(payload: &(dyn std::any::Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown payload", String::as_str),
+    }
+}
+
+/// Calls `f` and returns an error at `item` if it panics, see [MacroScope::catch_panics]
+///
+/// [MacroScope::catch_panics]: crate::MacroScope::catch_panics
+pub(crate) fn catch<T>(
+    mark: &str,
+    item: &SharedMarkedItem<Item>,
+    f: impl FnOnce() -> syn::Result<T>,
+) -> syn::Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let key = ItemKey::of(&item.item.borrow());
+        Err(Diagnostics::related(
+            std::slice::from_ref(item),
+            format!(
+                "the template of `#[{mark}]` panicked on {key}: {}",
+                message(&*payload)
+            ),
+        ))
+    })
+}
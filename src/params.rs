@@ -0,0 +1,113 @@
+//! Marks on the parameters of functions and methods, e.g. `fn handler(#[inject] db: Database)`
+
+use syn::{Attribute, FnArg, Ident, ImplItem, Item, Pat, Signature, TraitItem, Type};
+
+use crate::{find_attribute, lazy, take_mark, ItemHandle, MacroScope, ScopeConfig};
+
+/// A function parameter carrying a mark, see [MacroScope::get_marked_params]
+#[derive(Debug, Clone)]
+pub struct MarkedParam {
+    pub mark: Attribute,
+    /// The function, or the impl block or trait of the method, the parameter belongs to
+    pub item: ItemHandle,
+    /// The name of the method if the parameter belongs to a method of an impl block or trait
+    pub method: Option<Ident>,
+    /// Position of the parameter in the inputs of the function, a `self` receiver counts
+    pub index: usize,
+    pub pat: Pat,
+    pub ty: Type,
+}
+
+/// Takes `mark` from the typed parameters of `sig`, returns the parameters with their index
+fn take_params(
+    sig: &mut Signature,
+    mark: &str,
+    config: &ScopeConfig,
+) -> Vec<(Attribute, usize, Pat, Type)> {
+    let mut params = Vec::new();
+
+    for (index, input) in sig.inputs.iter_mut().enumerate() {
+        let FnArg::Typed(typed) = input else {
+            continue;
+        };
+        if let Some((attr, _)) = find_attribute(&typed.attrs, mark, config.match_mode) {
+            let mark = take_mark(&mut typed.attrs, attr, config);
+            params.push((mark, index, (*typed.pat).clone(), (*typed.ty).clone()));
+        }
+    }
+
+    params
+}
+
+impl MacroScope {
+    /// Returns the parameters marked with `mark` of all scanned functions and of the methods of
+    /// scanned impl blocks and traits, in source order
+    ///
+    /// The marks are handled according to [ScopeConfig::mark_policy], attribute macros are not
+    /// allowed on parameters so they usually have to be removed. Unlike item marks, parameter
+    /// marks are found independent of [ScopeConfig::scan_set]
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// let scope: MacroScope = "mod scope { fn handler(#[inject] db: Database, id: u32) {} }"
+    ///     .parse()
+    ///     .unwrap();
+    ///
+    /// let params = scope.get_marked_params("inject");
+    /// assert_eq!(params.len(), 1);
+    /// assert_eq!(params[0].index, 0);
+    /// ```
+    ///
+    /// [ScopeConfig::mark_policy]: crate::ScopeConfig::mark_policy
+    /// [ScopeConfig::scan_set]: crate::ScopeConfig::scan_set
+    pub fn get_marked_params(&self, mark: &str) -> Vec<MarkedParam> {
+        let mut marked = Vec::new();
+
+        for item in self.scanned_items() {
+            if let Item::Verbatim(tokens) = &*item.borrow() {
+                if !tokens.to_string().contains(mark) {
+                    continue;
+                }
+            }
+            // items that don't parse are left for the compiler to report
+            let _ = lazy::parse(&item);
+
+            let mut found = Vec::new();
+            match &mut *item.borrow_mut() {
+                Item::Fn(f) => found.push((None, take_params(&mut f.sig, mark, &self.config))),
+                Item::Impl(i) => {
+                    for impl_item in &mut i.items {
+                        if let ImplItem::Fn(f) = impl_item {
+                            let params = take_params(&mut f.sig, mark, &self.config);
+                            found.push((Some(f.sig.ident.clone()), params));
+                        }
+                    }
+                }
+                Item::Trait(t) => {
+                    for trait_item in &mut t.items {
+                        if let TraitItem::Fn(f) = trait_item {
+                            let params = take_params(&mut f.sig, mark, &self.config);
+                            found.push((Some(f.sig.ident.clone()), params));
+                        }
+                    }
+                }
+                _ => (),
+            }
+
+            for (method, params) in found {
+                for (mark, index, pat, ty) in params {
+                    marked.push(MarkedParam {
+                        mark,
+                        item: ItemHandle::from(item.clone()),
+                        method: method.clone(),
+                        index,
+                        pat,
+                        ty,
+                    });
+                }
+            }
+        }
+
+        marked
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+
+use proc_macro2::Span;
+
+use crate::MacroScope;
+
+/// A single step of a [Pipeline]
+///
+/// Passes receive the whole scope, so they can validate items, mutate them or push new items
+/// that are picked up by the passes registered after them
+pub trait Pass {
+    fn run(&self, scope: &mut MacroScope) -> syn::Result<()>;
+}
+
+impl<F> Pass for F
+where
+    F: Fn(&mut MacroScope) -> syn::Result<()>,
+{
+    fn run(&self, scope: &mut MacroScope) -> syn::Result<()> {
+        self(scope)
+    }
+}
+
+/// Runs a sequence of [Pass]es over a [MacroScope] in registration order
+///
+/// Passes can depend on other passes with [Pipeline::depends_on], e.g. a pass generating code
+/// for `#[system]` items on the pass collecting `#[resource]` items. Dependencies run first, the
+/// other passes keep their registration order
+#[derive(Default)]
+pub struct Pipeline {
+    passes: Vec<(String, Box<dyn Pass>)>,
+    dependencies: Vec<(String, String)>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a pass to the pipeline
+    pub fn pass(mut self, name: impl Into<String>, pass: impl Pass + 'static) -> Self {
+        self.passes.push((name.into(), Box::new(pass)));
+        self
+    }
+
+    /// Declares that the pass `pass` has to run after the pass `dependency`
+    pub fn depends_on(mut self, pass: impl Into<String>, dependency: impl Into<String>) -> Self {
+        self.dependencies.push((pass.into(), dependency.into()));
+        self
+    }
+
+    /// Returns the names of all passes in registration order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the names of all passes in execution order
+    ///
+    /// Fails if a dependency names an unknown pass or if passes depend on each other
+    pub fn order(&self) -> syn::Result<Vec<&str>> {
+        Ok(self
+            .ordered()?
+            .into_iter()
+            .map(|i| self.passes[i].0.as_str())
+            .collect())
+    }
+
+    /// Returns the indices of the passes in execution order, the first pass whose dependencies
+    /// all ran is next
+    fn ordered(&self) -> syn::Result<Vec<usize>> {
+        let index = |name: &str| {
+            self.passes
+                .iter()
+                .position(|(n, _)| n == name)
+                .ok_or_else(|| error(format!("pipeline has no pass named `{name}`")))
+        };
+
+        let mut dependencies = vec![Vec::new(); self.passes.len()];
+        for (pass, dependency) in &self.dependencies {
+            dependencies[index(pass)?].push(index(dependency)?);
+        }
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut done = vec![false; self.passes.len()];
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len())
+                .find(|&i| !done[i] && dependencies[i].iter().all(|&d| done[d]));
+            let Some(next) = next else {
+                let cycle: Vec<_> = (0..self.passes.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| format!("`{}`", self.passes[i].0))
+                    .collect();
+                return Err(error(format!(
+                    "pipeline passes depend on each other: {}",
+                    cycle.join(", ")
+                )));
+            };
+            done[next] = true;
+            order.push(next);
+        }
+
+        Ok(order)
+    }
+
+    /// Runs every pass in order, stopping at the first error
+    ///
+    /// Mutations recorded in the [journal](MacroScope::journal) are attributed to the running pass
+    pub fn run(&self, scope: &mut MacroScope) -> syn::Result<()> {
+        for i in self.ordered()? {
+            let (name, pass) = &self.passes[i];
+            scope.set_pass(Some(name.clone()));
+            let result = pass.run(scope);
+            scope.set_pass(None);
+            result?;
+        }
+
+        Ok(())
+    }
+}
+
+fn error(message: String) -> syn::Error {
+    syn::Error::new(Span::call_site(), message)
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("passes", &self.names().collect::<Vec<_>>())
+            .field("dependencies", &self.dependencies)
+            .finish()
+    }
+}
@@ -0,0 +1,152 @@
+//! Mark handlers published as separate crates, combined into a single scope macro
+
+use std::fmt;
+
+use proc_macro2::{Span, TokenStream};
+
+use crate::{diagnostics, Diagnostics, MacroScope};
+
+/// A reusable set of mark handlers, e.g. a crate generating code for `#[system]` and
+/// `#[resource]` items
+///
+/// The trait is object safe, so plugins from different crates can be combined at runtime into a
+/// [PluginSet] behind a single `#[macro_scope]` entry point
+pub trait ScopePlugin {
+    /// The name of the plugin, used in errors and in the [journal](MacroScope::journal)
+    fn name(&self) -> &str;
+
+    /// The marks the plugin handles, no two plugins of a [PluginSet] may handle the same mark
+    fn marks(&self) -> &[&str];
+
+    /// Processes the scope and returns the generated code, which is placed after the items
+    fn process(&self, scope: &mut MacroScope) -> syn::Result<TokenStream>;
+}
+
+/// Runs a set of [ScopePlugin]s over a [MacroScope] in registration order
+///
+/// ```
+/// # use macro_scope::{MacroScope, PluginSet, ScopePlugin};
+/// # use proc_macro2::TokenStream;
+/// struct Systems;
+///
+/// impl ScopePlugin for Systems {
+///     fn name(&self) -> &str {
+///         "systems"
+///     }
+///
+///     fn marks(&self) -> &[&str] {
+///         &["system"]
+///     }
+///
+///     fn process(&self, scope: &mut MacroScope) -> syn::Result<TokenStream> {
+///         let count = scope.get_marked("system").len();
+///         Ok(quote::quote!(const SYSTEMS: usize = #count;))
+///     }
+/// }
+///
+/// let mut scope: MacroScope = "mod scope { #[system] fn a() {} }".parse().unwrap();
+/// let tokens = PluginSet::new().with(Systems).expand(&mut scope).unwrap();
+/// assert!(tokens.to_string().contains("const SYSTEMS"));
+/// ```
+#[derive(Default)]
+pub struct PluginSet {
+    plugins: Vec<Box<dyn ScopePlugin>>,
+}
+
+impl PluginSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a plugin to the set
+    pub fn with(mut self, plugin: impl ScopePlugin + 'static) -> Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Appends a boxed plugin to the set, e.g. one returned by a plugin crate
+    pub fn push(&mut self, plugin: Box<dyn ScopePlugin>) {
+        self.plugins.push(plugin);
+    }
+
+    /// Returns the names of all plugins in registration order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.plugins.iter().map(|plugin| plugin.name())
+    }
+
+    /// Returns the marks handled by all plugins in registration order
+    pub fn marks(&self) -> impl Iterator<Item = &str> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.marks().iter().copied())
+    }
+
+    /// Fails if two plugins handle the same mark
+    fn check_marks(&self) -> syn::Result<()> {
+        let mut diagnostics = Diagnostics::new();
+
+        for (i, plugin) in self.plugins.iter().enumerate() {
+            for mark in plugin.marks() {
+                let earlier = self.plugins[..i]
+                    .iter()
+                    .find(|other| other.marks().contains(mark));
+                if let Some(other) = earlier {
+                    diagnostics.emit(
+                        Span::call_site(),
+                        format!(
+                            "`#[{mark}]` is handled by both `{}` and `{}`",
+                            other.name(),
+                            plugin.name()
+                        ),
+                    );
+                }
+            }
+        }
+
+        diagnostics.finish()
+    }
+
+    /// Runs every plugin in order and returns the code they generated
+    ///
+    /// All plugins run even if one fails, their errors are combined. With
+    /// [ScopeConfig::strict] marks that look like misspellings of the marks of all plugins are
+    /// reported first
+    ///
+    /// [ScopeConfig::strict]: crate::ScopeConfig::strict
+    pub fn run(&self, scope: &mut MacroScope) -> syn::Result<TokenStream> {
+        self.check_marks()?;
+        if scope.config.strict {
+            let marks: Vec<_> = self.marks().collect();
+            diagnostics::check_unknown_marks(&scope.scanned_items(), &marks, &scope.config)?;
+        }
+
+        let mut diagnostics = Diagnostics::new();
+        let mut generated = TokenStream::new();
+        for plugin in &self.plugins {
+            scope.set_pass(Some(plugin.name().to_string()));
+            let result = plugin.process(scope);
+            scope.set_pass(None);
+            if let Some(tokens) = diagnostics.check(result) {
+                generated.extend(tokens);
+            }
+        }
+
+        diagnostics.finish()?;
+        Ok(generated)
+    }
+
+    /// Runs every plugin and returns the module with the generated code placed at its end, see
+    /// [PluginSet::run]
+    pub fn expand(&self, scope: &mut MacroScope) -> syn::Result<TokenStream> {
+        let generated = self.run(scope)?;
+        Ok(scope.emit(generated))
+    }
+}
+
+impl fmt::Debug for PluginSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginSet")
+            .field("plugins", &self.names().collect::<Vec<_>>())
+            .finish()
+    }
+}
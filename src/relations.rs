@@ -0,0 +1,128 @@
+//! Relationships between the items of a scope
+
+use std::{cell::RefCell, rc::Rc};
+
+use syn::{Ident, Item, ItemImpl, Type};
+
+use crate::{lazy, MacroScope, SharedMarkedItem};
+
+/// Returns `true` if the self type of the impl is a path ending with `ident`, ignoring generic
+/// arguments
+fn is_impl_for(item: &ItemImpl, ident: &str) -> bool {
+    match &*item.self_ty {
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == ident),
+        _ => false,
+    }
+}
+
+/// Returns `true` if the path of the implemented trait ends with `trait_ident`
+fn implements(item: &ItemImpl, trait_ident: &str) -> bool {
+    item.trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .is_some_and(|segment| segment.ident == trait_ident)
+}
+
+impl MacroScope {
+    /// Returns the impl blocks of the scope, parsing them in lazily parsed scopes
+    fn impls(&self) -> Vec<Rc<RefCell<Item>>> {
+        self.scanned_items()
+            .into_iter()
+            .filter(|item| {
+                // impls that don't parse can't be inspected anyway
+                let _ = lazy::parse(item);
+                matches!(*item.borrow(), Item::Impl(_))
+            })
+            .collect()
+    }
+
+    /// Returns the self types of all impl blocks implementing a trait named `trait_ident`, in
+    /// source order
+    ///
+    /// Traits are compared by the last segment of their path, so `Plugin` also matches
+    /// `impl crate::Plugin for ..`
+    pub fn implementors_of(&self, trait_ident: &Ident) -> Vec<Type> {
+        let trait_ident = trait_ident.to_string();
+
+        self.impls()
+            .iter()
+            .filter_map(|item| match &*item.borrow() {
+                Item::Impl(item) if implements(item, &trait_ident) => Some((*item.self_ty).clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns all impl blocks for the type named `ident`, inherent and trait impls, in source
+    /// order
+    ///
+    /// Useful to inspect the available methods of a marked struct when generating wrappers or
+    /// bindings for it
+    pub fn impls_for(&self, ident: &Ident) -> Vec<ItemImpl> {
+        let ident = ident.to_string();
+
+        self.impls()
+            .iter()
+            .filter_map(|item| match &*item.borrow() {
+                Item::Impl(item) if is_impl_for(item, &ident) => Some(item.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl MacroScope {
+    /// Collects the path of the module containing every scanned item, relative to the scope
+    fn collect_module_paths(
+        &self,
+        items: &[Rc<RefCell<Item>>],
+        path: &mut Vec<Ident>,
+        paths: &mut Vec<(Rc<RefCell<Item>>, Vec<Ident>)>,
+    ) {
+        for item in items {
+            paths.push((item.clone(), path.clone()));
+
+            if let Some(children) = self.children(item) {
+                let Item::Mod(module) = &*item.borrow() else {
+                    continue;
+                };
+                path.push(module.ident.clone());
+                self.collect_module_paths(children, path, paths);
+                path.pop();
+            }
+        }
+    }
+
+    /// Returns the items marked with `mark` grouped by the path of their containing module,
+    /// relative to the scope
+    ///
+    /// Items directly in the scope have an empty path. Groups are in the order their first item
+    /// appears in, nested modules are only scanned with [ScopeConfig::recursive]
+    ///
+    /// [ScopeConfig::recursive]: crate::ScopeConfig::recursive
+    pub fn group_by_module(&self, mark: &str) -> Vec<(Vec<Ident>, Vec<SharedMarkedItem<Item>>)> {
+        let mut paths = Vec::new();
+        self.collect_module_paths(&self.items, &mut Vec::new(), &mut paths);
+
+        let mut groups: Vec<(Vec<Ident>, Vec<SharedMarkedItem<Item>>)> = Vec::new();
+        for marked in self.get_marked(mark) {
+            let Some((_, path)) = paths
+                .iter()
+                .find(|(item, _)| Rc::ptr_eq(item, &marked.item))
+            else {
+                continue;
+            };
+
+            match groups.iter_mut().find(|(p, _)| p == path) {
+                Some((_, items)) => items.push(marked),
+                None => groups.push((path.clone(), vec![marked])),
+            }
+        }
+
+        groups
+    }
+}
@@ -0,0 +1,313 @@
+//! Renaming items of a scope together with all references to them
+
+use std::collections::HashMap;
+
+use proc_macro2::{TokenStream, TokenTree};
+use syn::{
+    visit::Visit, visit_mut::VisitMut, Arm, Attribute, BinOp, Block, Expr, ExprClosure,
+    ExprForLoop, ExprIf, ExprPath, ExprWhile, FnArg, Ident, ImplItemFn, Item, ItemFn, Macro, Pat,
+    PatIdent, Path, Signature, Stmt, TraitItemFn, UseTree,
+};
+
+use crate::{item_ident_mut, lazy, MacroScope};
+
+/// What [MacroScope::apply_renames] did and couldn't do
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenameReport {
+    /// The old names of the renamed declarations, in source order
+    pub renamed: Vec<Ident>,
+    /// The names of the map that no item of the scope is declared with
+    pub undeclared: Vec<Ident>,
+    /// Occurrences of old names inside macro invocations and items syn can't parse, which are
+    /// left untouched
+    pub unresolved: Vec<Ident>,
+}
+
+impl RenameReport {
+    /// Returns `true` if every name was declared and every reference was rewritten
+    pub fn is_complete(&self) -> bool {
+        self.undeclared.is_empty() && self.unresolved.is_empty()
+    }
+}
+
+/// Returns `ident` renamed to `to`, keeping the span of the original
+fn renamed(ident: &Ident, to: &Ident) -> Ident {
+    Ident::new(&to.to_string(), ident.span())
+}
+
+/// Collects the identifiers in `tokens` that are renamed
+fn find_unresolved(tokens: TokenStream, renames: &HashMap<Ident, Ident>, found: &mut Vec<Ident>) {
+    for tree in tokens {
+        match tree {
+            TokenTree::Ident(ident) if renames.contains_key(&ident) => found.push(ident),
+            TokenTree::Group(group) => find_unresolved(group.stream(), renames, found),
+            _ => (),
+        }
+    }
+}
+
+/// Collects the names bound by a pattern
+#[derive(Default)]
+struct Bindings(Vec<Ident>);
+
+impl Visit<'_> for Bindings {
+    fn visit_pat_ident(&mut self, pat: &PatIdent) {
+        self.0.push(pat.ident.clone());
+        syn::visit::visit_pat_ident(self, pat);
+    }
+}
+
+/// Rewrites the first segment of paths after `self` and `super`, other segments name items
+/// outside of the scope or members like enum variants
+struct Rewrite<'a> {
+    renames: &'a HashMap<Ident, Ident>,
+    unresolved: Vec<Ident>,
+    /// The local bindings in scope, which shadow renamed items in expressions
+    bindings: Vec<Ident>,
+}
+
+impl Rewrite<'_> {
+    /// Visits `pat` and brings its bindings into scope
+    fn bind(&mut self, pat: &mut Pat) {
+        self.visit_pat_mut(pat);
+        let mut bindings = Bindings::default();
+        bindings.visit_pat(pat);
+        self.bindings.extend(bindings.0);
+    }
+
+    /// Visits a condition, the bindings of `let` expressions stay in scope for the branch
+    fn visit_condition(&mut self, cond: &mut Expr) {
+        match cond {
+            Expr::Let(expr) => {
+                self.visit_expr_mut(&mut expr.expr);
+                self.bind(&mut expr.pat);
+            }
+            Expr::Binary(expr) if matches!(expr.op, BinOp::And(_)) => {
+                self.visit_condition(&mut expr.left);
+                self.visit_condition(&mut expr.right);
+            }
+            cond => self.visit_expr_mut(cond),
+        }
+    }
+
+    /// Visits a function, the bindings of the enclosing function aren't visible in it
+    fn visit_fn(
+        &mut self,
+        attrs: &mut [Attribute],
+        sig: &mut Signature,
+        block: Option<&mut Block>,
+    ) {
+        for attr in attrs {
+            self.visit_attribute_mut(attr);
+        }
+        let outer = std::mem::take(&mut self.bindings);
+        self.visit_signature_mut(sig);
+        for input in &sig.inputs {
+            if let FnArg::Typed(typed) = input {
+                let mut bindings = Bindings::default();
+                bindings.visit_pat(&typed.pat);
+                self.bindings.extend(bindings.0);
+            }
+        }
+        if let Some(block) = block {
+            self.visit_block_mut(block);
+        }
+        self.bindings = outer;
+    }
+
+    fn rewrite_use(&self, tree: &mut UseTree) {
+        match tree {
+            UseTree::Path(path) if path.ident == "self" || path.ident == "super" => {
+                self.rewrite_use(&mut path.tree)
+            }
+            UseTree::Path(path) => self.rename(&mut path.ident),
+            UseTree::Name(name) => self.rename(&mut name.ident),
+            UseTree::Rename(rename) => self.rename(&mut rename.ident),
+            UseTree::Group(group) => group
+                .items
+                .iter_mut()
+                .for_each(|tree| self.rewrite_use(tree)),
+            UseTree::Glob(_) => (),
+        }
+    }
+
+    fn rename(&self, ident: &mut Ident) {
+        if let Some(to) = self.renames.get(ident) {
+            *ident = renamed(ident, to);
+        }
+    }
+}
+
+impl VisitMut for Rewrite<'_> {
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        if path.leading_colon.is_none() {
+            let first = path
+                .segments
+                .iter_mut()
+                .find(|segment| segment.ident != "self" && segment.ident != "super");
+            if let Some(segment) = first.filter(|segment| segment.ident != "crate") {
+                self.rename(&mut segment.ident);
+            }
+        }
+
+        syn::visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_use_tree_mut(&mut self, tree: &mut UseTree) {
+        self.rewrite_use(tree);
+    }
+
+    fn visit_expr_path_mut(&mut self, expr: &mut ExprPath) {
+        let local = expr.qself.is_none()
+            && expr
+                .path
+                .get_ident()
+                .is_some_and(|ident| self.bindings.contains(ident));
+        if !local {
+            syn::visit_mut::visit_expr_path_mut(self, expr);
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        let len = self.bindings.len();
+        for stmt in &mut block.stmts {
+            let Stmt::Local(local) = stmt else {
+                self.visit_stmt_mut(stmt);
+                continue;
+            };
+            // the bindings of a `let` are only in scope after its initializer
+            if let Some(init) = &mut local.init {
+                self.visit_expr_mut(&mut init.expr);
+                if let Some((_, diverge)) = &mut init.diverge {
+                    self.visit_expr_mut(diverge);
+                }
+            }
+            self.bind(&mut local.pat);
+        }
+        self.bindings.truncate(len);
+    }
+
+    fn visit_item_fn_mut(&mut self, item: &mut ItemFn) {
+        self.visit_fn(&mut item.attrs, &mut item.sig, Some(&mut item.block));
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, item: &mut ImplItemFn) {
+        self.visit_fn(&mut item.attrs, &mut item.sig, Some(&mut item.block));
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, item: &mut TraitItemFn) {
+        self.visit_fn(&mut item.attrs, &mut item.sig, item.default.as_mut());
+    }
+
+    fn visit_expr_closure_mut(&mut self, expr: &mut ExprClosure) {
+        let len = self.bindings.len();
+        for input in &mut expr.inputs {
+            self.bind(input);
+        }
+        self.visit_return_type_mut(&mut expr.output);
+        self.visit_expr_mut(&mut expr.body);
+        self.bindings.truncate(len);
+    }
+
+    fn visit_arm_mut(&mut self, arm: &mut Arm) {
+        let len = self.bindings.len();
+        self.bind(&mut arm.pat);
+        if let Some((_, guard)) = &mut arm.guard {
+            self.visit_condition(guard);
+        }
+        self.visit_expr_mut(&mut arm.body);
+        self.bindings.truncate(len);
+    }
+
+    fn visit_expr_if_mut(&mut self, expr: &mut ExprIf) {
+        let len = self.bindings.len();
+        self.visit_condition(&mut expr.cond);
+        self.visit_block_mut(&mut expr.then_branch);
+        self.bindings.truncate(len);
+        if let Some((_, else_branch)) = &mut expr.else_branch {
+            self.visit_expr_mut(else_branch);
+        }
+    }
+
+    fn visit_expr_while_mut(&mut self, expr: &mut ExprWhile) {
+        let len = self.bindings.len();
+        self.visit_condition(&mut expr.cond);
+        self.visit_block_mut(&mut expr.body);
+        self.bindings.truncate(len);
+    }
+
+    fn visit_expr_for_loop_mut(&mut self, expr: &mut ExprForLoop) {
+        self.visit_expr_mut(&mut expr.expr);
+        let len = self.bindings.len();
+        self.bind(&mut expr.pat);
+        self.visit_block_mut(&mut expr.body);
+        self.bindings.truncate(len);
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut Macro) {
+        syn::visit_mut::visit_path_mut(self, &mut mac.path);
+        find_unresolved(mac.tokens.clone(), self.renames, &mut self.unresolved);
+    }
+}
+
+impl MacroScope {
+    /// Renames the items of the scope declared with a name of `renames` and rewrites the
+    /// references to them in all items, e.g. for macros that mangle or version item names
+    ///
+    /// References are paths whose first segment, after `self::` and `super::`, is a renamed
+    /// name, paths starting with `crate::` or `::` are left alone. Names bound by `let`,
+    /// parameters, closures, `match` arms and `if let`, `while let` and `for` patterns shadow
+    /// renamed items in the expressions they are visible in. Tokens of macro invocations can't be
+    /// rewritten, the names found in them are reported as [unresolved](RenameReport::unresolved)
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use macro_scope::MacroScope;
+    /// # use quote::format_ident;
+    /// let mut scope: MacroScope = "mod scope { struct Foo; fn make() -> Foo { Foo } }"
+    ///     .parse()
+    ///     .unwrap();
+    ///
+    /// let renames = HashMap::from([(format_ident!("Foo"), format_ident!("FooV2"))]);
+    /// let report = scope.apply_renames(&renames);
+    /// assert!(report.is_complete());
+    /// assert!(!quote::quote!(#scope).to_string().contains("Foo "));
+    /// ```
+    pub fn apply_renames(&mut self, renames: &HashMap<Ident, Ident>) -> RenameReport {
+        let mut report = RenameReport::default();
+        let mut rewrite = Rewrite {
+            renames,
+            unresolved: Vec::new(),
+            bindings: Vec::new(),
+        };
+
+        self.drop_index();
+        for item in self.scanned_items() {
+            if lazy::parse(&item).is_err() {
+                if let Item::Verbatim(tokens) = &*item.borrow() {
+                    find_unresolved(tokens.clone(), renames, &mut rewrite.unresolved);
+                }
+                continue;
+            }
+
+            let mut item = item.borrow_mut();
+            if let Some(ident) = item_ident_mut(&mut item) {
+                if let Some(to) = renames.get(ident) {
+                    report.renamed.push(ident.clone());
+                    *ident = renamed(ident, to);
+                }
+            }
+            // lifted modules are empty, their content is visited as scanned items
+            rewrite.visit_item_mut(&mut item);
+        }
+
+        report.undeclared = renames
+            .keys()
+            .filter(|name| !report.renamed.contains(name))
+            .cloned()
+            .collect();
+        report.undeclared.sort_by_key(|name| name.to_string());
+        report.unresolved = rewrite.unresolved;
+        report
+    }
+}
@@ -0,0 +1,135 @@
+//! Opt-in reports of how much code every template generated
+//!
+//! Set `MACRO_SCOPE_REPORT` to `stderr` or to a file the reports are appended to
+
+use std::{fmt, fs::OpenOptions, io::Write};
+
+use proc_macro2::{TokenStream, TokenTree};
+
+use crate::{Generated, ItemKey, MacroScope};
+
+pub(crate) const REPORT_VAR: &str = "MACRO_SCOPE_REPORT";
+
+/// How many items a template matched and how much code it generated for them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarkReport {
+    pub mark: String,
+    pub items: usize,
+    /// The number of tokens generated for all items, including tests
+    pub tokens: usize,
+    /// The item the most tokens were generated for, with the number of tokens
+    pub largest: Option<(String, usize)>,
+}
+
+/// The size of the code generated by every registered template, see
+/// [MacroScope::expansion_report]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpansionReport {
+    /// The name of the scope module
+    pub scope: String,
+    /// One entry per template in registration order
+    pub marks: Vec<MarkReport>,
+}
+
+impl ExpansionReport {
+    /// Returns the number of tokens generated by all templates
+    pub fn tokens(&self) -> usize {
+        self.marks.iter().map(|mark| mark.tokens).sum()
+    }
+}
+
+impl fmt::Display for ExpansionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "macro_scope expansion of `{}`:", self.scope)?;
+        for mark in &self.marks {
+            write!(
+                f,
+                "  #[{}]: {} items, {} tokens",
+                mark.mark, mark.items, mark.tokens
+            )?;
+            if let Some((item, tokens)) = &mark.largest {
+                write!(f, ", largest {item} with {tokens} tokens")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "  total: {} tokens", self.tokens())
+    }
+}
+
+/// Counts the tokens of `tokens`, a group counts as its delimiters and its content
+fn count(tokens: &TokenStream) -> usize {
+    tokens
+        .clone()
+        .into_iter()
+        .map(|tree| match tree {
+            TokenTree::Group(group) => 2 + count(&group.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+impl MacroScope {
+    /// Summarizes how many items every registered template matched and how many tokens it
+    /// generated for them, so expansions that blow up in large scopes are easy to spot
+    ///
+    /// Templates that matched nothing are listed with zero items
+    pub fn expansion_report(&self, generated: &[Generated]) -> ExpansionReport {
+        let mut marks: Vec<MarkReport> = Vec::new();
+        for mark in self.marks() {
+            if !marks.iter().any(|m| m.mark == mark) {
+                marks.push(MarkReport {
+                    mark: mark.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        for generated in generated {
+            let Some(report) = marks.iter_mut().find(|m| m.mark == generated.template) else {
+                continue;
+            };
+            let tokens = count(&generated.tokens) + count(&generated.tests);
+            report.items += 1;
+            report.tokens += tokens;
+            if report
+                .largest
+                .as_ref()
+                .is_none_or(|(_, largest)| tokens > *largest)
+            {
+                let item = ItemKey::of(&generated.item.item.borrow()).to_string();
+                report.largest = Some((item, tokens));
+            }
+        }
+
+        ExpansionReport {
+            scope: self.name(),
+            marks,
+        }
+    }
+
+    /// Writes the report of an expansion if `MACRO_SCOPE_REPORT` is set
+    ///
+    /// Like dumping, reporting never fails the expansion
+    pub(crate) fn write_report(&self, generated: &[Generated]) {
+        let Some(target) = std::env::var_os(REPORT_VAR) else {
+            return;
+        };
+        let report = self.expansion_report(generated);
+
+        if target == "stderr" {
+            eprintln!("{report}");
+            return;
+        }
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&target)
+            .and_then(|mut file| writeln!(file, "{report}"));
+        if let Err(e) = written {
+            eprintln!(
+                "macro_scope: failed to write {}: {e}",
+                std::path::Path::new(&target).display()
+            );
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Declared arguments of marks, validated for every occurrence of the mark
+
+use std::collections::HashMap;
+
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Attribute, Expr, Item};
+
+use crate::{diagnostics, Diagnostics, MacroScope, MetaTree, SharedMarkedItem};
+
+/// The type of a mark argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    /// A string literal, `name = "users"`
+    Str,
+    /// An integer literal, `order = 1`
+    Int,
+    /// A boolean literal or a bare flag, `skip` or `skip = false`
+    Bool,
+    /// A path, `handler = crate::handle`
+    Path,
+}
+
+impl ArgType {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Str => "a string",
+            Self::Int => "an integer",
+            Self::Bool => "a boolean",
+            Self::Path => "a path",
+        }
+    }
+}
+
+/// The value of a mark argument checked against its [ArgType]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Path(syn::Path),
+}
+
+impl ArgValue {
+    fn parse(tree: &MetaTree, ty: ArgType) -> syn::Result<Self> {
+        let error = || syn::Error::new(tree.span(), format!("expected {}", ty.name()));
+
+        match ty {
+            ArgType::Str => tree.expect_str().map(Self::Str),
+            ArgType::Int => tree.expect_int().map(Self::Int),
+            ArgType::Bool => tree.expect_bool().map(Self::Bool),
+            ArgType::Path => match tree {
+                MetaTree::NameValue(_, value) => match &**value {
+                    Expr::Path(path) if path.qself.is_none() => Ok(Self::Path(path.path.clone())),
+                    _ => Err(error()),
+                },
+                _ => Err(error()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Key {
+    name: String,
+    ty: ArgType,
+    default: Option<ArgValue>,
+}
+
+/// Declares the arguments a mark accepts, e.g. for `#[route(path = "/users", method = "GET")]`
+///
+/// ```
+/// # use macro_scope::{schema::{ArgType, Schema}, MacroScope};
+/// let schema = Schema::new()
+///     .required("path", ArgType::Str)
+///     .optional("method", ArgType::Str, "GET");
+///
+/// let scope: MacroScope = r#"mod scope { #[route(path = "/users")] fn users() {} }"#
+///     .parse()
+///     .unwrap();
+/// let routes = scope.get_marked_with_args("route", &schema).unwrap();
+/// assert_eq!(routes[0].1.str("method"), Some("GET"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    keys: Vec<Key>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares an argument every occurrence of the mark has to set
+    pub fn required(mut self, name: &str, ty: ArgType) -> Self {
+        self.keys.push(Key {
+            name: name.into(),
+            ty,
+            default: None,
+        });
+        self
+    }
+
+    /// Declares an argument that is `default` if it isn't set
+    pub fn optional(mut self, name: &str, ty: ArgType, default: impl Into<ArgValue>) -> Self {
+        self.keys.push(Key {
+            name: name.into(),
+            ty,
+            default: Some(default.into()),
+        });
+        self
+    }
+
+    /// Declares a boolean flag that is `false` if it isn't set
+    pub fn flag(self, name: &str) -> Self {
+        self.optional(name, ArgType::Bool, false)
+    }
+
+    /// Checks the arguments of `attr` against the schema, all violations are reported together
+    pub fn parse(&self, attr: &Attribute) -> syn::Result<Args> {
+        let tree = MetaTree::from_attribute(attr)?;
+        let mut diagnostics = Diagnostics::new();
+        let mut values = HashMap::new();
+        let mut seen = Vec::new();
+        let names: Vec<_> = self.keys.iter().map(|key| key.name.as_str()).collect();
+
+        if let MetaTree::NameValue(..) = tree {
+            return Err(syn::Error::new(
+                attr.span(),
+                "expected arguments like `#[mark(key = value)]`",
+            ));
+        }
+
+        for arg in tree.children() {
+            let name = arg.name().unwrap_or_default();
+            let Some(key) = self.keys.iter().find(|key| key.name == name) else {
+                let message = match diagnostics::suggest(&name, &names) {
+                    Some(key) => format!("unknown argument `{name}`, did you mean `{key}`?"),
+                    None => format!("unknown argument `{name}`"),
+                };
+                diagnostics.emit(arg.span(), message);
+                continue;
+            };
+
+            if seen.contains(&key.name.as_str()) {
+                diagnostics.emit(arg.span(), format!("duplicate argument `{name}`"));
+                continue;
+            }
+            seen.push(&key.name);
+            if let Some(value) = diagnostics.check(ArgValue::parse(arg, key.ty)) {
+                values.insert(key.name.clone(), value);
+            }
+        }
+
+        for key in &self.keys {
+            if seen.contains(&key.name.as_str()) {
+                continue;
+            }
+            match &key.default {
+                Some(default) => {
+                    values.insert(key.name.clone(), default.clone());
+                }
+                None => diagnostics.emit(
+                    attr.span(),
+                    format!("missing required argument `{}`", key.name),
+                ),
+            }
+        }
+
+        diagnostics.finish()?;
+        Ok(Args {
+            values,
+            span: attr.span(),
+        })
+    }
+}
+
+impl From<&str> for ArgValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.into())
+    }
+}
+
+impl From<i64> for ArgValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<syn::Path> for ArgValue {
+    fn from(value: syn::Path) -> Self {
+        Self::Path(value)
+    }
+}
+
+impl From<bool> for ArgValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+/// The validated arguments of a mark, every declared argument is present
+#[derive(Debug, Clone)]
+pub struct Args {
+    values: HashMap<String, ArgValue>,
+    /// The span of the mark, for errors about the combination of arguments
+    pub span: Span,
+}
+
+impl Args {
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.values.get(name)
+    }
+
+    pub fn str(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            ArgValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.get(name)? {
+            ArgValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            ArgValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> Option<&syn::Path> {
+        match self.get(name)? {
+            ArgValue::Path(p) => Some(p),
+            _ => None,
+        }
+    }
+}
+
+impl MacroScope {
+    /// Returns all items marked with `mark` in source order together with their arguments
+    /// validated against `schema`, violations of all items are reported together
+    pub fn get_marked_with_args(
+        &self,
+        mark: &str,
+        schema: &Schema,
+    ) -> syn::Result<Vec<(SharedMarkedItem<Item>, Args)>> {
+        let mut diagnostics = Diagnostics::new();
+        let mut marked = Vec::new();
+
+        for item in self.get_marked(mark) {
+            if let Some(args) = diagnostics.check(schema.parse(&item.mark)) {
+                marked.push((item, args));
+            }
+        }
+
+        diagnostics.finish()?;
+        Ok(marked)
+    }
+}
@@ -0,0 +1,162 @@
+//! Selectors that let end users choose items from the arguments of the scope macro
+//!
+//! A selector string is a comma separated list of alternatives, an item is selected if it matches
+//! any of them. Every alternative is an item kind or `*` for any kind, followed by `:pub` to only
+//! match public items and by predicates in brackets, e.g. `struct:pub, fn[mark=system]`
+//!
+//! Supported predicates are `mark=<mark>` and `name=<ident>`, kinds with spaces in their name
+//! are written with underscores like `extern_crate`
+
+use std::{cell::RefCell, rc::Rc};
+
+use proc_macro2::Span;
+use syn::{Item, Visibility};
+
+use crate::{has_mark, item_ident, item_vis, lazy, ItemKind, MacroScope};
+
+/// A parsed selector string, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    alternatives: Vec<Alternative>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Alternative {
+    kind: Option<ItemKind>,
+    public: bool,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Predicate {
+    Mark(String),
+    Name(String),
+}
+
+/// Splits at commas outside of brackets
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&s[start..]);
+
+    parts
+}
+
+impl Selector {
+    /// Parses a selector string, errors are reported at `span`, usually the span of the string
+    /// literal the selector was written in
+    pub fn parse(selector: &str, span: Span) -> syn::Result<Self> {
+        let error = |message: String| syn::Error::new(span, message);
+        let mut alternatives = Vec::new();
+
+        for part in split_top_level(selector) {
+            let part = part.trim();
+            if part.is_empty() {
+                return Err(error(format!("empty selector in `{selector}`")));
+            }
+
+            let (head, predicates) = match part.split_once('[') {
+                Some((head, rest)) => match rest.strip_suffix(']') {
+                    Some(predicates) => (head, predicates),
+                    None => return Err(error(format!("missing `]` in `{part}`"))),
+                },
+                None => (part, ""),
+            };
+
+            let mut head = head.split(':').map(str::trim);
+            let kind = match head.next().unwrap_or_default() {
+                "*" => None,
+                kind => match ItemKind::from_name(&kind.replace('_', " ")) {
+                    Some(kind) => Some(kind),
+                    None => return Err(error(format!("unknown item kind `{kind}`"))),
+                },
+            };
+
+            let mut public = false;
+            for modifier in head {
+                match modifier {
+                    "pub" => public = true,
+                    _ => return Err(error(format!("unknown modifier `:{modifier}`"))),
+                }
+            }
+
+            let predicates = predicates
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(|predicate| match predicate.split_once('=') {
+                    Some(("mark", mark)) => Ok(Predicate::Mark(mark.trim().into())),
+                    Some(("name", name)) => Ok(Predicate::Name(name.trim().into())),
+                    _ => Err(error(format!(
+                        "unknown predicate `{predicate}`, expected `mark=..` or `name=..`"
+                    ))),
+                })
+                .collect::<syn::Result<_>>()?;
+
+            alternatives.push(Alternative {
+                kind,
+                public,
+                predicates,
+            });
+        }
+
+        Ok(Self { alternatives })
+    }
+}
+
+/// Returns `true` if the item is declared `pub`, restricted visibilities don't count
+fn is_public(item: &Item) -> bool {
+    matches!(item_vis(item), Some(Visibility::Public(_)))
+}
+
+impl MacroScope {
+    /// Returns the items matching `selector` in source order, without touching their marks
+    ///
+    /// Parses all items of a lazily parsed scope
+    pub fn select(&self, selector: &Selector) -> Vec<Rc<RefCell<Item>>> {
+        self.scanned_items()
+            .into_iter()
+            .filter(|item| {
+                // unparsable items stay verbatim and are matched as such
+                let _ = lazy::parse(item);
+                selector.alternatives.iter().any(|alt| {
+                    let matches = {
+                        let item = item.borrow();
+                        alt.kind.is_none_or(|kind| ItemKind::of(&item) == kind)
+                            && (!alt.public || is_public(&item))
+                    };
+
+                    matches
+                        && alt.predicates.iter().all(|predicate| match predicate {
+                            Predicate::Mark(mark) => has_mark(item, mark, &self.config),
+                            Predicate::Name(name) => {
+                                item_ident(&item.borrow()).is_some_and(|ident| ident == name)
+                            }
+                        })
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the items chosen by [ScopeConfig::select](crate::ScopeConfig::select), or all
+    /// scanned items if no selector was given
+    pub fn selected(&self) -> Vec<Rc<RefCell<Item>>> {
+        match &self.config.select {
+            Some(selector) => self.select(selector),
+            None => self.scanned_items(),
+        }
+    }
+}
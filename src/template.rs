@@ -0,0 +1,133 @@
+use std::{fmt, rc::Rc};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Item, ItemStruct};
+
+use crate::{MacroScope, MarkedItem, SharedMarkedItem};
+
+/// Generates code for a single [MarkedItem](crate::MarkedItem)
+///
+/// Implemented for every closure with a matching signature, so most templates can be written
+/// inline with [quote::quote]
+pub trait Template {
+    fn expand(&self, item: &SharedMarkedItem<Item>) -> syn::Result<TokenStream>;
+
+    /// Generates test items for the item like property or roundtrip tests, the tests of all
+    /// items are merged into a single `#[cfg(test)] mod tests`, see
+    /// [MacroScope::register_with_tests]
+    fn tests(&self, item: &SharedMarkedItem<Item>) -> syn::Result<TokenStream> {
+        let _ = item;
+        Ok(TokenStream::new())
+    }
+}
+
+impl<F> Template for F
+where
+    F: Fn(&SharedMarkedItem<Item>) -> syn::Result<TokenStream>,
+{
+    fn expand(&self, item: &SharedMarkedItem<Item>) -> syn::Result<TokenStream> {
+        self(item)
+    }
+}
+
+/// Handles every item carrying one mark, for macro crates that organize their marks into separate
+/// modules or plugins
+///
+/// Unlike a [Template] the handler owns its mark and receives a copy of the item, see
+/// [MacroScope::register_handler]
+pub trait MarkHandler {
+    fn mark(&self) -> &str;
+    fn handle(&self, item: MarkedItem<Item>) -> syn::Result<TokenStream>;
+
+    /// Same as [Template::tests]
+    fn tests(&self, item: MarkedItem<Item>) -> syn::Result<TokenStream> {
+        let _ = item;
+        Ok(TokenStream::new())
+    }
+}
+
+/// Runs a [MarkHandler] as a [Template]
+struct Handler(Box<dyn MarkHandler>);
+
+impl Template for Handler {
+    fn expand(&self, item: &SharedMarkedItem<Item>) -> syn::Result<TokenStream> {
+        self.0.handle(item.to_owned_item())
+    }
+
+    fn tests(&self, item: &SharedMarkedItem<Item>) -> syn::Result<TokenStream> {
+        self.0.tests(item.to_owned_item())
+    }
+}
+
+/// A [Template] together with a template generating its tests
+struct WithTests<T, U>(T, U);
+
+impl<T: Template, U: Template> Template for WithTests<T, U> {
+    fn expand(&self, item: &SharedMarkedItem<Item>) -> syn::Result<TokenStream> {
+        self.0.expand(item)
+    }
+
+    fn tests(&self, item: &SharedMarkedItem<Item>) -> syn::Result<TokenStream> {
+        self.1.expand(item)
+    }
+}
+
+/// The templates registered on a scope, in registration order
+#[derive(Clone, Default)]
+pub(crate) struct Templates(pub Vec<(String, Rc<dyn Template>)>);
+
+impl fmt::Debug for Templates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|(mark, _)| mark))
+            .finish()
+    }
+}
+
+impl MacroScope {
+    /// Registers a [MarkHandler] for its mark, it runs during [MacroScope::expand] in
+    /// registration order together with the registered templates
+    pub fn register_handler(&mut self, handler: Box<dyn MarkHandler>) {
+        let mark = handler.mark().to_string();
+        self.register(mark, Handler(handler));
+    }
+
+    /// Registers a [Template] for `mark` like [MacroScope::register] and a second template that
+    /// generates test items for every marked item
+    ///
+    /// The test items of all marked items are merged into one `#[cfg(test)] mod tests` at the
+    /// end of the generated code that imports everything from the scope. If the scope already
+    /// has an inline `mod tests`, the test items are appended to it instead
+    pub fn register_with_tests(
+        &mut self,
+        mark: impl Into<String>,
+        template: impl Template + 'static,
+        tests: impl Template + 'static,
+    ) {
+        self.register(mark, WithTests(template, tests));
+    }
+
+    /// Registers a template implementing `trait_path` for every struct marked with `mark`
+    ///
+    /// `body` only returns the items of the impl block, the impl header including the split
+    /// generics of the struct is generated. Marks on anything but a struct are reported as errors
+    pub fn impl_for_marked<F>(&mut self, mark: impl Into<String>, trait_path: syn::Path, body: F)
+    where
+        F: Fn(&ItemStruct) -> syn::Result<TokenStream> + 'static,
+    {
+        self.register(mark, move |marked: &SharedMarkedItem<Item>| {
+            let marked: MarkedItem<ItemStruct> = marked.to_owned_item().try_into()?;
+            let item = &marked.item;
+            let ident = &item.ident;
+            let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+            let body = body(item)?;
+
+            Ok(quote! {
+                impl #impl_generics #trait_path for #ident #ty_generics #where_clause {
+                    #body
+                }
+            })
+        });
+    }
+}
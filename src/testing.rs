@@ -0,0 +1,72 @@
+//! Merging the test items generated by templates into a single test module
+
+use std::{cell::RefCell, rc::Rc};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Item, ItemMod};
+
+use crate::{lazy, Generated, MacroScope};
+
+impl MacroScope {
+    /// Returns the `#[cfg(test)] mod tests` with the test items of all `generated` code, see
+    /// [MacroScope::register_with_tests]
+    ///
+    /// If the scope has an inline `mod tests`, the test items are appended to it and nothing is
+    /// returned
+    pub(crate) fn merge_tests(&mut self, generated: &[Generated]) -> syn::Result<TokenStream> {
+        let tests: TokenStream = generated.iter().map(|g| g.tests.clone()).collect();
+        if tests.is_empty() {
+            return Ok(tests);
+        }
+
+        // modules of lazy scopes are still unparsed
+        for item in &self.items {
+            let module =
+                matches!(&*item.borrow(), Item::Verbatim(tokens) if lazy::is_module(tokens));
+            if module {
+                let _ = lazy::parse(item);
+            }
+        }
+
+        let existing = self.items.iter().find(|item| {
+            matches!(
+                &*item.borrow(),
+                Item::Mod(ItemMod { ident, content: Some(_), .. }) if ident == "tests"
+            )
+        });
+        let Some(module) = existing.cloned() else {
+            return Ok(quote! {
+                #[cfg(test)]
+                mod tests {
+                    #[allow(unused_imports)]
+                    use super::*;
+                    #tests
+                }
+            });
+        };
+
+        let items = syn::parse2::<syn::File>(tests)?.items;
+        self.drop_index();
+        match self
+            .modules
+            .iter_mut()
+            .find(|(m, _)| Rc::ptr_eq(m, &module))
+        {
+            Some((_, children)) => {
+                children.extend(items.into_iter().map(|item| Rc::new(RefCell::new(item))))
+            }
+            None => {
+                if let Item::Mod(ItemMod {
+                    content: Some((_, content)),
+                    ..
+                }) = &mut *module.borrow_mut()
+                {
+                    content.extend(items);
+                }
+            }
+        }
+
+        Ok(TokenStream::new())
+    }
+}
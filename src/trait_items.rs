@@ -0,0 +1,64 @@
+//! The associated items of marked traits, grouped by kind
+
+use syn::{Item, ItemTrait, TraitItem, TraitItemConst, TraitItemFn, TraitItemType};
+
+use crate::{MarkedItem, SharedMarkedItem};
+
+/// The associated items of a trait by kind, each in declaration order, see
+/// [MarkedItem::trait_items]
+///
+/// Macro invocations and items syn can't parse are not collected
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraitItems {
+    pub types: Vec<TraitItemType>,
+    pub consts: Vec<TraitItemConst>,
+    pub methods: Vec<TraitItemFn>,
+}
+
+impl TraitItems {
+    pub fn of(item: &ItemTrait) -> Self {
+        let mut items = Self::default();
+
+        for trait_item in &item.items {
+            match trait_item {
+                TraitItem::Type(ty) => items.types.push(ty.clone()),
+                TraitItem::Const(c) => items.consts.push(c.clone()),
+                TraitItem::Fn(f) => items.methods.push(f.clone()),
+                _ => (),
+            }
+        }
+
+        items
+    }
+
+    /// Returns the methods without a default body, the ones every impl has to provide
+    pub fn required_methods(&self) -> impl Iterator<Item = &TraitItemFn> {
+        self.methods.iter().filter(|f| f.default.is_none())
+    }
+
+    /// Returns the methods taking `self` in some form
+    pub fn receiver_methods(&self) -> impl Iterator<Item = &TraitItemFn> {
+        self.methods.iter().filter(|f| f.sig.receiver().is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.consts.is_empty() && self.methods.is_empty()
+    }
+}
+
+impl MarkedItem<ItemTrait> {
+    /// Returns the associated items of the marked trait grouped by kind
+    pub fn trait_items(&self) -> TraitItems {
+        TraitItems::of(&self.item)
+    }
+}
+
+impl SharedMarkedItem<Item> {
+    /// Returns the associated items grouped by kind if the marked item is a trait
+    pub fn trait_items(&self) -> Option<TraitItems> {
+        match &*self.item.borrow() {
+            Item::Trait(item) => Some(TraitItems::of(item)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! All-or-nothing mutation of a scope
+
+use std::{cell::RefCell, rc::Rc};
+
+use syn::Item;
+
+use crate::{MacroScope, Mutation};
+
+/// The state of a scope before a transaction
+struct Snapshot {
+    /// The scope with its settings, templates, metadata and handles to its items
+    scope: MacroScope,
+    /// The content of every scanned item, the handles are shared with the running scope
+    contents: Vec<(Rc<RefCell<Item>>, Item)>,
+}
+
+impl Snapshot {
+    fn take(scope: &MacroScope) -> Self {
+        Self {
+            scope: scope.clone(),
+            contents: scope
+                .scanned_items()
+                .into_iter()
+                .map(|item| {
+                    let content = item.borrow().clone();
+                    (item, content)
+                })
+                .collect(),
+        }
+    }
+
+    /// Puts the content back into the original handles, so handles taken before the transaction
+    /// stay valid, and restores everything else except the journal entries
+    fn restore(self, scope: &mut MacroScope) {
+        for (item, content) in self.contents {
+            *item.borrow_mut() = content;
+        }
+        let entries = std::mem::take(&mut scope.journal.borrow_mut().entries);
+        *scope = self.scope;
+        scope.journal.borrow_mut().entries = entries;
+        scope.drop_index();
+    }
+}
+
+impl MacroScope {
+    /// Runs `f` on the scope and undoes all changes to its items if it returns an error
+    ///
+    /// Covers removed marks, replaced or mutated items, pushed items and changes to the module
+    /// shell, so a validation failing halfway through a pass doesn't leave the scope half
+    /// mutated when the error is emitted. Everything else is restored as well, e.g. the config,
+    /// the prelude, registered templates, the [gensym](MacroScope::gensym) counter, the current
+    /// pass and [metadata](MacroScope::set_metadata). Only the [journal](MacroScope::journal)
+    /// keeps the mutations of the transaction, followed by [Mutation::RolledBack]
+    ///
+    /// ```
+    /// # use macro_scope::MacroScope;
+    /// let mut scope: MacroScope = "mod scope { #[system] fn a() {} }".parse().unwrap();
+    /// let result = scope.transaction(|scope| {
+    ///     scope.get_marked("system");
+    ///     scope.push_item(syn::parse_quote!(struct B;));
+    ///     Err::<(), _>(syn::Error::new(proc_macro2::Span::call_site(), "invalid"))
+    /// });
+    ///
+    /// assert!(result.is_err());
+    /// assert_eq!(scope.items.len(), 1);
+    /// assert_eq!(scope.get_marked("system").len(), 1);
+    /// ```
+    pub fn transaction<R>(
+        &mut self,
+        f: impl FnOnce(&mut MacroScope) -> syn::Result<R>,
+    ) -> syn::Result<R> {
+        let snapshot = Snapshot::take(self);
+
+        f(self).inspect_err(|_| {
+            snapshot.restore(self);
+            self.record(Mutation::RolledBack);
+        })
+    }
+}
@@ -0,0 +1,78 @@
+//! The `use` declarations of a scope, reused by generated modules
+
+use std::collections::HashSet;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Ident, Item, ItemUse, UsePath, UseTree, Visibility};
+
+use crate::MacroScope;
+
+/// Rewrites a use tree written in the scope so it resolves the same from a module inside of it
+fn rewrite(tree: &UseTree, declared: &HashSet<Ident>) -> UseTree {
+    let super_path = |tree: UseTree| {
+        UseTree::Path(UsePath {
+            ident: Ident::new("super", Span::call_site()),
+            colon2_token: Default::default(),
+            tree: Box::new(tree),
+        })
+    };
+
+    match tree {
+        UseTree::Path(path) if path.ident == "self" => UseTree::Path(UsePath {
+            ident: Ident::new("super", path.ident.span()),
+            ..path.clone()
+        }),
+        UseTree::Path(path) if path.ident == "super" => super_path(tree.clone()),
+        UseTree::Path(path) if declared.contains(&path.ident) => super_path(tree.clone()),
+        UseTree::Name(name) if declared.contains(&name.ident) => super_path(tree.clone()),
+        UseTree::Rename(rename) if declared.contains(&rename.ident) => super_path(tree.clone()),
+        UseTree::Group(group) => {
+            let mut group = group.clone();
+            for tree in &mut group.items {
+                *tree = rewrite(tree, declared);
+            }
+            UseTree::Group(group)
+        }
+        tree => tree.clone(),
+    }
+}
+
+impl MacroScope {
+    /// Returns the `use` declarations directly in the scope, in source order
+    ///
+    /// Declarations of lazily parsed scopes are parsed without touching the items
+    pub fn use_items(&self) -> Vec<ItemUse> {
+        self.items
+            .iter()
+            .filter_map(|item| match &*item.borrow() {
+                Item::Use(item) => Some(item.clone()),
+                Item::Verbatim(tokens) => syn::parse2(tokens.clone()).ok(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the [use declarations](MacroScope::use_items) of the scope rewritten for a module
+    /// generated inside of the scope, so generated code can use the same imports
+    ///
+    /// `self::` and `super::` paths and paths starting with an item of the scope get another
+    /// `super::`, paths starting with `crate::`, `::` or an external crate are kept. The
+    /// declarations become private and are allowed to be unused
+    pub fn child_uses(&self) -> TokenStream {
+        let declared: HashSet<Ident> = self.declared_idents().into_iter().collect();
+
+        self.use_items()
+            .into_iter()
+            .map(|mut item| {
+                item.vis = Visibility::Inherited;
+                item.attrs.retain(|attr| attr.path().is_ident("cfg"));
+                item.tree = rewrite(&item.tree, &declared);
+                quote! {
+                    #[allow(unused_imports)]
+                    #item
+                }
+            })
+            .collect()
+    }
+}
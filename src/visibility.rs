@@ -0,0 +1,153 @@
+//! Checks that generated code only references items it can see
+
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::{Span, TokenStream};
+use syn::{visit::Visit, Item, ItemMod, Visibility};
+
+use crate::{item_ident, item_vis, lazy, Diagnostics, MacroScope};
+
+/// Every named item of a scope by its path relative to the scope
+type Declared = HashMap<Vec<String>, (Visibility, Span)>;
+
+fn declare(items: &[Item], prefix: &mut Vec<String>, declared: &mut Declared) {
+    for item in items {
+        let (Some(ident), Some(vis)) = (item_ident(item), item_vis(item)) else {
+            continue;
+        };
+        prefix.push(ident.to_string());
+        declared.insert(prefix.clone(), (vis.clone(), ident.span()));
+
+        if let Item::Mod(ItemMod {
+            content: Some((_, content)),
+            ..
+        }) = item
+        {
+            declare(content, prefix, declared);
+        }
+        prefix.pop();
+    }
+}
+
+/// Returns `true` if an item declared at `path` with `vis` can be named at the root of the scope
+fn reachable(path: &[String], vis: &Visibility) -> bool {
+    match vis {
+        Visibility::Inherited => path.len() == 1,
+        Visibility::Restricted(r) if r.path.is_ident("self") => path.len() == 1,
+        Visibility::Restricted(r) if r.path.is_ident("super") => path.len() <= 2,
+        _ => true,
+    }
+}
+
+/// Returns the segments of a path relative to the scope, [None] for paths that start outside of
+/// it like `crate::` or `::std`
+fn segments(path: &syn::Path) -> Option<Vec<String>> {
+    if path.leading_colon.is_some() {
+        return None;
+    }
+
+    let mut segments: Vec<_> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+    match segments.first().map(String::as_str) {
+        Some("crate" | "super" | "Self") => None,
+        Some("self") => {
+            segments.remove(0);
+            Some(segments)
+        }
+        _ => Some(segments),
+    }
+}
+
+/// Collects all paths below a node
+#[derive(Default)]
+struct Paths(Vec<syn::Path>);
+
+impl<'ast> Visit<'ast> for Paths {
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        self.0.push(path.clone());
+        syn::visit::visit_path(self, path);
+    }
+}
+
+impl MacroScope {
+    /// Reports references in `generated` that won't compile or leak private items once the code
+    /// is emitted at the root of the scope
+    ///
+    /// Paths into nested modules must only go through items that are visible at the root, e.g.
+    /// `inner::Secret` fails if `Secret` isn't at least `pub(super)`. Generated `pub fn`s must not
+    /// mention private items of the scope in their signature. The errors point at the declaration
+    /// of the private item. Runs on the output of every template in [ScopeConfig::strict] mode
+    ///
+    /// Parses all items of a lazily parsed scope
+    ///
+    /// [ScopeConfig::strict]: crate::ScopeConfig::strict
+    pub fn check_visibility(&self, generated: &TokenStream) -> syn::Result<()> {
+        let file: syn::File = syn::parse2(generated.clone())?;
+
+        for item in &self.scanned_items() {
+            let _ = lazy::parse(item);
+        }
+        let items: Vec<_> = self.items.iter().map(|i| self.emit_item(i)).collect();
+        let mut declared = Declared::new();
+        declare(&items, &mut Vec::new(), &mut declared);
+
+        let mut diagnostics = Diagnostics::new();
+        let mut reported = HashSet::new();
+
+        let mut paths = Paths::default();
+        paths.visit_file(&file);
+        for path in &paths.0 {
+            let Some(segments) = segments(path) else {
+                continue;
+            };
+            for len in 2..=segments.len() {
+                let prefix = &segments[..len];
+                let Some((vis, span)) = declared.get(prefix) else {
+                    break;
+                };
+                if !reachable(prefix, vis) {
+                    let path = prefix.join("::");
+                    if reported.insert(path.clone()) {
+                        diagnostics.emit(
+                            *span,
+                            format!("`{path}` is private, but referenced by generated code"),
+                        );
+                    }
+                    break;
+                }
+            }
+        }
+
+        for item in &file.items {
+            let Item::Fn(f) = item else {
+                continue;
+            };
+            if !matches!(f.vis, Visibility::Public(_)) {
+                continue;
+            }
+
+            let generics: HashSet<_> = f.sig.generics.type_params().map(|p| &p.ident).collect();
+            let mut paths = Paths::default();
+            paths.visit_signature(&f.sig);
+            for path in &paths.0 {
+                if path.get_ident().is_some_and(|i| generics.contains(i)) {
+                    continue;
+                }
+                let Some(segments) = segments(path) else {
+                    continue;
+                };
+                if let Some((Visibility::Inherited, span)) = declared.get(&segments) {
+                    let message = format!(
+                        "`{}` is private, but exposed by the generated `pub fn {}`",
+                        segments.join("::"),
+                        f.sig.ident
+                    );
+                    if reported.insert(message.clone()) {
+                        diagnostics.emit(*span, message);
+                    }
+                }
+            }
+        }
+
+        diagnostics.finish()
+    }
+}
@@ -0,0 +1,74 @@
+//! Proc macros for `tests/adapters.rs`, proc-macro-error can only report errors inside a macro
+//! invocation
+
+use macro_scope::Diagnostics;
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, LitStr,
+};
+
+/// `report!(emit "a" "b")`, the mode followed by the messages to record
+struct Report {
+    mode: Ident,
+    messages: Vec<LitStr>,
+}
+
+impl Parse for Report {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mode = input.parse()?;
+        let mut messages = Vec::new();
+        while !input.is_empty() {
+            messages.push(input.parse()?);
+        }
+        Ok(Self { mode, messages })
+    }
+}
+
+/// Records every message as an error at its literal and reports them through proc-macro-error
+/// with `emit_proc_macro_error` or `abort_proc_macro_error`
+///
+/// A further error is emitted after the adapter, so aborting can be told apart from emitting.
+/// Expands to an array of the reported messages, so the test can look at them
+#[proc_macro]
+pub fn report(input: TokenStream) -> TokenStream {
+    let output = proc_macro_error::entry_point(
+        || {
+            let report: Report = syn::parse(input).unwrap();
+            let diagnostics: Diagnostics = report
+                .messages
+                .iter()
+                .map(|lit| syn::Error::new(lit.span(), lit.value()))
+                .collect();
+
+            match report.mode.to_string().as_str() {
+                "emit" => diagnostics.emit_proc_macro_error(),
+                "abort" => diagnostics.abort_proc_macro_error(),
+                mode => panic!("unknown mode `{mode}`"),
+            }
+            proc_macro_error::emit_call_site_error!("after");
+            TokenStream::new()
+        },
+        false,
+    );
+
+    let errors = messages(output.into());
+    quote!([#(#errors),*]).into()
+}
+
+/// Returns the messages of the `compile_error!` invocations in `output`
+fn messages(output: TokenStream2) -> Vec<String> {
+    let mut messages = Vec::new();
+    for tt in output {
+        match tt {
+            TokenTree::Group(group) => match syn::parse2::<LitStr>(group.stream()) {
+                Ok(lit) => messages.push(lit.value()),
+                Err(_) => messages.extend(self::messages(group.stream())),
+            },
+            _ => (),
+        }
+    }
+    messages
+}
@@ -0,0 +1,50 @@
+//! Reporting `Diagnostics` through other diagnostic crates, run these tests with
+//! `cargo test --all-features`
+
+#[cfg(feature = "manyhow")]
+#[test]
+fn manyhow_adapter() {
+    use macro_scope::Diagnostics;
+    use manyhow::ToTokensError;
+    use proc_macro2::Span;
+
+    let diagnostics = || -> Diagnostics {
+        ["first", "second"]
+            .into_iter()
+            .map(|message| syn::Error::new(Span::call_site(), message))
+            .collect()
+    };
+
+    let mut emitter = manyhow::Emitter::new();
+    diagnostics().emit_to_manyhow(&mut emitter);
+    let error = emitter.into_result().unwrap_err();
+    assert_eq!(
+        error.into_token_stream().to_string(),
+        r#":: core :: compile_error ! { "first" } :: core :: compile_error ! { "second" }"#
+    );
+
+    let error = diagnostics().finish_manyhow().unwrap_err();
+    assert_eq!(
+        error
+            .into_token_stream()
+            .to_string()
+            .matches("compile_error")
+            .count(),
+        2
+    );
+    assert!(Diagnostics::new().finish_manyhow().is_ok());
+}
+
+#[cfg(feature = "proc-macro-error")]
+#[test]
+fn proc_macro_error_adapter() {
+    use adapter_macros::report;
+
+    // emitting keeps going, the error after the adapter is reported too
+    assert_eq!(report!(emit "first" "second"), ["first", "second", "after"]);
+    // aborting stops at the adapter
+    assert_eq!(report!(abort "first" "second"), ["first", "second"]);
+    // without recorded errors aborting returns normally
+    assert_eq!(report!(emit), ["after"]);
+    assert_eq!(report!(abort), ["after"]);
+}
@@ -0,0 +1,20 @@
+use std::fs;
+
+#[test]
+fn scan_directory() {
+    let dir = std::env::temp_dir().join(format!("macro_scope_scan_{}", std::process::id()));
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("a.rs"), "#[system] fn a() {} struct A;").unwrap();
+    fs::write(dir.join("nested/b.rs"), "#[system] struct B;").unwrap();
+    fs::write(dir.join("notes.txt"), "#[system] not rust").unwrap();
+
+    let scope = macro_scope::build::scan(&dir).unwrap();
+    assert_eq!(scope.items.len(), 3);
+    assert_eq!(scope.get_marked("system").len(), 2);
+
+    fs::write(dir.join("broken.rs"), "struct").unwrap();
+    let error = macro_scope::build::scan(&dir).unwrap_err().to_string();
+    assert!(error.contains("broken.rs:1:"), "{error}");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
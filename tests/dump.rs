@@ -0,0 +1,47 @@
+//! Dumping is configured through the environment of the process, so it is tested in its own test
+//! binary
+
+use std::fs;
+
+use macro_scope::{MacroScope, SharedMarkedItem};
+use syn::Item;
+
+#[test]
+fn dump_expansion() {
+    let dir = std::env::temp_dir().join(format!("macro_scope_dump_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    std::env::set_var("MACRO_SCOPE_DUMP", &dir);
+    // cargo sets the crate name while compiling the crate that invokes the macro
+    std::env::set_var("CARGO_CRATE_NAME", "consumer");
+
+    let mut scope: MacroScope = "mod systems { #[system] fn a() {} }".parse().unwrap();
+    scope.journaling = true;
+    scope.register("system", |_: &SharedMarkedItem<Item>| {
+        Ok(quote::quote!(
+            const SYSTEM: () = ();
+        ))
+    });
+    scope.expand().unwrap();
+    std::env::remove_var("CARGO_CRATE_NAME");
+    let mut scope: MacroScope = "mod systems {}".parse().unwrap();
+    scope.expand().unwrap();
+    std::env::remove_var("MACRO_SCOPE_DUMP");
+
+    let read = |file: &str| fs::read_to_string(dir.join(file)).unwrap();
+    assert_eq!(
+        read("consumer.systems.0.pre.rs"),
+        "mod systems {\n    #[system]\n    fn a() {}\n}\n"
+    );
+    assert_eq!(
+        read("consumer.systems.0.post.rs"),
+        "mod systems {\n    fn a() {}\n    const SYSTEM: () = ();\n}\n"
+    );
+    assert_eq!(
+        read("consumer.systems.0.journal.txt"),
+        "removed `#[system]` from fn a\n"
+    );
+    // without a crate name the files are only named after the scope
+    assert_eq!(read("systems.1.pre.rs"), "mod systems {}\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
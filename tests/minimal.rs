@@ -0,0 +1,78 @@
+//! The minimal scope also builds without the default `full` feature, run these tests with
+//! `cargo test --no-default-features` too
+
+use macro_scope::{MatchMode, MinimalItem, MinimalScope};
+use quote::ToTokens;
+
+#[test]
+fn minimal_items() {
+    let scope: MinimalScope = "#[outer] pub mod scope {
+        #![inner]
+        #[system] pub struct A { x: u8 }
+        #[system] enum B { X, Y(u8) }
+        #[system] pub(crate) const unsafe extern \"C\" fn c() -> [u8; { 1 }] { [0] }
+        #[system] impl A { fn d() {} }
+        #[system] const E: u8 = { 1 } + 1;
+        #[system] struct Invalid { x }
+    }"
+    .parse()
+    .unwrap();
+
+    let kinds: Vec<_> = scope
+        .items
+        .iter()
+        .map(|item| match &*item.borrow() {
+            MinimalItem::Struct(item) => format!("struct {}", item.ident),
+            MinimalItem::Enum(item) => format!("enum {}", item.ident),
+            MinimalItem::Fn(item) => format!("fn {}", item.ident),
+            MinimalItem::Other(tokens) => tokens.to_string(),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "struct A",
+            "enum B",
+            "fn c",
+            "# [system] impl A { fn d () { } }",
+            "# [system] const E : u8 = { 1 } + 1 ;",
+            "# [system] struct Invalid { x }",
+        ]
+    );
+
+    let marked = scope.get_marked("system");
+    let names: Vec<_> = marked
+        .iter()
+        .map(|m| (m.item.borrow().ident().unwrap().to_string(), m.position))
+        .collect();
+    assert_eq!(names, [("A".into(), 0), ("B".into(), 1), ("c".into(), 2)]);
+    // a second query finds nothing, the marks were removed
+    assert!(scope.get_marked("system").is_empty());
+
+    assert_eq!(
+        scope.to_token_stream().to_string(),
+        "# [outer] pub mod scope { # ! [inner] pub struct A { x : u8 } enum B { X , Y (u8) } \
+         pub (crate) const unsafe extern \"C\" fn c () -> [u8 ; { 1 }] { [0] } \
+         # [system] impl A { fn d () { } } # [system] const E : u8 = { 1 } + 1 ; \
+         # [system] struct Invalid { x } }"
+    );
+}
+
+#[test]
+fn minimal_match_mode() {
+    let mut scope: MinimalScope = "mod scope { #[render_system] fn a() {} #[system] fn b() {} }"
+        .parse()
+        .unwrap();
+    scope.match_mode = MatchMode::Exact;
+    assert_eq!(scope.get_marked("system").len(), 1);
+    // the default matches like `MacroScope`, by names containing the mark
+    scope.match_mode = MatchMode::default();
+    assert_eq!(scope.get_marked("system").len(), 1);
+    assert_eq!(scope.get_marked("system").len(), 0);
+
+    let error = "mod scope;".parse::<MinimalScope>().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "macro_scope requires an inline module body"
+    );
+}
@@ -0,0 +1,117 @@
+use macro_scope::{MacroScope, SharedMarkedItem};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Item;
+
+fn expand_unmarked(input: TokenStream) -> TokenStream {
+    let mut scope: MacroScope = syn::parse2(input).unwrap();
+    scope.register("unused_mark", |_: &SharedMarkedItem<Item>| {
+        Ok(quote!(
+            fn unused() {}
+        ))
+    });
+    scope.expand().unwrap()
+}
+
+fn assert_round_trip(input: TokenStream) {
+    assert_eq!(
+        expand_unmarked(input.clone()).to_string(),
+        input.to_string()
+    );
+}
+
+#[test]
+fn module_shell() {
+    assert_round_trip(quote! {
+        /// docs on the module
+        #[cfg(test)]
+        #[allow(dead_code)]
+        pub(crate) mod scope {
+            #![allow(unused)]
+        }
+    });
+}
+
+#[test]
+fn item_attributes_and_order() {
+    assert_round_trip(quote! {
+        mod scope {
+            use std::fmt;
+
+            /// A struct
+            #[derive(Debug, Clone)]
+            #[repr(C)]
+            pub struct A {
+                #[doc(hidden)]
+                pub x: u32,
+            }
+
+            #[non_exhaustive]
+            enum B { One, Two(u8) }
+
+            impl fmt::Display for A {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}", self.x)
+                }
+            }
+
+            #[inline]
+            fn c() {}
+
+            macro_rules! d { () => {} }
+
+            const E: u8 = 0;
+
+            mod nested {
+                #[unknown_attribute]
+                struct F;
+            }
+        }
+    });
+}
+
+#[test]
+fn braceless_module() {
+    assert_round_trip(quote! {
+        #[path = "other.rs"]
+        mod scope;
+    });
+}
+
+#[test]
+fn empty_module() {
+    assert_round_trip(quote! {
+        mod scope {}
+    });
+}
+
+#[test]
+fn missing_body() {
+    let config = |missing_body| macro_scope::ScopeConfig {
+        missing_body,
+        ..Default::default()
+    };
+
+    let error = MacroScope::parse_with_config(
+        quote::quote!(
+            mod scope;
+        ),
+        config(macro_scope::MissingBody::Error),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "macro_scope requires an inline module body"
+    );
+
+    let scope = MacroScope::parse_with_config(
+        quote::quote!(
+            mod scope;
+        ),
+        config(macro_scope::MissingBody::Warn),
+    )
+    .unwrap();
+    assert!(quote::ToTokens::to_token_stream(&scope)
+        .to_string()
+        .contains("deprecated"));
+}
@@ -0,0 +1,2560 @@
+use macro_scope::{MacroScope, ScopeConfig, SharedMarkedItem};
+use proc_macro2::TokenStream;
+use syn::Item;
+
+/// Template generating nothing, for tests that only look at the scanned items
+fn noop(_: &SharedMarkedItem<Item>) -> syn::Result<TokenStream> {
+    Ok(TokenStream::new())
+}
+
+/// Parses `source` with `config` once eagerly and once lazily
+fn parse_both(source: &str, config: ScopeConfig) -> [MacroScope; 2] {
+    [false, true].map(|lazy| {
+        let config = ScopeConfig {
+            lazy,
+            ..config.clone()
+        };
+        MacroScope::parse_with_config(source.parse().unwrap(), config).unwrap()
+    })
+}
+
+#[test]
+fn from_str() {
+    let scope: MacroScope = "mod scope { #[system] fn a() {} #[system] struct B; struct C; }"
+        .parse()
+        .unwrap();
+
+    let marked = scope.get_items_by_mark_prefix("system");
+    assert_eq!(marked["system"].len(), 2);
+    assert_eq!(scope.items.len(), 3);
+}
+
+#[test]
+fn from_str_error() {
+    assert!("struct NotAModule;".parse::<MacroScope>().is_err());
+}
+
+#[test]
+fn lazy_items_match_eager_items() {
+    let source = r#"
+        mod scope {
+            #![allow(unused)]
+            use std::{fmt, collections::HashMap};
+            extern crate alloc;
+            pub(crate) const A: [u8; 2] = { [1, 2] };
+            static B: Option<fn() -> u8> = None;
+            type C<T> = HashMap<T, Vec<T>>;
+            #[derive(Debug)]
+            pub struct D<const N: usize = { 3 }> { x: [u8; N] }
+            struct E(u8);
+            enum F { A = { 1 }, B }
+            trait G = Clone + Send;
+            const fn h() -> u8 { 1 }
+            unsafe impl<T> Send for D<T> where T: Fn() -> u8 {}
+            impl Foo<{ N }> { fn f() {} }
+            macro_rules! m { () => {} }
+            m!(x);
+            m! { y }
+            extern "C" { fn ffi(); }
+            mod nested { struct Inner; }
+        }
+    "#;
+
+    let eager: MacroScope = source.parse().unwrap();
+    let mut lazy = MacroScope::parse_lazy(source.parse().unwrap()).unwrap();
+    lazy.parse_items().unwrap();
+
+    let tokens = |scope: &MacroScope| -> Vec<String> {
+        scope
+            .items
+            .iter()
+            .map(|item| quote::ToTokens::to_token_stream(&*item.borrow()).to_string())
+            .collect()
+    };
+    assert_eq!(tokens(&lazy), tokens(&eager));
+}
+
+#[test]
+fn lazy_parses_marked_items() {
+    let source = "mod scope { #[system] fn a() {} struct B; }";
+    let scope = MacroScope::parse_lazy(source.parse().unwrap()).unwrap();
+
+    let marked = scope.get_items_by_mark_prefix("system");
+    assert_eq!(marked["system"].len(), 1);
+    assert!(matches!(*scope.items[0].borrow(), syn::Item::Fn(_)));
+    assert!(matches!(*scope.items[1].borrow(), syn::Item::Verbatim(_)));
+}
+
+#[test]
+fn config_from_args() {
+    use macro_scope::{ItemKind, MarkPolicy, MatchMode, ScopeConfig};
+
+    let config: ScopeConfig = syn::parse_quote!(
+        recursive,
+        match_mode = "exact",
+        scan = "struct, fn",
+        mark_policy = "keep"
+    );
+    assert!(config.recursive);
+    assert_eq!(config.match_mode, MatchMode::Exact);
+    assert!(config.scan_set.contains(ItemKind::Fn));
+    assert!(!config.scan_set.contains(ItemKind::Enum));
+    assert_eq!(config.mark_policy, MarkPolicy::Keep);
+
+    let error = syn::parse_str::<ScopeConfig>("recursiv").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unknown option `recursiv`, did you mean `recursive`?"
+    );
+}
+
+#[test]
+fn recursive_scan() {
+    use macro_scope::ScopeConfig;
+
+    let source = "mod scope { mod a { #[system] struct A; mod b { #[system] fn b() {} } } }";
+    let config = ScopeConfig {
+        recursive: true,
+        ..Default::default()
+    };
+    let scope = MacroScope::parse_with_config(source.parse().unwrap(), config).unwrap();
+
+    assert_eq!(scope.get_items_by_mark_prefix("system")["system"].len(), 2);
+    assert_eq!(
+        quote::ToTokens::to_token_stream(&scope).to_string(),
+        "mod scope { mod a { struct A ; mod b { fn b () { } } } }"
+    );
+}
+
+#[test]
+fn impl_for_marked() {
+    let mut scope: MacroScope = r#"mod scope {
+        #[component(cfg(test))] struct A<T: Clone> where T: Copy { t: T }
+        #[component] fn b() {}
+    }"#
+    .parse()
+    .unwrap();
+    scope.impl_for_marked("component", syn::parse_quote!(Component), |_| {
+        Ok(quote::quote!(
+            fn id() {}
+        ))
+    });
+
+    let error = scope.expand().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "`#[component]` can only be used on structs"
+    );
+
+    let mut scope: MacroScope =
+        r#"mod scope { #[component(cfg(test))] struct A<T: Clone> where T: Copy { t: T } }"#
+            .parse()
+            .unwrap();
+    scope.impl_for_marked("component", syn::parse_quote!(Component), |_| {
+        Ok(quote::quote!(
+            fn id() {}
+        ))
+    });
+    assert_eq!(
+        scope.expand().unwrap().to_string(),
+        "mod scope { struct A < T : Clone > where T : Copy { t : T } \
+         # [cfg (test)] impl < T : Clone > Component for A < T > where T : Copy { fn id () { } } }"
+    );
+}
+
+#[test]
+fn impl_relations() {
+    let scope: MacroScope = syn::parse_quote! {
+        mod scope {
+            #[system]
+            struct Foo<T>(T);
+
+            struct Bar;
+
+            impl<T> Foo<T> {
+                fn new(value: T) -> Self { Self(value) }
+            }
+
+            impl<T> crate::Plugin for Foo<T> {}
+            impl Plugin for Bar {}
+            impl Clone for Bar { fn clone(&self) -> Self { Bar } }
+        }
+    };
+
+    let implementors: Vec<String> = scope
+        .implementors_of(&syn::parse_quote!(Plugin))
+        .iter()
+        .map(|ty| quote::ToTokens::to_token_stream(ty).to_string())
+        .collect();
+    assert_eq!(implementors, ["Foo < T >", "Bar"]);
+
+    assert_eq!(scope.impls_for(&syn::parse_quote!(Foo)).len(), 2);
+    assert_eq!(scope.impls_for(&syn::parse_quote!(Bar)).len(), 2);
+}
+
+#[test]
+fn strip_inner_marks() {
+    let mut scope: MacroScope = syn::parse_quote! {
+        mod scope {
+            #[table]
+            struct User {
+                #[column(primary)]
+                id: u32,
+                #[doc = "name"]
+                name: String,
+            }
+
+            enum State {
+                #[column]
+                A(#[column] u8),
+                B,
+            }
+        }
+    };
+    scope.config.inner_marks = vec!["column".into()];
+    scope.register("table", noop);
+
+    let expanded = scope.expand().unwrap().to_string();
+    assert!(!expanded.contains("column"));
+    assert!(expanded.contains("doc"));
+}
+
+#[test]
+fn shadow_module() {
+    let scope: MacroScope = syn::parse_quote! {
+        mod scope {
+            #[ffi]
+            struct Foo {
+                bar: Bar,
+                baz: Baz,
+                outer: Outer<Bar, Baz>,
+            }
+
+            #[ffi]
+            struct Bar(u8);
+
+            struct Baz;
+            struct Outer<T, U>(T, U);
+        }
+    };
+
+    let shadow = scope.shadow_module("ffi", &syn::parse_quote!(ffi), "Raw");
+    let expected = quote::quote! {
+        mod ffi {
+            struct FooRaw {
+                bar: BarRaw,
+                baz: super::Baz,
+                outer: super::Outer<BarRaw, super::Baz>,
+            }
+            struct BarRaw(u8);
+        }
+    };
+    assert_eq!(shadow.to_string(), expected.to_string());
+}
+
+#[test]
+fn verbatim_marks() {
+    let scope: MacroScope = syn::parse_quote! {
+        mod scope {
+            #[doc = "declaration"]
+            #[ffi]
+            fn declared();
+        }
+    };
+
+    let marked = scope.get_marked("ffi");
+    assert_eq!(marked.len(), 1);
+    assert_eq!(marked[0].index, 1);
+    let tokens = quote::ToTokens::to_token_stream(&*marked[0].item.borrow()).to_string();
+    assert_eq!(
+        tokens,
+        quote::quote!(
+            #[doc = "declaration"]
+            fn declared();
+        )
+        .to_string()
+    );
+
+    marked[0].restore_mark();
+    assert_eq!(scope.items_with_any(&["ffi"]).len(), 1);
+}
+
+#[test]
+fn extern_crate_marks() {
+    let scope: MacroScope = "mod scope {
+        #[link_plugin] extern crate foo;
+        #[link_plugin] extern crate bar as baz;
+        extern crate std;
+    }"
+    .parse()
+    .unwrap();
+
+    let plugins = scope
+        .get_marked_as::<syn::ItemExternCrate>("link_plugin")
+        .unwrap();
+    let names: Vec<_> = plugins
+        .iter()
+        .map(|p| p.crate_ident().to_string())
+        .collect();
+    assert_eq!(names, ["foo", "baz"]);
+}
+
+#[test]
+fn source_order() {
+    let scope: MacroScope =
+        "mod scope { #[system_b] fn a() {} #[system_a] fn b() {} #[system_b] fn c() {} }"
+            .parse()
+            .unwrap();
+
+    let mut marked: Vec<_> = scope
+        .get_items_by_mark_prefix("system")
+        .into_values()
+        .flatten()
+        .collect();
+    marked.sort_by_key(macro_scope::MarkedItem::source_key);
+
+    let positions: Vec<_> = marked.iter().map(|m| m.position).collect();
+    assert_eq!(positions, [0, 1, 2]);
+
+    // marks queried last come first, several marks on one item are ordered by their attribute
+    let scope: MacroScope = "mod scope { #[system_a] #[system_b] fn a() {} #[system_a] fn b() {} }"
+        .parse()
+        .unwrap();
+    let mut marked = scope.get_marked("system_b");
+    marked.extend(scope.get_marked("system_a"));
+    marked.sort_by_key(macro_scope::MarkedItem::source_key);
+
+    let order: Vec<_> = marked.iter().map(|m| (m.mark_name(), m.position)).collect();
+    assert_eq!(
+        order,
+        [
+            ("system_a".to_string(), 0),
+            ("system_b".to_string(), 0),
+            ("system_a".to_string(), 1)
+        ]
+    );
+}
+
+#[test]
+fn diff() {
+    let old: MacroScope = "mod scope { struct A; struct B(u8); fn c() {} impl A {} }"
+        .parse()
+        .unwrap();
+    let new: MacroScope = "mod scope { struct A; struct B(u16); fn d() {} impl A {} }"
+        .parse()
+        .unwrap();
+
+    let diff = new.diff(&old);
+    let names = |keys: &[macro_scope::ItemKey]| -> Vec<String> {
+        keys.iter().map(ToString::to_string).collect()
+    };
+    assert_eq!(names(&diff.added), ["fn d"]);
+    assert_eq!(names(&diff.removed), ["fn c"]);
+    assert_eq!(names(&diff.changed), ["struct B"]);
+    assert!(old.diff(&old).is_empty());
+}
+
+#[test]
+fn strict_unscanned_kind() {
+    let mut scope = MacroScope::parse_with_config(
+        "mod scope { #[system] struct A; #[system] impl A {} }"
+            .parse()
+            .unwrap(),
+        macro_scope::ScopeConfig {
+            strict: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    scope.register("system", noop);
+
+    let error = scope.expand().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "`#[system]` is not supported on items of kind `impl`"
+    );
+}
+
+#[test]
+fn scan_set() {
+    let config = ScopeConfig {
+        scan_set: macro_scope::ScanSet::only([macro_scope::ItemKind::Struct]),
+        ..Default::default()
+    };
+    let source = "mod scope { #[mark] const A: u8 = 0; #[mark] struct B; }";
+    for scope in parse_both(source, config) {
+        let marked = scope.get_marked("mark");
+        assert_eq!(marked.len(), 1);
+        assert!(matches!(&*marked[0].item.borrow(), Item::Struct(_)));
+        // the mark on the excluded const is left alone
+        assert!(quote::ToTokens::to_token_stream(&scope)
+            .to_string()
+            .contains("# [mark] const A"));
+    }
+}
+
+#[test]
+fn skipped_items() {
+    let mut config = macro_scope::ScopeConfig::default();
+    config.scan_set.0.remove(&macro_scope::ItemKind::Verbatim);
+    let scope = MacroScope::parse_with_config(
+        "mod scope { struct A; impl A {} fn declared(); }"
+            .parse()
+            .unwrap(),
+        config,
+    )
+    .unwrap();
+
+    let reasons: Vec<_> = scope.skipped_items().iter().map(|s| s.reason).collect();
+    assert_eq!(
+        reasons,
+        [
+            macro_scope::SkipReason::ExcludedKind(macro_scope::ItemKind::Impl),
+            macro_scope::SkipReason::Unsupported,
+        ]
+    );
+}
+
+#[test]
+fn const_values() {
+    let tree: macro_scope::MetaTree =
+        syn::parse_quote!(mark(size = 4, offset = -1, name = "a", big = 300));
+    let value = |name: &str, ty: syn::Type| {
+        tree.child(name)
+            .unwrap()
+            .const_value(&ty)
+            .map(|tokens| tokens.to_string())
+            .map_err(|e| e.to_string())
+    };
+
+    assert_eq!(value("size", syn::parse_quote!(usize)).unwrap(), "4");
+    assert_eq!(value("size", syn::parse_quote!(f32)).unwrap(), "4.0");
+    assert_eq!(value("offset", syn::parse_quote!(i8)).unwrap(), "{ - 1 }");
+    assert_eq!(
+        value("name", syn::parse_quote!(&'static str)).unwrap(),
+        "\"a\""
+    );
+    assert_eq!(
+        value("offset", syn::parse_quote!(usize)).unwrap_err(),
+        "`-1` is out of range for `usize`"
+    );
+    assert_eq!(
+        value("big", syn::parse_quote!(u8)).unwrap_err(),
+        "`300` is out of range for `u8`"
+    );
+    assert_eq!(
+        value("name", syn::parse_quote!(u8)).unwrap_err(),
+        "expected a value of type `u8`"
+    );
+}
+
+#[test]
+fn generated_attributes() {
+    let mut scope: MacroScope = "mod scope { #[component] struct Foo; }".parse().unwrap();
+    scope.provenance = true;
+    scope.automatically_derived = true;
+    scope.allow = vec![
+        syn::parse_quote!(dead_code),
+        syn::parse_quote!(clippy::pedantic),
+    ];
+    scope.register(
+        "component",
+        |_: &macro_scope::SharedMarkedItem<syn::Item>| {
+            Ok(quote::quote!(impl Foo {} struct FooState;))
+        },
+    );
+
+    let file: syn::File = syn::parse2(scope.expand().unwrap()).unwrap();
+    let syn::Item::Mod(module) = &file.items[0] else {
+        panic!("expected a module");
+    };
+    let items = &module.content.as_ref().unwrap().1;
+    let expected = quote::quote! {
+        #[allow(dead_code, clippy::pedantic)]
+        #[doc = "generated from `Foo` by #[component]"]
+        #[automatically_derived]
+        impl Foo {}
+        #[allow(dead_code, clippy::pedantic)]
+        #[doc = "generated from `Foo` by #[component]"]
+        struct FooState;
+    };
+    let generated: proc_macro2::TokenStream = items[1..]
+        .iter()
+        .map(quote::ToTokens::to_token_stream)
+        .collect();
+    assert_eq!(generated.to_string(), expected.to_string());
+}
+
+#[test]
+fn pushed_impls_are_automatically_derived() {
+    let mut scope: MacroScope = "mod scope { struct Foo; }".parse().unwrap();
+    scope.automatically_derived = true;
+
+    let pushed = scope
+        .push_tokens(quote::quote!(impl Foo {} struct Bar;))
+        .unwrap();
+    let attrs = |item: &std::rc::Rc<std::cell::RefCell<Item>>| match &*item.borrow() {
+        Item::Impl(item) => item.attrs.clone(),
+        Item::Struct(item) => item.attrs.clone(),
+        _ => unreachable!(),
+    };
+    let impl_attrs = attrs(&pushed[0]);
+    assert_eq!(impl_attrs.len(), 1);
+    assert!(impl_attrs[0].path().is_ident("automatically_derived"));
+    // only impls are marked
+    assert!(attrs(&pushed[1]).is_empty());
+
+    let emitted = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(emitted.contains("# [automatically_derived] impl Foo { }"));
+}
+
+#[test]
+fn journal() {
+    let mut scope: MacroScope = "mod scope { #[system] fn a() {} }".parse().unwrap();
+    scope.journaling = true;
+
+    macro_scope::Pipeline::new()
+        .pass("collect", |scope: &mut MacroScope| {
+            scope.get_marked("system");
+            Ok(())
+        })
+        .pass("extend", |scope: &mut MacroScope| {
+            scope.push_item(syn::parse_quote!(
+                struct B;
+            ));
+            Ok(())
+        })
+        .run(&mut scope)
+        .unwrap();
+
+    let journal: Vec<_> = scope.journal().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        journal,
+        [
+            "[collect] removed `#[system]` from fn a",
+            "[extend] pushed struct B"
+        ]
+    );
+}
+
+#[test]
+fn selectors() {
+    let config: macro_scope::ScopeConfig =
+        syn::parse_quote!(select = "struct:pub, fn[mark=system], *[name=C]");
+    let scope = MacroScope::parse_with_config(
+        quote::quote! {
+            mod scope {
+                pub struct A;
+                struct B;
+                #[system] fn a() {}
+                fn b() {}
+                const C: u8 = 0;
+            }
+        },
+        config,
+    )
+    .unwrap();
+
+    let names: Vec<_> = scope
+        .selected()
+        .iter()
+        .map(|item| quote::ToTokens::to_token_stream(&*item.borrow()).to_string())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "pub struct A ;",
+            "# [system] fn a () { }",
+            "const C : u8 = 0 ;"
+        ]
+    );
+
+    let error = syn::parse_str::<macro_scope::ScopeConfig>(r#"select = "strukt""#).unwrap_err();
+    assert_eq!(error.to_string(), "unknown item kind `strukt`");
+}
+
+#[test]
+fn group_by_module() {
+    let scope = MacroScope::parse_with_config(
+        quote::quote! {
+            mod scope {
+                #[system] fn a() {}
+                mod render {
+                    #[system] fn b() {}
+                    mod passes {
+                        #[system] fn c() {}
+                    }
+                    #[system] fn d() {}
+                }
+            }
+        },
+        macro_scope::ScopeConfig {
+            recursive: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let groups: Vec<_> = scope
+        .group_by_module("system")
+        .iter()
+        .map(|(path, items)| {
+            let path: Vec<_> = path.iter().map(ToString::to_string).collect();
+            (path.join("::"), items.len())
+        })
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            ("".to_string(), 1),
+            ("render".to_string(), 2),
+            ("render::passes".to_string(), 1)
+        ]
+    );
+}
+
+#[test]
+fn mark_handlers() {
+    struct Describe;
+
+    impl macro_scope::MarkHandler for Describe {
+        fn mark(&self) -> &str {
+            "describe"
+        }
+
+        fn handle(
+            &self,
+            item: macro_scope::MarkedItem<syn::Item>,
+        ) -> syn::Result<proc_macro2::TokenStream> {
+            let item: macro_scope::MarkedItem<syn::ItemStruct> = item.try_into()?;
+            let ident = &item.item.ident;
+            let name = ident.to_string();
+            Ok(quote::quote!(impl #ident { const NAME: &str = #name; }))
+        }
+    }
+
+    let mut scope: MacroScope = "mod scope { #[describe] struct Foo; }".parse().unwrap();
+    scope.register_handler(Box::new(Describe));
+
+    let expanded = scope.expand().unwrap().to_string();
+    assert!(
+        expanded.contains("const NAME : & str = \"Foo\""),
+        "{expanded}"
+    );
+}
+
+#[test]
+fn mark_meta() {
+    let scope: MacroScope =
+        r#"mod scope { #[a] fn a() {} #[b(order = 1)] fn b() {} #[c = "x"] fn c() {} }"#
+            .parse()
+            .unwrap();
+
+    let a = &scope.get_marked("a")[0];
+    assert!(a.is_path() && a.list().is_none() && a.name_value().is_none());
+    let b = &scope.get_marked("b")[0];
+    assert_eq!(b.list().unwrap().tokens.to_string(), "order = 1");
+    let c = &scope.get_marked("c")[0];
+    assert_eq!(c.value_str().as_deref(), Some("x"));
+    assert!(c.name_value().is_some());
+}
+
+#[test]
+fn discover_marks() {
+    let scope: MacroScope = "mod scope {
+        #[system] #[derive(Debug)] struct A;
+        #[ecs::component] #[system] fn b() {}
+        #[ignored] impl A {}
+    }"
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        scope.discover_marks(None),
+        ["system", "derive", "ecs::component"]
+    );
+    assert_eq!(scope.discover_marks(Some("ecs")), ["ecs::component"]);
+}
+
+#[test]
+fn recursion_limits() {
+    let tokens = quote::quote! {
+        mod scope {
+            #[system] fn a() {}
+            mod one {
+                #[system] fn b() {}
+                mod two {
+                    #[system] fn c() {}
+                }
+            }
+            #[macro_scope(skip)]
+            mod generated {
+                #[system] fn d() {}
+            }
+        }
+    };
+
+    let config: macro_scope::ScopeConfig = syn::parse_quote!(recursive, max_depth = 1);
+    let scope = MacroScope::parse_with_config(tokens, config).unwrap();
+    assert_eq!(scope.get_marked("system").len(), 2);
+
+    let emitted = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(!emitted.contains("macro_scope"));
+    assert!(emitted.contains("fn c"));
+    assert!(emitted.contains("# [system] fn d"));
+
+    let source = "mod scope {
+        #[macro_scope(skip)]
+        mod generated { #[macro_scope(skip)] mod nested {} }
+    }";
+    let expected = quote::quote! {
+        mod scope {
+            mod generated { mod nested {} }
+        }
+    };
+    for scope in parse_both(source, Default::default()) {
+        assert_eq!(quote::quote!(#scope).to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn inherited_marks() {
+    let config: macro_scope::ScopeConfig =
+        syn::parse_quote!(recursive, inherit_marks = "component");
+    let scope = MacroScope::parse_with_config(
+        quote::quote! {
+            mod scope {
+                #[component]
+                mod shapes {
+                    struct Circle;
+                    #[macro_scope(no_inherit)]
+                    struct Helper;
+                    impl Circle {}
+                    mod three_d {
+                        struct Sphere;
+                    }
+                }
+            }
+        },
+        config,
+    )
+    .unwrap();
+
+    let names: Vec<_> = scope
+        .get_marked("component")
+        .iter()
+        .map(|marked| match &*marked.item.borrow() {
+            syn::Item::Struct(item) => item.ident.to_string(),
+            item => panic!("unexpected item {item:?}"),
+        })
+        .collect();
+    assert_eq!(names, ["Circle", "Sphere"]);
+
+    let source = "mod scope {
+        #[component] struct A;
+        mod plain { #[macro_scope(no_inherit)] struct B; }
+    }";
+    let expected = quote::quote! {
+        mod scope {
+            #[component] struct A;
+            mod plain { struct B; }
+        }
+    };
+    let configs: [macro_scope::ScopeConfig; 2] = [
+        Default::default(),
+        syn::parse_quote!(recursive, inherit_marks = "component"),
+    ];
+    for config in configs {
+        for scope in parse_both(source, config) {
+            assert_eq!(quote::quote!(#scope).to_string(), expected.to_string());
+        }
+    }
+
+    let emitted = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(!emitted.contains("macro_scope"));
+}
+
+#[test]
+fn expand_per_item() {
+    let mut scope: MacroScope = "mod scope { #[component] struct A; #[component] struct B; }"
+        .parse()
+        .unwrap();
+    scope.register(
+        "component",
+        |marked: &macro_scope::SharedMarkedItem<syn::Item>| {
+            let syn::Item::Struct(item) = &*marked.item.borrow() else {
+                unreachable!()
+            };
+            let ident = &item.ident;
+            Ok(quote::quote!(impl #ident {}))
+        },
+    );
+
+    let expansion = scope.expand_per_item().unwrap();
+    let per_item: Vec<_> = expansion
+        .per_item
+        .iter()
+        .map(|generated| (generated.template.as_str(), generated.tokens.to_string()))
+        .collect();
+    assert_eq!(
+        per_item,
+        [
+            ("component", "impl A { }".into()),
+            ("component", "impl B { }".to_string())
+        ]
+    );
+    assert_eq!(expansion.generated.to_string(), "impl A { } impl B { }");
+}
+
+#[test]
+fn prelude() {
+    let source = "mod scope { #[system] fn a() {} struct Local; }";
+    for mut scope in parse_both(source, Default::default()) {
+        scope.use_path(syn::parse_quote!(::framework::Registry));
+        scope.use_path(syn::parse_quote!(::framework::Registry));
+        scope.use_path(syn::parse_quote!(::framework::Local));
+        scope.register("system", |_: &SharedMarkedItem<Item>| {
+            Ok(quote::quote!(impl Registry {}))
+        });
+
+        let (_, generated) = scope.expand_split().unwrap();
+        let expected = quote::quote! {
+            #[allow(unused_imports)]
+            use ::framework::Registry;
+            impl Registry {}
+        };
+        assert_eq!(generated.to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn generic_usage() {
+    let item: syn::ItemStruct = syn::parse_quote! {
+        struct Buffer<'a, T: Clone, U, const N: usize> {
+            data: &'a [T; N],
+            len: usize,
+            other: Vec<U::Item>,
+        }
+    };
+
+    let usage: Vec<_> = item
+        .fields
+        .iter()
+        .map(|field| macro_scope::generics::GenericUsage::of_field(field, &item.generics))
+        .collect();
+
+    assert_eq!(usage[0].types, [syn::parse_quote!(T)] as [syn::Ident; 1]);
+    assert_eq!(usage[0].consts, [syn::parse_quote!(N)] as [syn::Ident; 1]);
+    assert_eq!(
+        usage[0].lifetimes,
+        [syn::parse_quote!('a)] as [syn::Lifetime; 1]
+    );
+    assert!(usage[1].is_empty());
+    assert_eq!(usage[2].types, [syn::parse_quote!(U)] as [syn::Ident; 1]);
+}
+
+#[test]
+fn mark_schema() {
+    use macro_scope::schema::{ArgType, Schema};
+
+    let fallback: syn::Path = syn::parse_quote!(crate::fallback);
+    let schema = Schema::new()
+        .required("path", ArgType::Str)
+        .optional("order", ArgType::Int, 0)
+        .flag("hidden")
+        .optional("handler", ArgType::Path, fallback);
+
+    let scope: MacroScope = r#"mod scope {
+        #[route(path = "/a", order = 2, hidden, handler = crate::a)] fn a() {}
+        #[route(pth = "/b")] fn b() {}
+        #[route(path = 1)] fn c() {}
+    }"#
+    .parse()
+    .unwrap();
+
+    let errors: Vec<_> = scope
+        .get_marked_with_args("route", &schema)
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            "unknown argument `pth`, did you mean `path`?",
+            "missing required argument `path`",
+            "expected a string literal",
+        ]
+    );
+
+    let scope: MacroScope =
+        r#"mod scope { #[route(path = "/a", order = 2, hidden, handler = crate::a)] fn a() {} }"#
+            .parse()
+            .unwrap();
+    let routes = scope.get_marked_with_args("route", &schema).unwrap();
+    let args = &routes[0].1;
+    assert_eq!(args.str("path"), Some("/a"));
+    assert_eq!(args.int("order"), Some(2));
+    assert_eq!(args.bool("hidden"), Some(true));
+    assert!(args.path("handler").is_some());
+}
+
+#[test]
+fn gensym() {
+    let names = |source: &str| {
+        let scope: MacroScope = source.parse().unwrap();
+        [scope.gensym("registry"), scope.gensym("registry")].map(|ident| ident.to_string())
+    };
+
+    let a = names("mod a { struct A; }");
+    assert_ne!(a[0], a[1]);
+    assert!(a[0].starts_with("__registry_"));
+    assert_eq!(a, names("mod a { struct A; }"));
+    assert_ne!(a, names("mod b { struct B; }"));
+}
+
+#[test]
+fn attribute_index() {
+    let source = "mod scope {
+        #[system_a] fn a() {}
+        #[derive(Debug)] struct B;
+        #[system_b] #[other] fn c() {}
+        #[render_system] fn d() {}
+    }";
+    let modes = [
+        macro_scope::MatchMode::Contains,
+        macro_scope::MatchMode::Prefix,
+        macro_scope::MatchMode::Exact,
+    ];
+
+    for match_mode in modes {
+        let parse = || {
+            let config = macro_scope::ScopeConfig {
+                match_mode,
+                ..Default::default()
+            };
+            MacroScope::parse_with_config(source.parse().unwrap(), config).unwrap()
+        };
+        for mark in ["system", "system_b", "other", "derive", "missing"] {
+            let indexed = parse();
+            indexed.build_index();
+            let plain = parse();
+            let positions = |scope: &MacroScope| -> Vec<_> {
+                scope
+                    .get_marked(mark)
+                    .iter()
+                    .map(|m| (m.position, m.mark_name()))
+                    .collect()
+            };
+            assert_eq!(
+                positions(&indexed),
+                positions(&plain),
+                "{match_mode:?} {mark}"
+            );
+        }
+    }
+}
+
+#[test]
+fn item_handles() {
+    let scope: MacroScope = "mod scope { #[system] fn a() {} struct B; }"
+        .parse()
+        .unwrap();
+
+    let marked = scope.get_marked_handles("system");
+    assert_eq!(marked.len(), 1);
+    let handle = &marked[0].item;
+    assert!(handle.ptr_eq(&scope.item_handles()[0]));
+
+    handle.write(|item| {
+        if let syn::Item::Fn(f) = item {
+            f.sig.ident = syn::parse_quote!(renamed);
+        }
+    });
+    let name = handle.read(|item| macro_scope::ItemKey::of(item).to_string());
+    assert_eq!(name, "fn renamed");
+    assert!(quote::ToTokens::to_token_stream(&scope)
+        .to_string()
+        .contains("fn renamed"));
+}
+
+#[test]
+fn related_errors() {
+    let scope: MacroScope = "mod scope { #[system] fn a() {} #[system] struct B; }"
+        .parse()
+        .unwrap();
+
+    let marked = scope.get_marked("system");
+    let error = macro_scope::Diagnostics::related(&marked, "conflicting systems");
+    let messages: Vec<_> = error.into_iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "conflicting systems",
+            "note: also involved in: conflicting systems"
+        ]
+    );
+}
+
+#[test]
+fn idempotent_expansion() {
+    let expand = |tokens: proc_macro2::TokenStream| {
+        let config = macro_scope::ScopeConfig {
+            mark_policy: macro_scope::MarkPolicy::Keep,
+            ..Default::default()
+        };
+        let mut scope = MacroScope::parse_with_config(tokens, config).unwrap();
+        scope.idempotent = true;
+        scope.register("system", |_: &macro_scope::SharedMarkedItem<syn::Item>| {
+            Ok(quote::quote!(
+                fn generated() {}
+            ))
+        });
+        scope.expand().unwrap()
+    };
+
+    let once = expand("mod scope { #[system] fn a() {} }".parse().unwrap());
+    let twice = expand(once.clone());
+    assert_eq!(twice.to_string(), once.to_string());
+    assert_eq!(twice.to_string().matches("fn generated").count(), 1);
+}
+
+#[test]
+fn idempotent_failed_items_stay_untagged() {
+    let mut scope: MacroScope = "mod scope { #[system] struct A; #[system] struct B; }"
+        .parse()
+        .unwrap();
+    scope.idempotent = true;
+    scope.register(
+        "system",
+        |marked: &macro_scope::SharedMarkedItem<syn::Item>| match macro_scope::ItemKey::of(
+            &marked.item.borrow(),
+        )
+        .name
+        .as_str()
+        {
+            "A" => Ok(Default::default()),
+            _ => Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "unsupported",
+            )),
+        },
+    );
+
+    assert!(scope.expand().is_err());
+    let tagged = |item: &std::rc::Rc<std::cell::RefCell<syn::Item>>| {
+        quote::ToTokens::to_token_stream(&*item.borrow())
+            .to_string()
+            .contains("expanded")
+    };
+    assert!(tagged(&scope.items[0]));
+    assert!(!tagged(&scope.items[1]));
+}
+
+#[test]
+fn nested_scopes() {
+    let source = "mod app { #[system] fn a() {} #[plugin] mod physics { #[system] fn step() {} } }";
+    let parse = || {
+        let config = macro_scope::ScopeConfig {
+            recursive: true,
+            ..Default::default()
+        };
+        MacroScope::parse_with_config(source.parse().unwrap(), config).unwrap()
+    };
+    let systems = |scope: &MacroScope| scope.get_marked("system").len();
+
+    let mut scope = parse();
+    let nested = scope.nested_scopes("plugin");
+    assert_eq!(nested.len(), 1);
+    scope.defer_scope(&nested[0]);
+    assert_eq!(systems(&scope), 1);
+    let tokens = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(tokens.contains("# [plugin] mod physics { # [system] fn step"));
+
+    let mut scope = parse();
+    let nested = scope.nested_scopes("plugin");
+    let inner = scope.consume_scope(&nested[0]).unwrap();
+    assert_eq!(systems(&inner), 1);
+    assert_eq!(systems(&scope), 1);
+    let tokens = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(!tokens.contains("physics"));
+
+    let mut scope: MacroScope = source.parse().unwrap();
+    let nested = scope.nested_scopes("plugin");
+    scope.merge_scope(&nested[0]);
+    assert_eq!(systems(&scope), 2);
+    let tokens = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(tokens.contains("mod physics { fn step"));
+}
+
+#[test]
+fn attr_macro_entry() {
+    let scope = MacroScope::from_attr_macro(
+        "recursive".parse().unwrap(),
+        "mod scope { mod inner { #[system] fn a() {} } }"
+            .parse()
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(scope.config.recursive);
+    assert_eq!(scope.get_marked("system").len(), 1);
+
+    let scope = MacroScope::from_attr_macro(
+        Default::default(),
+        "impl Foo { fn a() {} }".parse().unwrap(),
+    )
+    .unwrap();
+    assert!(scope.module.is_none());
+    assert_eq!(scope.items.len(), 1);
+    assert_eq!(
+        quote::ToTokens::to_token_stream(&scope).to_string(),
+        "impl Foo { fn a () { } }"
+    );
+
+    let error =
+        MacroScope::from_attr_macro("unknown".parse().unwrap(), "struct A;".parse().unwrap());
+    assert!(error.is_err());
+}
+
+#[test]
+fn scope_inputs() {
+    use macro_scope::ScopeInput;
+
+    let parse = |source: &str| syn::parse_str::<ScopeInput>(source).unwrap();
+    assert!(matches!(
+        parse("mod scope { struct A; }"),
+        ScopeInput::Module(_)
+    ));
+    assert!(matches!(
+        parse("#[system] struct A;"),
+        ScopeInput::SingleItem(_)
+    ));
+    assert!(matches!(parse("struct A; struct B;"), ScopeInput::File(_)));
+    assert!(matches!(
+        parse("#![allow(dead_code)] struct A;"),
+        ScopeInput::File(_)
+    ));
+
+    for source in [
+        "mod scope { #[system] fn a() {} }",
+        "#[system] fn a() {}",
+        "#[system] fn a() {} struct B;",
+    ] {
+        let scope = MacroScope::from_input(parse(source), Default::default()).unwrap();
+        assert_eq!(scope.get_marked("system").len(), 1, "{source}");
+    }
+}
+
+#[test]
+fn generated_visibility() {
+    let scope: MacroScope = "mod scope {
+        struct Private;
+        pub struct Public;
+        mod inner { struct Secret; pub(super) struct Shared; }
+    }"
+    .parse()
+    .unwrap();
+
+    let check = |tokens: &str| {
+        scope
+            .check_visibility(&tokens.parse().unwrap())
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(check("fn a(_: Private, _: inner::Shared) {}"), Ok(()));
+    assert_eq!(check("pub fn b<T>(_: Public, _: T) {}"), Ok(()));
+    assert_eq!(
+        check("fn c() { let _ = inner::Secret; }"),
+        Err("`inner::Secret` is private, but referenced by generated code".into())
+    );
+    assert_eq!(
+        check("pub fn d() -> Private { Private }"),
+        Err("`Private` is private, but exposed by the generated `pub fn d`".into())
+    );
+}
+
+#[test]
+fn reexports() {
+    let config = macro_scope::ScopeConfig {
+        recursive: true,
+        ..Default::default()
+    };
+    let source = "mod scope {
+        #[prelude] pub struct App;
+        pub mod shapes { #[prelude(rename = \"Shape\")] pub trait Base {} }
+    }";
+    let scope = MacroScope::parse_with_config(source.parse().unwrap(), config).unwrap();
+
+    let prelude = scope
+        .reexports("prelude", &syn::parse_quote!(prelude))
+        .unwrap();
+    let expected = quote::quote! {
+        pub mod prelude {
+            pub use super::App;
+            pub use super::shapes::Base as Shape;
+        }
+    };
+    assert_eq!(prelude.to_string(), expected.to_string());
+
+    let scope: MacroScope = "mod scope { #[prelude] struct Private; }".parse().unwrap();
+    let error = scope
+        .reexports("prelude", &syn::parse_quote!(prelude))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "`Private` needs to be `pub` to be re-exported by `#[prelude]`"
+    );
+}
+
+#[test]
+fn token_hashes() {
+    let mut scope: MacroScope = "mod scope { struct A; fn b() {} }".parse().unwrap();
+    let snapshot = scope.token_hashes();
+    assert!(scope.changed_since(&snapshot).is_empty());
+
+    let handle = &scope.item_handles()[0];
+    let before = handle.token_hash();
+    handle.write(|item| {
+        *item = syn::parse_quote!(
+            struct A(u8);
+        )
+    });
+    assert_ne!(handle.token_hash(), before);
+
+    scope.push_item(syn::parse_quote!(
+        struct C;
+    ));
+    let diff = scope.changed_since(&snapshot);
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.added[0].to_string(), "struct C");
+}
+
+#[test]
+fn hidden_module() {
+    let register = |mut scope: MacroScope| {
+        scope.hidden_module = Some("__generated".into());
+        scope.register("system", |_: &macro_scope::SharedMarkedItem<syn::Item>| {
+            Ok(quote::quote! {
+                pub fn registered() {}
+                struct Registry;
+                impl Registry {}
+            })
+        });
+        scope
+    };
+
+    let scope = "mod scope { #[system] fn a() {} }".parse().unwrap();
+    let tokens = register(scope).expand().unwrap();
+    let expected = quote::quote! {
+        mod scope {
+            fn a() {}
+            #[doc(hidden)]
+            mod __generated {
+                #[allow(unused_imports)]
+                use super::*;
+                pub fn registered() {}
+                pub(super) struct Registry;
+                impl Registry {}
+            }
+            pub use __generated::registered;
+            use __generated::Registry;
+        }
+    };
+    assert_eq!(tokens.to_string(), expected.to_string());
+
+    let source = "mod scope { #[system] fn a() {} mod __generated {} }";
+    for scope in parse_both(source, Default::default()) {
+        let error = register(scope).expand().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`__generated` is already used as the name of the module of generated code"
+        );
+    }
+}
+
+#[test]
+fn generated_tests() {
+    let register = |mut scope: MacroScope| {
+        scope.register_with_tests(
+            "roundtrip",
+            noop,
+            |marked: &macro_scope::SharedMarkedItem<syn::Item>| {
+                let name = macro_scope::ItemKey::of(&marked.item.borrow()).name;
+                let name = quote::format_ident!("roundtrip_{}", name.to_lowercase());
+                Ok(quote::quote!(#[test] fn #name() {}))
+            },
+        );
+        scope
+    };
+
+    let source = "mod scope { #[roundtrip] struct A; #[roundtrip] struct B; }";
+    let tokens = register(source.parse().unwrap()).expand().unwrap();
+    let expected = quote::quote! {
+        mod scope {
+            struct A;
+            struct B;
+            #[cfg(test)]
+            mod tests {
+                #[allow(unused_imports)]
+                use super::*;
+                #[test] fn roundtrip_a() {}
+                #[test] fn roundtrip_b() {}
+            }
+        }
+    };
+    assert_eq!(tokens.to_string(), expected.to_string());
+
+    let source = "mod scope { #[roundtrip] struct A; #[cfg(test)] mod tests { fn own() {} } }";
+    let expected = quote::quote! {
+        mod scope {
+            struct A;
+            #[cfg(test)] mod tests { fn own() {} #[test] fn roundtrip_a() {} }
+        }
+    };
+    for scope in parse_both(source, Default::default()) {
+        let tokens = register(scope).expand().unwrap();
+        assert_eq!(tokens.to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn static_assertions() {
+    use macro_scope::emit::Assertions;
+
+    let scope: MacroScope = "mod scope { #[checked] struct A<T>(T); #[checked] fn b() {} }"
+        .parse()
+        .unwrap();
+    let checks = Assertions::new().implements(quote::quote!(Send));
+    let error = checks.for_marked(&scope, "checked").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "static assertions are only supported on structs, enums and unions"
+    );
+
+    let item: syn::ItemStruct = syn::parse_quote!(
+        struct A<T>(T);
+    );
+    let tokens = checks.for_type(&item.ident, &item.generics).unwrap();
+    let expected = quote::quote! {
+        const _: () = {
+            fn assert_impl<T: ?::core::marker::Sized + Send>() {}
+            #[allow(dead_code)]
+            fn check<T>() {
+                assert_impl::<A<T> >();
+            }
+        };
+    };
+    assert_eq!(tokens.to_string(), expected.to_string());
+    assert!(Assertions::new()
+        .size(8)
+        .for_type(&item.ident, &item.generics)
+        .is_err());
+}
+
+#[test]
+fn mark_arguments() {
+    let scope: MacroScope = "mod scope {
+        #[register(category = \"audio\")] fn a() {}
+        #[register(category = \"video\")] fn b() {}
+        #[register(category = audio, id = 3)] fn c() {}
+        #[other(category = \"audio\")] fn d() {}
+    }"
+    .parse()
+    .unwrap();
+
+    let names = |items: Vec<_>| -> Vec<String> {
+        items
+            .iter()
+            .map(|item: &std::rc::Rc<std::cell::RefCell<syn::Item>>| {
+                macro_scope::ItemKey::of(&item.borrow()).name
+            })
+            .collect()
+    };
+    assert_eq!(
+        names(scope.items_with_mark_arg("register", "category", "audio")),
+        ["a", "c"]
+    );
+    assert_eq!(
+        names(scope.items_with_mark_arg("register", "category", "video")),
+        ["b"]
+    );
+    assert_eq!(
+        names(scope.items_with_mark_arg("register", "id", "3")),
+        ["c"]
+    );
+    assert_eq!(scope.get_marked("register").len(), 3);
+}
+
+#[test]
+fn mark_meta_tree() {
+    let scope: MacroScope = r#"mod scope {
+        #[schema(table(name = "users", index(col = "id")), size = 4, cached, owner = User)]
+        struct User;
+    }"#
+    .parse()
+    .unwrap();
+
+    let tree = scope.get_marked("schema")[0].meta_tree().unwrap();
+    assert_eq!(tree.name().as_deref(), Some("schema"));
+    let table = tree.child("table").unwrap();
+    assert_eq!(table.get_str("name").unwrap().as_deref(), Some("users"));
+    let index = table.child("index").unwrap();
+    assert_eq!(index.expect_list().unwrap().len(), 1);
+    assert_eq!(index.get_str("col").unwrap().as_deref(), Some("id"));
+    assert_eq!(tree.child("size").unwrap().expect_int::<u8>().unwrap(), 4);
+    assert!(tree.child("cached").unwrap().expect_bool().unwrap());
+    assert_eq!(tree.child("owner").unwrap().expect_ident().unwrap(), "User");
+    assert_eq!(table.get_str("missing").unwrap(), None);
+
+    // errors point at the offending argument, `(message, column)`
+    let error = |e: syn::Error| (e.to_string(), e.span().start().column);
+    assert_eq!(
+        error(tree.get_str("size").unwrap_err()),
+        ("expected a string literal".to_string(), 66)
+    );
+    assert_eq!(
+        error(tree.child("size").unwrap().expect_bool().unwrap_err()),
+        ("expected a boolean literal".to_string(), 66)
+    );
+    assert_eq!(
+        error(table.expect_lit().unwrap_err()),
+        ("expected a literal like `name = \"value\"`".to_string(), 17)
+    );
+    assert_eq!(
+        error(tree.child("cached").unwrap().expect_list().unwrap_err()),
+        ("expected a list like `name(...)`".to_string(), 69)
+    );
+
+    let parse_error = |source: &str| {
+        let scope: MacroScope = source.parse().unwrap();
+        error(scope.get_marked("schema")[0].meta_tree().unwrap_err())
+    };
+    // a missing `=` between the name and the value
+    assert_eq!(
+        parse_error(r#"mod scope { #[schema(name "users")] struct User; }"#),
+        ("expected `,`".to_string(), 26)
+    );
+
+    // a nested list where a value is expected
+    let scope: MacroScope = r#"mod scope { #[schema(name(first = "a"))] struct User; }"#
+        .parse()
+        .unwrap();
+    let tree = scope.get_marked("schema")[0].meta_tree().unwrap();
+    assert_eq!(
+        error(tree.get_str("name").unwrap_err()),
+        ("expected a literal like `name = \"value\"`".to_string(), 21)
+    );
+}
+
+#[test]
+fn pipeline_dependencies() {
+    let empty_pass = |_: &mut MacroScope| Ok(());
+    let pipeline = macro_scope::Pipeline::new()
+        .pass("systems", empty_pass)
+        .pass("resources", empty_pass)
+        .pass("docs", empty_pass)
+        .depends_on("systems", "resources");
+    assert_eq!(pipeline.order().unwrap(), ["resources", "systems", "docs"]);
+
+    let pipeline = pipeline.depends_on("resources", "systems");
+    let mut scope: MacroScope = "mod scope {}".parse().unwrap();
+    assert_eq!(
+        pipeline.run(&mut scope).unwrap_err().to_string(),
+        "pipeline passes depend on each other: `systems`, `resources`"
+    );
+
+    let pipeline = macro_scope::Pipeline::new()
+        .pass("a", empty_pass)
+        .depends_on("a", "b");
+    assert_eq!(
+        pipeline.order().unwrap_err().to_string(),
+        "pipeline has no pass named `b`"
+    );
+}
+
+#[test]
+fn child_uses() {
+    let source = "mod scope {
+        use super::types::*;
+        pub use self::shapes::Circle;
+        use std::{fmt, rc::Rc};
+        use shapes::{Square as Quad, self};
+        use crate::Registry;
+        mod shapes { pub struct Circle; pub struct Square; }
+    }";
+
+    let expected = quote::quote! {
+        #[allow(unused_imports)]
+        use super::super::types::*;
+        #[allow(unused_imports)]
+        use super::shapes::Circle;
+        #[allow(unused_imports)]
+        use std::{fmt, rc::Rc};
+        #[allow(unused_imports)]
+        use super::shapes::{Square as Quad, self};
+        #[allow(unused_imports)]
+        use crate::Registry;
+    };
+    for scope in parse_both(source, Default::default()) {
+        assert_eq!(scope.use_items().len(), 5);
+        assert_eq!(scope.child_uses().to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn trait_items() {
+    let scope: MacroScope = "mod scope {
+        #[service]
+        trait Store {
+            type Key;
+            const NAME: &'static str;
+            fn get(&self, key: Self::Key) -> Option<String>;
+            fn len(&self) -> usize { 0 }
+            fn open() -> Self;
+        }
+        #[service] struct NotATrait;
+    }"
+    .parse()
+    .unwrap();
+
+    let marked = scope.get_marked("service");
+    assert!(marked[1].trait_items().is_none());
+
+    let items = marked[0].trait_items().unwrap();
+    assert_eq!(items.types.len(), 1);
+    assert_eq!(items.consts[0].ident, "NAME");
+    let names = |methods: Vec<&syn::TraitItemFn>| -> Vec<String> {
+        methods.iter().map(|f| f.sig.ident.to_string()).collect()
+    };
+    assert_eq!(names(items.required_methods().collect()), ["get", "open"]);
+    assert_eq!(names(items.receiver_methods().collect()), ["get", "len"]);
+}
+
+#[test]
+fn mocks() {
+    let scope: MacroScope = "mod scope {
+        #[mockable]
+        pub trait Clock {
+            fn now(&self) -> u64;
+            fn advance(&mut self, by: u64);
+            fn name(&self) -> String { String::new() }
+        }
+    }"
+    .parse()
+    .unwrap();
+
+    let expected = quote::quote! {
+        #[allow(clippy::type_complexity)]
+        #[derive(Default)]
+        pub struct MockClock {
+            pub now: ::core::option::Option<::std::boxed::Box<dyn Fn() -> u64> >,
+            pub advance: ::core::option::Option<::std::boxed::Box<dyn FnMut(u64)> >,
+        }
+
+        impl Clock for MockClock {
+            fn now(&self) -> u64 {
+                match &self.now {
+                    ::core::option::Option::Some(f) => f(),
+                    ::core::option::Option::None => {
+                        ::core::panic!("`MockClock::now` was called but not configured")
+                    }
+                }
+            }
+            fn advance(&mut self, __arg1: u64) {
+                match &mut self.advance {
+                    ::core::option::Option::Some(f) => f(__arg1),
+                    ::core::option::Option::None => {
+                        ::core::panic!("`MockClock::advance` was called but not configured")
+                    }
+                }
+            }
+        }
+    };
+    let normalize =
+        |tokens: proc_macro2::TokenStream| prettyplease::unparse(&syn::parse2(tokens).unwrap());
+    assert_eq!(
+        normalize(scope.mocks("mockable").unwrap()),
+        normalize(expected)
+    );
+
+    let scope: MacroScope = "mod scope { #[mockable] trait Store { type Key; fn new() -> Self; } }"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        scope.mocks("mockable").unwrap_err().to_string(),
+        "associated types can't be mocked"
+    );
+}
+
+#[test]
+fn ident_index() {
+    let mut scope: MacroScope = "mod scope { struct Point; impl Point {} fn Point() {} }"
+        .parse()
+        .unwrap();
+
+    assert_eq!(scope.find("Point").len(), 2);
+    assert!(scope.find("Missing").is_empty());
+
+    let definition = scope
+        .definition_of(&syn::parse_quote!(self::Point))
+        .unwrap();
+    assert!(definition.read(|item| matches!(item, syn::Item::Struct(_))));
+    assert!(scope
+        .definition_of(&syn::parse_quote!(Vec<Point>))
+        .is_none());
+
+    scope.push_item(syn::parse_quote!(
+        enum Shape {}
+    ));
+    assert!(scope.definition_of(&syn::parse_quote!(Shape)).is_some());
+}
+
+#[test]
+fn renames() {
+    let mut scope: MacroScope = "mod scope {
+        use self::Foo as Alias;
+        struct Foo;
+        impl Foo { fn new() -> Self { Foo } }
+        enum E { Foo }
+        fn make() -> Foo { let _ = E::Foo; println!(\"{}\", Foo); Foo::new() }
+    }"
+    .parse()
+    .unwrap();
+
+    let renames = std::collections::HashMap::from([
+        (quote::format_ident!("Foo"), quote::format_ident!("FooV2")),
+        (quote::format_ident!("Bar"), quote::format_ident!("BarV2")),
+    ]);
+    let report = scope.apply_renames(&renames);
+    assert_eq!(report.renamed, ["Foo"]);
+    assert_eq!(report.undeclared, ["Bar"]);
+    assert_eq!(report.unresolved, ["Foo"]);
+
+    let tokens = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(tokens.contains("use self :: FooV2 as Alias"));
+    assert!(tokens.contains("struct FooV2 ;"));
+    assert!(tokens.contains("impl FooV2"));
+    assert!(tokens.contains("E :: Foo ;"));
+    assert!(tokens.contains("FooV2 :: new ()"));
+
+    let mut scope: MacroScope = "mod scope {
+        fn count() -> u32 { 0 }
+        fn a() -> u32 { let count = count(); count + 1 }
+        fn b(count: u32) -> u32 { count }
+        fn c() -> u32 { let f = |count: u32| count; f(count()) }
+        fn d(x: Option<u32>) -> u32 {
+            if let Some(count) = x { count } else { count() }
+        }
+        fn e(x: Option<u32>) -> u32 { match x { Some(count) => count, None => count() } }
+        fn f() -> u32 { { let count = 1; } count() }
+    }"
+    .parse()
+    .unwrap();
+    let renames = std::collections::HashMap::from([(
+        quote::format_ident!("count"),
+        quote::format_ident!("tally"),
+    )]);
+    assert!(scope.apply_renames(&renames).is_complete());
+
+    let expected = quote::quote! {
+        mod scope {
+            fn tally() -> u32 { 0 }
+            fn a() -> u32 { let count = tally(); count + 1 }
+            fn b(count: u32) -> u32 { count }
+            fn c() -> u32 { let f = |count: u32| count; f(tally()) }
+            fn d(x: Option<u32>) -> u32 {
+                if let Some(count) = x { count } else { tally() }
+            }
+            fn e(x: Option<u32>) -> u32 { match x { Some(count) => count, None => tally() } }
+            fn f() -> u32 { { let count = 1; } tally() }
+        }
+    };
+    assert_eq!(quote::quote!(#scope).to_string(), expected.to_string());
+}
+
+#[test]
+fn expansion_report() {
+    let mut scope: MacroScope = "mod scope { #[big] struct A; #[big] struct B; }"
+        .parse()
+        .unwrap();
+    scope.register(
+        "big",
+        |marked: &macro_scope::SharedMarkedItem<syn::Item>| {
+            Ok(
+                match macro_scope::ItemKey::of(&marked.item.borrow())
+                    .name
+                    .as_str()
+                {
+                    "A" => quote::quote!(
+                        fn a() {}
+                    ),
+                    _ => quote::quote!(
+                        fn b() {
+                            let _ = 1;
+                        }
+                    ),
+                },
+            )
+        },
+    );
+    scope.register("unused", noop);
+
+    let expansion = scope.expand_per_item().unwrap();
+    let report = scope.expansion_report(&expansion.per_item);
+    assert_eq!(report.marks.len(), 2);
+    assert_eq!(report.marks[0].items, 2);
+    assert_eq!(report.marks[0].tokens, 17);
+    assert_eq!(report.marks[1].items, 0);
+    assert_eq!(report.tokens(), 17);
+
+    let text = report.to_string();
+    assert!(
+        text.contains("#[big]: 2 items, 17 tokens, largest"),
+        "{text}"
+    );
+    assert!(text.contains("#[unused]: 0 items, 0 tokens"), "{text}");
+}
+
+#[test]
+fn lenient_parse() {
+    let source = "mod scope { #[system] fn a() {} #[system] fn b() -> {} struct C; }";
+    assert!(source.parse::<MacroScope>().is_err());
+
+    let config = macro_scope::ScopeConfig {
+        lenient: true,
+        ..Default::default()
+    };
+    let scope = MacroScope::parse_with_config(source.parse().unwrap(), config).unwrap();
+    assert!(matches!(&*scope.items[0].borrow(), syn::Item::Fn(_)));
+    assert!(matches!(&*scope.items[1].borrow(), syn::Item::Verbatim(_)));
+    assert!(matches!(&*scope.items[2].borrow(), syn::Item::Struct(_)));
+    assert_eq!(scope.get_marked("system").len(), 2);
+
+    let tokens = quote::ToTokens::to_token_stream(&scope).to_string();
+    assert!(tokens.contains("fn b () -> { } struct C"), "{tokens}");
+}
+
+#[test]
+fn resilient_expansion() {
+    let mut scope: MacroScope = "mod scope { #[system] struct A; #[system] struct B; }"
+        .parse()
+        .unwrap();
+    scope.register(
+        "system",
+        |marked: &macro_scope::SharedMarkedItem<syn::Item>| match macro_scope::ItemKey::of(
+            &marked.item.borrow(),
+        )
+        .name
+        .as_str()
+        {
+            "A" => Ok(quote::quote!(
+                fn a() {}
+            )),
+            _ => Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "unsupported",
+            )),
+        },
+    );
+
+    let tokens = scope.expand_resilient();
+    let expected = quote::quote! {
+        mod scope {
+            struct A;
+            struct B;
+            fn a() {}
+        }
+        ::core::compile_error! { "unsupported" }
+    };
+    assert_eq!(tokens.to_string(), expected.to_string());
+}
+
+#[test]
+fn attribute_order() {
+    let source = "mod scope {
+        /// Docs
+        #[derive(Debug)]
+        #[system]
+        #[serde(rename = \"a\")]
+        /// More docs
+        struct A;
+    }";
+    let expected: proc_macro2::TokenStream = source.replace("#[system]", "").parse().unwrap();
+
+    for scope in parse_both(source, Default::default()) {
+        let marked = scope.get_marked("system");
+        let tokens = quote::ToTokens::to_token_stream(&scope);
+        assert_eq!(
+            tokens.to_string(),
+            expected.to_string(),
+            "lazy: {}",
+            scope.config.lazy
+        );
+
+        marked[0].restore_mark();
+        let original: proc_macro2::TokenStream = source.parse().unwrap();
+        let tokens = quote::ToTokens::to_token_stream(&scope);
+        assert_eq!(
+            tokens.to_string(),
+            original.to_string(),
+            "lazy: {}",
+            scope.config.lazy
+        );
+    }
+
+    let mut scope: MacroScope = source.parse().unwrap();
+    scope.idempotent = true;
+    scope.register("system", noop);
+    let tokens = scope.expand().unwrap().to_string();
+    let order = [
+        "Docs",
+        "derive",
+        "expanded",
+        "serde",
+        "More docs",
+        "struct A",
+    ];
+    let positions: Vec<_> = order.iter().map(|s| tokens.find(s).unwrap()).collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]), "{tokens}");
+}
+
+#[test]
+fn attributes() {
+    let source =
+        "mod scope { #[deprecated] #[system] fn a() {} struct B; #[deprecated] impl B {} }";
+    for scope in parse_both(source, Default::default()) {
+        let names: Vec<_> = scope
+            .attributes()
+            .map(|(_, attr)| attr.path().get_ident().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["deprecated", "system", "deprecated"]);
+
+        let deprecated: Vec<_> = scope
+            .attributes()
+            .filter(|(_, attr)| attr.path().is_ident("deprecated"))
+            .map(|(item, _)| item)
+            .collect();
+        assert!(deprecated[1].ptr_eq(&scope.item_handles()[2]));
+    }
+}
+
+#[test]
+fn marked_params() {
+    let source = "mod scope {
+        fn handler(#[inject] db: Database, id: u32) {}
+        impl Service { fn run(&self, #[inject] log: Logger) {} }
+    }";
+    for scope in parse_both(source, Default::default()) {
+        let params = scope.get_marked_params("inject");
+        assert_eq!(params.len(), 2);
+        assert!(params[0].method.is_none());
+        assert_eq!(params[0].index, 0);
+        let ty = &params[0].ty;
+        assert_eq!(quote::quote!(#ty).to_string(), "Database");
+        assert_eq!(params[1].method.as_ref().unwrap(), "run");
+        assert_eq!(params[1].index, 1);
+        let pat = &params[1].pat;
+        assert_eq!(quote::quote!(#pat).to_string(), "log");
+
+        let tokens = quote::ToTokens::to_token_stream(&scope).to_string();
+        assert!(!tokens.contains("inject"), "{tokens}");
+    }
+}
+
+#[test]
+fn item_metadata() {
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct Layout(usize);
+
+    let mut scope: MacroScope = "mod scope { struct A; struct B; }".parse().unwrap();
+    let items = scope.item_handles();
+
+    assert_eq!(scope.set_metadata(&items[0], Layout(4)), None);
+    scope.metadata_mut::<Layout>(&items[1]).0 += 8;
+    scope.set_metadata(&items[1], "other type");
+
+    let copy = scope.clone();
+    assert_eq!(scope.metadata::<Layout>(&items[0]), Some(&Layout(4)));
+    assert_eq!(scope.take_metadata::<Layout>(&items[1]), Some(Layout(8)));
+    assert_eq!(scope.metadata::<Layout>(&items[1]), None);
+    assert_eq!(scope.metadata::<&str>(&items[1]), Some(&"other type"));
+    assert_eq!(copy.metadata::<Layout>(&items[1]), Some(&Layout(8)));
+    assert_eq!(scope.metadata::<u32>(&items[0]), None);
+}
+
+#[test]
+fn plugin_set() {
+    struct Counter(&'static str, &'static [&'static str]);
+
+    impl macro_scope::ScopePlugin for Counter {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn marks(&self) -> &[&str] {
+            self.1
+        }
+
+        fn process(&self, scope: &mut MacroScope) -> syn::Result<proc_macro2::TokenStream> {
+            let ident = quote::format_ident!("{}", self.0.to_uppercase());
+            let count = scope.get_marked(self.1[0]).len();
+            Ok(quote::quote!(const #ident: usize = #count;))
+        }
+    }
+
+    let mut scope: MacroScope = "mod scope { #[system] fn a() {} #[resource] struct B; }"
+        .parse()
+        .unwrap();
+    let plugins = macro_scope::PluginSet::new()
+        .with(Counter("systems", &["system"]))
+        .with(Counter("resources", &["resource"]));
+    assert_eq!(plugins.marks().collect::<Vec<_>>(), ["system", "resource"]);
+
+    let tokens = plugins.expand(&mut scope).unwrap();
+    let expected = quote::quote! {
+        mod scope {
+            fn a() {}
+            struct B;
+            const SYSTEMS: usize = 1usize;
+            const RESOURCES: usize = 1usize;
+        }
+    };
+    assert_eq!(tokens.to_string(), expected.to_string());
+
+    let conflicting = macro_scope::PluginSet::new()
+        .with(Counter("a", &["system"]))
+        .with(Counter("b", &["system"]));
+    assert_eq!(
+        conflicting.run(&mut scope).unwrap_err().to_string(),
+        "`#[system]` is handled by both `a` and `b`"
+    );
+}
+
+#[test]
+fn caught_panics() {
+    let mut scope: MacroScope = "mod scope { #[system] struct A; #[system] struct B; }"
+        .parse()
+        .unwrap();
+    scope.catch_panics = true;
+    scope.register(
+        "system",
+        |marked: &macro_scope::SharedMarkedItem<syn::Item>| match macro_scope::ItemKey::of(
+            &marked.item.borrow(),
+        )
+        .name
+        .as_str()
+        {
+            "A" => Ok(quote::quote!(
+                fn a() {}
+            )),
+            name => panic!("can't expand {name}"),
+        },
+    );
+
+    let tokens = scope.expand_resilient().to_string();
+    assert!(tokens.contains("fn a () { }"), "{tokens}");
+    assert!(
+        tokens.contains("the template of `#[system]` panicked on struct B: can't expand B"),
+        "{tokens}"
+    );
+}
+
+#[test]
+fn diagnostics_bridge() {
+    let error = |message| syn::Error::new(proc_macro2::Span::call_site(), message);
+
+    let mut diagnostics: macro_scope::Diagnostics = [error("a"), error("b")].into_iter().collect();
+    diagnostics.extend([error("c")]);
+    diagnostics.emit(proc_macro2::Span::call_site(), "d");
+
+    let mut emitted = Vec::new();
+    diagnostics.emit_each(|e| emitted.push(e.to_string()));
+    assert_eq!(emitted, ["a", "b", "c", "d"]);
+}
+
+#[test]
+fn analysis() {
+    use macro_scope::ArgValue;
+
+    let mut scope: MacroScope = "mod scope {
+        #[table(index(a), index(b), ratio = 1., count = 01, huge = 1e999)] struct A;
+        impl Clone for A { fn clone(&self) -> Self { A } }
+    }"
+    .parse()
+    .unwrap();
+    scope.register("table", noop);
+
+    let analysis = scope.analysis();
+    assert_eq!(analysis.module.as_deref(), Some("scope"));
+    assert_eq!(analysis.items[0].ident.as_deref(), Some("A"));
+    assert_eq!(analysis.items[1].kind, "impl");
+    assert_eq!(analysis.impls[0].trait_path.as_deref(), Some("Clone"));
+
+    let attr = &analysis.items[0].attributes[0];
+    assert!(attr.mark);
+    assert_eq!(attr.name, "table");
+    let Some(ArgValue::Object(args)) = &attr.args else {
+        panic!("expected an object, got {:?}", attr.args);
+    };
+    let object = |key: &str| ArgValue::Object([(key.to_string(), ArgValue::Bool(true))].into());
+    assert_eq!(
+        args["index"],
+        ArgValue::Array(vec![object("a"), object("b")])
+    );
+    assert_eq!(args["ratio"], ArgValue::Float(1.0));
+    assert_eq!(args["count"], ArgValue::Int(1));
+    assert_eq!(args["huge"], ArgValue::Str("1e999".to_string()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn analysis_json() {
+    let mut scope: MacroScope =
+        "mod scope { #[table(index(a), index(b), ratio = 1., count = 01)] struct A; }"
+            .parse()
+            .unwrap();
+    scope.register("table", noop);
+
+    let json = scope.analysis_json();
+    assert!(
+        json.contains(r#""index":[{"a":true},{"b":true}]"#),
+        "{json}"
+    );
+    assert!(json.contains(r#""ratio":1.0"#), "{json}");
+    assert!(json.contains(r#""count":1"#), "{json}");
+    assert!(json.contains(r#""name":"table","mark":true"#), "{json}");
+
+    let scope: MacroScope = "mod scope { impl Clone for A {} }".parse().unwrap();
+    let json = scope.analysis_json();
+    assert!(
+        json.ends_with(r#""impls":[{"self":"A","trait":"Clone"}]}"#),
+        "{json}"
+    );
+}
+
+#[test]
+fn analysis_lazy() {
+    let source = "mod scope { #[system] fn a() {} #[system] fn b() -> {} }";
+    let config = macro_scope::ScopeConfig {
+        lazy: true,
+        ..Default::default()
+    };
+    let scope = MacroScope::parse_with_config(source.parse().unwrap(), config).unwrap();
+
+    let analysis = scope.analysis();
+    let names: Vec<_> = analysis
+        .items
+        .iter()
+        .map(|item| (item.kind, item.attributes[0].name.as_str()))
+        .collect();
+    assert_eq!(names, [("fn", "system"), ("verbatim item", "system")]);
+}
+
+#[test]
+fn mark_locations() {
+    let scope: MacroScope = "mod scope {\n    #[system] fn a() {}\n  #[system] fn b() {}\n}"
+        .parse()
+        .unwrap();
+
+    let locations: Vec<_> = scope
+        .get_marked("system")
+        .iter()
+        .map(|marked| {
+            let location = marked.location();
+            (location.line, location.column)
+        })
+        .collect();
+    assert_eq!(locations, [(2, 4), (3, 2)]);
+}
+
+#[test]
+fn ident_index_after_handle_rename() {
+    let scope: MacroScope = "mod scope { struct Old; }".parse().unwrap();
+    let item = scope.find("Old").remove(0);
+
+    item.write(|item| {
+        if let syn::Item::Struct(s) = item {
+            s.ident = quote::format_ident!("New");
+        }
+    });
+    assert!(scope.find("Old").is_empty());
+    assert!(scope.find("New")[0].ptr_eq(&item));
+}
+
+#[test]
+fn from_conversions() {
+    let scope: MacroScope = "mod scope {
+        #[dto] struct UserDto { id: u32, name: String }
+        #[entity] struct UserEntity { id: u32, name: String, created: u64 }
+        #[dto] struct GroupDto { id: u32 }
+        #[entity] struct Group { id: u32 }
+        #[dto(with = Account)] struct MemberDto { id: u32 }
+        #[entity] struct Account { id: u32, name: String }
+    }"
+    .parse()
+    .unwrap();
+
+    let expected = quote::quote! {
+        impl ::core::convert::From<UserEntity> for UserDto {
+            fn from(value: UserEntity) -> Self {
+                Self { id: value.id, name: value.name, }
+            }
+        }
+        impl ::core::convert::From<GroupDto> for Group {
+            fn from(value: GroupDto) -> Self {
+                Self { id: value.id, }
+            }
+        }
+        impl ::core::convert::From<Group> for GroupDto {
+            fn from(value: Group) -> Self {
+                Self { id: value.id, }
+            }
+        }
+        impl ::core::convert::From<Account> for MemberDto {
+            fn from(value: Account) -> Self {
+                Self { id: value.id, }
+            }
+        }
+    };
+    let tokens = scope.from_conversions("dto", "entity").unwrap();
+    assert_eq!(tokens.to_string(), expected.to_string());
+    // the marks are removed from the paired structs
+    assert!(scope.get_marked("dto").is_empty());
+
+    let error = |source: &str| {
+        let scope: MacroScope = source.parse().unwrap();
+        scope
+            .from_conversions("dto", "entity")
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error("mod scope { #[dto] struct UserDto { id: u32 } #[entity] struct User { key: u32 } }"),
+        "no conversion between `UserDto` and `User` possible, unmatched fields `id` in `UserDto` \
+         and `key` in `User`"
+    );
+    assert_eq!(
+        error("mod scope { #[dto] struct UserDto<T> { id: T } #[entity] struct User { id: u32 } }"),
+        "generic structs are not supported"
+    );
+    assert_eq!(
+        error("mod scope { #[dto] struct UserDto(u32); #[entity] struct User { id: u32 } }"),
+        "only structs with named fields are supported"
+    );
+
+    // structs without a partner are reported on either side
+    assert_eq!(
+        error("mod scope { #[dto] struct OrphanDto { id: u32 } }"),
+        "no struct marked with `#[entity]` to pair `OrphanDto` with, name it like this struct or \
+         add `with = Other` to the mark"
+    );
+    assert_eq!(
+        error("mod scope { #[entity(with = UserDto)] struct User { id: u32 } }"),
+        "no struct `UserDto` is marked with `#[dto]`"
+    );
+    // the name of the mark is only stripped as a suffix
+    assert!(error(
+        "mod scope { #[dto] struct DtoUser { id: u32 } #[entity] struct User { id: u32 } }"
+    )
+    .contains("to pair `DtoUser` with"));
+
+    // a requested direction reports the fields it can't convert
+    let source = "mod scope {
+        #[dto(into)] struct UserDto { id: u32 }
+        #[entity] struct User { id: u32, created: u64 }
+    }";
+    let scope: MacroScope = source.parse().unwrap();
+    let error = scope.from_conversions("dto", "entity").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "`UserDto` has no field `created` to convert into `User`"
+    );
+    assert_eq!(error.span().start().line, 3);
+
+    let scope: MacroScope = source.replace("into", "from").parse().unwrap();
+    let tokens = scope.from_conversions("dto", "entity").unwrap().to_string();
+    assert!(tokens.contains("From < User > for UserDto"));
+    assert!(!tokens.contains("From < UserDto > for User"));
+}
+
+#[test]
+fn enum_dispatch() {
+    let scope: MacroScope = "mod scope {
+        #[dispatch]
+        pub trait Shape {
+            fn area(&self) -> f64;
+            fn scale(&mut self, by: f64);
+        }
+        #[shape] struct Circle { r: f64 }
+        #[shape] struct Square(f64);
+    }"
+    .parse()
+    .unwrap();
+
+    let expected = quote::quote! {
+        pub enum ShapeEnum {
+            Circle(Circle),
+            Square(Square),
+        }
+
+        impl Shape for ShapeEnum {
+            fn area(&self) -> f64 {
+                match self {
+                    Self::Circle(v) => v.area(),
+                    Self::Square(v) => v.area(),
+                }
+            }
+            fn scale(&mut self, __arg1: f64) {
+                match self {
+                    Self::Circle(v) => v.scale(__arg1),
+                    Self::Square(v) => v.scale(__arg1),
+                }
+            }
+        }
+
+        impl ::core::convert::From<Circle> for ShapeEnum {
+            fn from(value: Circle) -> Self {
+                Self::Circle(value)
+            }
+        }
+        impl ::core::convert::From<Square> for ShapeEnum {
+            fn from(value: Square) -> Self {
+                Self::Square(value)
+            }
+        }
+    };
+    let normalize =
+        |tokens: proc_macro2::TokenStream| prettyplease::unparse(&syn::parse2(tokens).unwrap());
+    assert_eq!(
+        normalize(scope.enum_dispatch("dispatch", "shape").unwrap()),
+        normalize(expected)
+    );
+
+    let error = |source: &str| {
+        let scope: MacroScope = source.parse().unwrap();
+        let error = scope.enum_dispatch("dispatch", "shape").unwrap_err();
+        error.into_iter().map(|e| e.to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        error("mod scope { #[dispatch] trait T { fn f(&self); } #[shape] enum E { A(u8) } }"),
+        ["`#[shape]` can only be used on structs"]
+    );
+    assert_eq!(
+        error("mod scope { #[dispatch] trait T { fn f(&self); } #[shape] struct S<X>(X); }"),
+        ["generic variants are not supported"]
+    );
+    assert_eq!(
+        error(
+            "mod scope {
+                #[dispatch] trait T { fn new() -> Self; const N: u8; }
+                #[shape] struct S;
+            }"
+        ),
+        [
+            "methods without `self` can't be dispatched",
+            "only methods can be dispatched"
+        ]
+    );
+}
+
+#[test]
+fn emit_only_marked() {
+    let mut scope: MacroScope = "mod scope { #[system] fn a() {} #[keep] struct B; struct C; }"
+        .parse()
+        .unwrap();
+    scope.register("system", |_: &macro_scope::SharedMarkedItem<syn::Item>| {
+        Ok(quote::quote!(
+            const SYSTEM: () = ();
+        ))
+    });
+    scope.emit_mode = macro_scope::EmitMode::OnlyMarked(vec!["system".into(), "keep".into()]);
+
+    let expected = quote::quote! {
+        fn a() {}
+        struct B;
+        const SYSTEM: () = ();
+    };
+    assert_eq!(scope.expand().unwrap().to_string(), expected.to_string());
+}
+
+#[test]
+fn items_by_mark_and_kind() {
+    use macro_scope::ItemKind;
+
+    let scope: MacroScope = "mod scope {
+        #[system] fn a() {}
+        #[system] struct B;
+        #[system] fn c() {}
+        #[render_system] fn d() {}
+        struct E;
+    }"
+    .parse()
+    .unwrap();
+
+    let grouped = scope.get_items_by_mark_and_kind("system");
+    let names = |key: (&str, ItemKind)| -> Vec<String> {
+        grouped[&(key.0.to_string(), key.1)]
+            .iter()
+            .map(|m| macro_scope::ItemKey::of(&m.item.borrow()).name)
+            .collect()
+    };
+    assert_eq!(grouped.len(), 3);
+    assert_eq!(names(("system", ItemKind::Fn)), ["a", "c"]);
+    assert_eq!(names(("system", ItemKind::Struct)), ["B"]);
+    assert_eq!(names(("render_system", ItemKind::Fn)), ["d"]);
+}
+
+#[test]
+fn items_with_all() {
+    let scope: MacroScope = "mod scope {
+        #[system] #[parallel] fn a() {}
+        #[system] fn b() {}
+        #[parallel] fn c() {}
+        #[parallel] #[render_system] fn d() {}
+    }"
+    .parse()
+    .unwrap();
+
+    let names = |items: Vec<std::rc::Rc<std::cell::RefCell<syn::Item>>>| -> Vec<String> {
+        items
+            .iter()
+            .map(|item| macro_scope::ItemKey::of(&item.borrow()).name)
+            .collect()
+    };
+    assert_eq!(
+        names(scope.items_with_all(&["system", "parallel"])),
+        ["a", "d"]
+    );
+    assert_eq!(names(scope.items_with_all(&["system"])), ["a", "b", "d"]);
+    assert_eq!(names(scope.items_with_all(&[])), ["a", "b", "c", "d"]);
+    assert!(scope.items_with_all(&["system", "missing"]).is_empty());
+    // the marks are left on the items
+    assert_eq!(scope.get_marked("parallel").len(), 3);
+}
+
+#[test]
+fn items_without() {
+    use macro_scope::ItemKind;
+
+    let config = macro_scope::ScopeConfig {
+        lazy: true,
+        ..Default::default()
+    };
+    let scope = MacroScope::parse_with_config(
+        "mod scope {
+            #[system] fn a() {}
+            fn b() {}
+            struct C;
+            #[system] struct D;
+        }"
+        .parse()
+        .unwrap(),
+        config,
+    )
+    .unwrap();
+
+    let names = |items: Vec<std::rc::Rc<std::cell::RefCell<syn::Item>>>| -> Vec<String> {
+        items
+            .iter()
+            .map(|item| macro_scope::ItemKey::of(&item.borrow()).name)
+            .collect()
+    };
+    let unfiltered = scope.items_without("system", None);
+    assert_eq!(unfiltered.len(), 2);
+    assert!(matches!(*unfiltered[0].borrow(), syn::Item::Verbatim(_)));
+
+    // filtering by kind parses the items
+    assert_eq!(
+        names(scope.items_without("system", Some(ItemKind::Fn))),
+        ["b"]
+    );
+    assert_eq!(names(unfiltered), ["b", "C"]);
+    assert_eq!(
+        names(scope.items_without("system", Some(ItemKind::Struct))),
+        ["C"]
+    );
+    assert!(scope
+        .items_without("system", Some(ItemKind::Enum))
+        .is_empty());
+}
+
+#[test]
+fn unknown_mark_suggestion() {
+    let source = "mod scope {
+        #[derive(Debug)] #[compnent] struct A;
+        #[component] struct B;
+        #[inline] fn c() {}
+    }";
+    let config = macro_scope::ScopeConfig {
+        strict: true,
+        ..Default::default()
+    };
+    for mut scope in parse_both(source, config) {
+        scope.register("component", noop);
+
+        let error = scope.expand().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown mark `compnent`, did you mean `component`?"
+        );
+        assert_eq!(error.into_iter().count(), 1);
+    }
+
+    let source = "mod scope {
+        /// Docs
+        #[docs] struct A;
+        #[test] #[text] fn b() {}
+        #[serde::text_] struct C;
+    }";
+    let config = macro_scope::ScopeConfig {
+        strict: true,
+        ..Default::default()
+    };
+    for mut scope in parse_both(source, config) {
+        scope.register("docs", noop);
+        scope.register("text", noop);
+        assert!(scope.expand().is_ok());
+    }
+}
+
+#[test]
+fn replace_marks() {
+    let source = "mod scope {
+        #[derive(Debug)] #[internal] #[repr(C)] struct A;
+        #[inline] fn b() {}
+    }";
+    let config = macro_scope::ScopeConfig {
+        mark_policy: macro_scope::MarkPolicy::ReplaceWith(Box::new(syn::parse_quote!(
+            #[doc(hidden)]
+        ))),
+        ..Default::default()
+    };
+    for scope in parse_both(source, config) {
+        let marked = scope.get_marked("internal");
+        assert_eq!(marked.len(), 1);
+        assert_eq!(marked[0].index, 1);
+
+        let expected = quote::quote! {
+            mod scope {
+                #[derive(Debug)] #[doc(hidden)] #[repr(C)] struct A;
+                #[inline] fn b() {}
+            }
+        };
+        assert_eq!(quote::quote!(#scope).to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn attributes_on_generated_items() {
+    use macro_scope::emit::{with_attr, with_cfg};
+
+    let cfg: macro_scope::MetaTree = syn::parse_str("cfg(feature = \"net\")").unwrap();
+    let tokens = with_cfg(
+        &cfg,
+        quote::quote!(
+            struct A;
+            fn b() {}
+        ),
+    )
+    .unwrap();
+    let expected = quote::quote! {
+        #[cfg(feature = "net")] struct A;
+        #[cfg(feature = "net")] fn b() {}
+    };
+    assert_eq!(tokens.to_string(), expected.to_string());
+
+    let error = with_cfg(
+        &cfg,
+        quote::quote!(
+            struct A;
+            fn declared();
+        ),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "can't add `#[cfg (feature = \"net\")]` to an item syn can't parse"
+    );
+    let error = with_attr(
+        &syn::parse_quote!(#[inline]),
+        quote::quote!(
+            fn declared();
+        ),
+    );
+    assert!(error.is_err());
+}
+
+#[test]
+fn transaction_rollback() {
+    let mut scope: MacroScope = "mod scope { #[system] fn a() {} }".parse().unwrap();
+    scope.journaling = true;
+    let item = scope.item_handles().remove(0);
+    scope.gensym("x");
+
+    let mut generated = None;
+    let result = scope.transaction(|scope| {
+        scope.get_marked("system");
+        scope.config.strict = true;
+        scope.use_path(syn::parse_quote!(std::fmt));
+        scope.register("system", noop);
+        scope.set_metadata(&item, 1u8);
+        generated = Some(scope.gensym("x"));
+        Err::<(), _>(syn::Error::new(proc_macro2::Span::call_site(), "invalid"))
+    });
+    assert!(result.is_err());
+
+    assert!(!scope.config.strict);
+    assert!(scope.prelude.is_empty());
+    assert_eq!(scope.marks().count(), 0);
+    assert_eq!(scope.metadata::<u8>(&item), None);
+    assert_eq!(Some(scope.gensym("x")), generated);
+    assert_eq!(scope.get_marked("system").len(), 1);
+
+    let journal: Vec<_> = scope.journal().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        journal,
+        [
+            "removed `#[system]` from fn a",
+            "rolled back a transaction",
+            "removed `#[system]` from fn a"
+        ]
+    );
+}
+
+#[test]
+fn unmarked_items_are_not_borrowed_mutably() {
+    let source = "mod scope { #[system] fn a() {} struct B; fn declared(); }";
+    for scope in parse_both(source, Default::default()) {
+        let unmarked: Vec<_> = scope.items[1..].iter().map(|item| item.borrow()).collect();
+        assert_eq!(scope.get_marked("system").len(), 1);
+        assert!(scope.get_marked("other").is_empty());
+        drop(unmarked);
+    }
+}
+
+#[test]
+fn hidden_module_restricted_visibility() {
+    let tokens = quote::quote! {
+        pub(super) fn a() {}
+        pub(in super::super) struct B;
+        pub(crate) struct C;
+        pub(self) struct D;
+    };
+    let module = quote::format_ident!("__generated");
+    let tokens = macro_scope::emit::in_hidden_module(&module, tokens).unwrap();
+    let expected = quote::quote! {
+        #[doc(hidden)]
+        mod __generated {
+            #[allow(unused_imports)]
+            use super::*;
+            pub(in super::super) fn a() {}
+            pub(in super::super::super) struct B;
+            pub(crate) struct C;
+            pub(in super) struct D;
+        }
+        pub(super) use __generated::a;
+        pub(in super::super) use __generated::B;
+        pub(crate) use __generated::C;
+        pub(self) use __generated::D;
+    };
+    assert_eq!(tokens.to_string(), expected.to_string());
+}